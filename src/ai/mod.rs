@@ -0,0 +1,233 @@
+use bevy::prelude::*;
+
+use crate::{
+    controls::selection::{Selectable, SelectionMask},
+    entities::{structures::Structure, units::{behaviour::GatherOrder, orders::{AttackOrder, MoveOrder}, Unit}, world_objects::ResourceNode, Owner},
+    network::{client::ServerConnection, server::GameServer, session::NetworkSession},
+    resources::{player::{PlayerId, Stockpiles}, settings::AiDifficulty},
+    simulation::{commands::{IssueCommandEvent, PlayerCommand}, replay::ReplayPlayback, SimId, SimulationSet, SimulationTick},
+    states::{match_rules::{setup_match, ActiveMatchRules}, InMatch},
+};
+use skirmish::SkirmishAi;
+
+pub mod skirmish;
+
+const AI_ARG: &str = "--ai";
+// Seat the lobby's computer opponent takes
+pub const AI_PLAYER: PlayerId = 1;
+// Distance within which a player sees enemy entities
+pub const SIGHT_RANGE: f32 = 15.;
+
+// One of the player's own units, or an enemy one in sight
+#[derive(Clone, Copy, Debug)]
+pub struct AiUnit {
+    pub sim_id: SimId,
+    pub owner: PlayerId,
+    pub position: Vec2,
+    // Has no move, attack or gather order
+    pub idle: bool,
+    pub gathering: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AiStructure {
+    pub sim_id: SimId,
+    pub owner: PlayerId,
+    pub position: Vec2,
+    pub headquarters: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AiResourceNode {
+    pub sim_id: SimId,
+    pub position: Vec2,
+}
+
+// What a computer player knows about the match when it gets to act, everything sorted by SimId.
+// Enemies only show up while something of the player's is close enough to see them.
+#[derive(Clone, Debug, Default)]
+pub struct AiView {
+    pub tick: u64,
+    pub player: PlayerId,
+    pub stockpile: u32,
+    pub population_cap: u32,
+    pub units: Vec<AiUnit>,
+    pub structures: Vec<AiStructure>,
+    pub enemy_units: Vec<AiUnit>,
+    pub enemy_structures: Vec<AiStructure>,
+    pub resource_nodes: Vec<AiResourceNode>,
+}
+
+impl AiView {
+    pub fn headquarters(&self) -> Option<&AiStructure> {
+        self.structures.iter().find(|structure| structure.headquarters)
+            .or(self.structures.first())
+    }
+}
+
+// Strategy of a computer player. It plays through the same commands as people do, so anything it
+// does shows up in replays and stats the same way.
+pub trait AiController: Send + Sync {
+    fn name(&self) -> &str;
+
+    // Ticks between decisions
+    fn interval(&self) -> u64 {
+        15
+    }
+
+    // Forget everything about the previous match
+    fn reset(&mut self) {}
+
+    fn think(&mut self, view: &AiView) -> Vec<PlayerCommand>;
+}
+
+pub struct AiPlayer {
+    pub player: PlayerId,
+    pub controller: Box<dyn AiController>,
+    // Added for the lobby's opponent setting, replaced every match
+    from_settings: bool,
+}
+
+// Computer players in the next or current match. Controllers added here stay for every match,
+// the lobby's opponent is added when a match starts.
+#[derive(Default, Resource)]
+pub struct AiPlayers {
+    pub players: Vec<AiPlayer>,
+}
+
+impl AiPlayers {
+    pub fn add(&mut self, player: PlayerId, controller: impl AiController + 'static) {
+        self.players.retain(|ai| ai.player != player);
+        self.players.push(AiPlayer {
+            player,
+            controller: Box::new(controller),
+            from_settings: false,
+        });
+    }
+
+    // None unless --ai <difficulty>[,<difficulty>] was passed, giving every player in turn a
+    // skirmish AI of that difficulty, e.g. for AI against AI matches in headless runs
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let position = args.iter().position(|arg| arg == AI_ARG)?;
+        let mut ai_players = AiPlayers::default();
+        let names = args.get(position + 1).map_or("", |names| names.as_str());
+        for (player, name) in names.split(',').enumerate() {
+            match AiDifficulty::from_name(name) {
+                Some(difficulty) => ai_players.add(player as PlayerId, SkirmishAi::new(difficulty)),
+                None => println!("Ignoring {} {}, expected easy, normal or hard", AI_ARG, name),
+            }
+        }
+        (!ai_players.players.is_empty()).then_some(ai_players)
+    }
+}
+
+// Computer players for skirmishes and AI experiments
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AiPlayers>()
+            // The lobby's opponent is part of the rules the match was started with
+            .add_systems(OnEnter(InMatch), setup_ai_players.after(setup_match))
+            // Replays already hold the commands the AI gave
+            .add_systems(FixedUpdate, run_ai_players
+                .in_set(SimulationSet::Logic)
+                .run_if(not(resource_exists::<ReplayPlayback>)));
+    }
+}
+
+pub fn setup_ai_players(
+    rules: Res<ActiveMatchRules>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    server: Option<Res<GameServer>>,
+    mut ai_players: ResMut<AiPlayers>,
+) {
+    ai_players.players.retain(|ai| !ai.from_settings);
+    // The second seat belongs to another person in networked matches and on dedicated servers
+    let local = session.is_none() && connection.is_none() && server.is_none();
+    if let Some(difficulty) = rules.game.opponent.filter(|_| local) {
+        if !ai_players.players.iter().any(|ai| ai.player == AI_PLAYER) {
+            ai_players.players.push(AiPlayer {
+                player: AI_PLAYER,
+                controller: Box::new(SkirmishAi::new(difficulty)),
+                from_settings: true,
+            });
+        }
+    }
+    for ai in ai_players.players.iter_mut() {
+        ai.controller.reset();
+        println!("Player {} is played by the {} AI", ai.player, ai.controller.name());
+    }
+}
+
+pub fn run_ai_players(
+    tick: Res<SimulationTick>,
+    rules: Res<ActiveMatchRules>,
+    stockpiles: Res<Stockpiles>,
+    mut ai_players: ResMut<AiPlayers>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_units: Query<(&SimId, &Owner, &Transform, Has<MoveOrder>, Has<AttackOrder>, Has<GatherOrder>), With<Unit>>,
+    q_structures: Query<(&SimId, &Owner, &Transform, Option<&Selectable>), With<Structure>>,
+    q_resource_nodes: Query<(&SimId, &Transform), With<ResourceNode>>,
+) {
+    if ai_players.players.is_empty() {
+        return;
+    }
+    let mut units: Vec<AiUnit> = q_units.iter()
+        .map(|(sim_id, owner, transform, moving, attacking, gathering)| AiUnit {
+            sim_id: *sim_id,
+            owner: owner.0,
+            position: transform.translation.xz(),
+            idle: !moving && !attacking && !gathering,
+            gathering,
+        })
+        .collect();
+    units.sort_by_key(|unit| unit.sim_id);
+    let mut structures: Vec<AiStructure> = q_structures.iter()
+        .map(|(sim_id, owner, transform, selectable)| AiStructure {
+            sim_id: *sim_id,
+            owner: owner.0,
+            position: transform.translation.xz(),
+            headquarters: selectable.is_some_and(|selectable| matches!(selectable.selection_mask, SelectionMask::Hq)),
+        })
+        .collect();
+    structures.sort_by_key(|structure| structure.sim_id);
+    let mut resource_nodes: Vec<AiResourceNode> = q_resource_nodes.iter()
+        .map(|(sim_id, transform)| AiResourceNode {
+            sim_id: *sim_id,
+            position: transform.translation.xz(),
+        })
+        .collect();
+    resource_nodes.sort_by_key(|node| node.sim_id);
+
+    for ai in ai_players.players.iter_mut() {
+        if tick.0 % ai.controller.interval().max(1) != 0 {
+            continue;
+        }
+        let player = ai.player;
+        let own_units: Vec<AiUnit> = units.iter().filter(|unit| unit.owner == player).copied().collect();
+        let own_structures: Vec<AiStructure> = structures.iter().filter(|structure| structure.owner == player).copied().collect();
+        let in_sight = |position: Vec2| own_units.iter().map(|unit| unit.position)
+            .chain(own_structures.iter().map(|structure| structure.position))
+            .any(|own| own.distance(position) <= SIGHT_RANGE);
+        let view = AiView {
+            tick: tick.0,
+            player,
+            stockpile: stockpiles.get(player),
+            population_cap: rules.game.population_cap,
+            enemy_units: units.iter().filter(|unit| unit.owner != player && in_sight(unit.position)).copied().collect(),
+            enemy_structures: structures.iter().filter(|structure| structure.owner != player && in_sight(structure.position)).copied().collect(),
+            resource_nodes: resource_nodes.clone(),
+            units: own_units,
+            structures: own_structures,
+        };
+        for command in ai.controller.think(&view) {
+            ev_issue.send(IssueCommandEvent {
+                player,
+                command,
+            });
+        }
+    }
+}
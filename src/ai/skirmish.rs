@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+
+use crate::{
+    entities::{structures::{is_valid_site, STRUCTURE_COST}, units::UNIT_COST},
+    resources::settings::AiDifficulty,
+    simulation::{commands::PlayerCommand, fixed::FixedVec2, SimId},
+};
+
+use super::{AiController, AiUnit, AiView, SIGHT_RANGE};
+
+// Resource nodes with one of the player's structures this close are already taken
+const EXPANSION_RADIUS: f32 = 8.;
+// Expansions are built this far from the node, towards home, leaving it the room a structure needs
+const EXPANSION_OFFSET: f32 = 5.;
+// Structures built at home go around the HQ at this distance
+const BASE_RADIUS: f32 = 8.;
+// Idle units near home fight back against enemies this close without waiting for the rest of the
+// army, out in the field they attack whatever they see
+const ENGAGE_RANGE: f32 = 10.;
+// Enemies this close to home are attacked by everything that is idle
+const DEFEND_RANGE: f32 = 20.;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BuildStep {
+    Train,
+    // Build a structure at the nearest free resource node, or at home once there are none
+    Expand,
+}
+
+// How well a difficulty plays
+#[derive(Clone, Copy, Debug)]
+pub struct Profile {
+    // Ticks between decisions
+    pub interval: u64,
+    // Build order steps taken per decision at most
+    pub actions: usize,
+    // Opening, after which units are trained for as long as there are resources
+    pub build_order: &'static [BuildStep],
+    // Idle units needed before attacking, grows by one with every attack
+    pub attack_wave: usize,
+    // Units kept gathering resources
+    pub gatherers: usize,
+    pub scouts: bool,
+}
+
+impl Profile {
+    pub fn for_difficulty(difficulty: AiDifficulty) -> Self {
+        use BuildStep::*;
+        match difficulty {
+            AiDifficulty::Easy => Profile {
+                interval: 90,
+                actions: 1,
+                build_order: &[Train, Train, Expand, Train, Train],
+                attack_wave: 10,
+                gatherers: 2,
+                scouts: false,
+            },
+            AiDifficulty::Normal => Profile {
+                interval: 45,
+                actions: 2,
+                build_order: &[Train, Expand, Train, Train, Train, Expand],
+                attack_wave: 8,
+                gatherers: 3,
+                scouts: true,
+            },
+            AiDifficulty::Hard => Profile {
+                interval: 15,
+                actions: 3,
+                build_order: &[Train, Train, Expand, Train, Train, Expand, Train, Expand],
+                attack_wave: 6,
+                gatherers: 4,
+                scouts: true,
+            },
+        }
+    }
+}
+
+// Follows a build order, sends a scout to find the enemy base and attacks it in waves
+pub struct SkirmishAi {
+    difficulty: AiDifficulty,
+    profile: Profile,
+    step: usize,
+    scout: Option<SimId>,
+    scout_target: usize,
+    // Structure marking the enemy base once scouted
+    enemy_base: Option<(SimId, Vec2)>,
+    attacks: usize,
+    // Units given an order this decision, so the steps after don't take them too
+    assigned: Vec<SimId>,
+}
+
+impl SkirmishAi {
+    pub fn new(difficulty: AiDifficulty) -> Self {
+        SkirmishAi::with_profile(difficulty, Profile::for_difficulty(difficulty))
+    }
+
+    pub fn with_profile(difficulty: AiDifficulty, profile: Profile) -> Self {
+        SkirmishAi {
+            difficulty,
+            profile,
+            step: 0,
+            scout: None,
+            scout_target: 0,
+            enemy_base: None,
+            attacks: 0,
+            assigned: Vec::new(),
+        }
+    }
+
+    pub fn enemy_base(&self) -> Option<(SimId, Vec2)> {
+        self.enemy_base
+    }
+
+    // Where to put the next structure, None without a home to build around or room left there
+    fn expansion_site(&self, view: &AiView) -> Option<Vec2> {
+        let home = view.headquarters()?.position;
+        let mut nodes: Vec<Vec2> = view.resource_nodes.iter().map(|node| node.position).collect();
+        nodes.sort_by(|a, b| a.distance(home).total_cmp(&b.distance(home)));
+        let free_node = nodes.into_iter().find(|node| !view.structures.iter()
+            .any(|structure| structure.position.distance(*node) <= EXPANSION_RADIUS));
+        if let Some(node) = free_node {
+            return Some(node + (home - node).normalize_or_zero() * EXPANSION_OFFSET);
+        }
+        // Spread around the HQ, one eighth of a turn apart, skipping spots without room
+        let obstacles: Vec<FixedVec2> = view.structures.iter().map(|structure| structure.position)
+            .chain(view.resource_nodes.iter().map(|node| node.position))
+            .map(FixedVec2::from_vec2)
+            .collect();
+        (0..8)
+            .map(|turn| (view.structures.len() + turn) as f32 * std::f32::consts::FRAC_PI_4)
+            .map(|angle| home + Vec2::from_angle(angle) * BASE_RADIUS)
+            .find(|site| is_valid_site(FixedVec2::from_vec2(*site), obstacles.iter().copied()))
+    }
+
+    // Idle units that are neither scouting nor given something else to do this decision
+    fn free_units<'a>(&self, view: &'a AiView) -> Vec<&'a AiUnit> {
+        view.units.iter()
+            .filter(|unit| unit.idle && Some(unit.sim_id) != self.scout && !self.assigned.contains(&unit.sim_id))
+            .collect()
+    }
+
+    // Spend on the build order while it can be afforded
+    fn build(&mut self, view: &AiView, commands: &mut Vec<PlayerCommand>) {
+        let mut stockpile = view.stockpile;
+        let mut population = view.units.len() as u32;
+        for _ in 0..self.profile.actions {
+            let step = self.profile.build_order.get(self.step).copied().unwrap_or(BuildStep::Train);
+            match step {
+                BuildStep::Train => {
+                    if stockpile < UNIT_COST || population >= view.population_cap || view.structures.is_empty() {
+                        return;
+                    }
+                    let structure = &view.structures[self.step % view.structures.len()];
+                    commands.push(PlayerCommand::Train { structure: structure.sim_id });
+                    stockpile -= UNIT_COST;
+                    population += 1;
+                },
+                BuildStep::Expand => {
+                    if stockpile < STRUCTURE_COST {
+                        return;
+                    }
+                    let Some(site) = self.expansion_site(view) else { return; };
+                    // Taken from the back, the scout and gatherers come from the front
+                    let builder = self.free_units(view).last().copied()
+                        .or_else(|| view.units.iter().rev().find(|unit| unit.gathering && !self.assigned.contains(&unit.sim_id)));
+                    let Some(builder) = builder.map(|unit| unit.sim_id) else { return; };
+                    commands.push(PlayerCommand::Build { position: FixedVec2::from_vec2(site), units: vec![builder], queued: false });
+                    self.assigned.push(builder);
+                    stockpile -= STRUCTURE_COST;
+                },
+            }
+            self.step += 1;
+        }
+    }
+
+    // Keep enough units gathering at the node nearest home
+    fn gather(&mut self, view: &AiView, commands: &mut Vec<PlayerCommand>) {
+        let Some(home) = view.headquarters().map(|structure| structure.position) else { return; };
+        let node = view.resource_nodes.iter()
+            .min_by(|a, b| a.position.distance(home).total_cmp(&b.position.distance(home)));
+        let Some(node) = node else { return; };
+        let gathering = view.units.iter().filter(|unit| unit.gathering).count();
+        let workers: Vec<SimId> = self.free_units(view).into_iter()
+            .take(self.profile.gatherers.saturating_sub(gathering))
+            .map(|unit| unit.sim_id)
+            .collect();
+        if workers.is_empty() {
+            return;
+        }
+        self.assigned.extend(workers.iter().copied());
+        commands.push(PlayerCommand::Gather { units: workers, node: node.sim_id, queued: false });
+    }
+
+    // Places the enemy base is likely at: resource nodes, then across the map from home
+    fn scout_points(&self, view: &AiView) -> Vec<Vec2> {
+        let mut points: Vec<Vec2> = view.resource_nodes.iter().map(|node| node.position).collect();
+        if let Some(home) = view.headquarters() {
+            points.retain(|point| point.distance(home.position) > EXPANSION_RADIUS);
+            points.push(-home.position);
+        }
+        points.push(Vec2::ZERO);
+        points
+    }
+
+    fn scout(&mut self, view: &AiView, commands: &mut Vec<PlayerCommand>) {
+        if !self.profile.scouts || self.enemy_base.is_some() {
+            self.scout = None;
+            return;
+        }
+        let scout = self.scout.and_then(|sim_id| view.units.iter().find(|unit| unit.sim_id == sim_id))
+            .or_else(|| self.free_units(view).first().copied());
+        let Some(scout) = scout else { return; };
+        self.scout = Some(scout.sim_id);
+        if !scout.idle {
+            return;
+        }
+        let points = self.scout_points(view);
+        let target = points[self.scout_target % points.len()];
+        self.scout_target += 1;
+        commands.push(PlayerCommand::Move { units: vec![scout.sim_id], target: FixedVec2::from_vec2(target), queued: false });
+    }
+
+    fn attack(&mut self, view: &AiView, commands: &mut Vec<PlayerCommand>) {
+        let army = self.free_units(view);
+        if army.is_empty() {
+            return;
+        }
+        let home = view.headquarters().map(|structure| structure.position);
+        let enemies = view.enemy_structures.iter().map(|structure| (structure.sim_id, structure.position))
+            .chain(view.enemy_units.iter().map(|unit| (unit.sim_id, unit.position)));
+        let nearest_to = |position: Vec2| enemies.clone()
+            .min_by(|(_, a), (_, b)| a.distance(position).total_cmp(&b.distance(position)));
+
+        // Defend home with everything that is free
+        let intruder = home.and_then(|home| nearest_to(home).filter(|(_, position)| position.distance(home) <= DEFEND_RANGE));
+        if let Some((target, _)) = intruder {
+            commands.push(PlayerCommand::Attack { units: army.iter().map(|unit| unit.sim_id).collect(), target, queued: false });
+            return;
+        }
+        let wave = self.profile.attack_wave + self.attacks;
+        if army.len() >= wave {
+            let units = army.iter().map(|unit| unit.sim_id).collect();
+            let centre = army.iter().map(|unit| unit.position).sum::<Vec2>() / army.len() as f32;
+            if let Some((target, _)) = nearest_to(centre).or(self.enemy_base) {
+                commands.push(PlayerCommand::Attack { units, target, queued: false });
+            } else {
+                // Without a scouted base the army goes looking itself
+                let points = self.scout_points(view);
+                let target = points[self.attacks % points.len()];
+                commands.push(PlayerCommand::Move { units, target: FixedVec2::from_vec2(target), queued: false });
+            }
+            self.attacks += 1;
+            return;
+        }
+        // Units that ran into enemies on their own fight them
+        for unit in army {
+            let away = !home.is_some_and(|home| home.distance(unit.position) <= DEFEND_RANGE);
+            let range = if away { SIGHT_RANGE } else { ENGAGE_RANGE };
+            if let Some((target, _)) = nearest_to(unit.position).filter(|(_, position)| position.distance(unit.position) <= range) {
+                commands.push(PlayerCommand::Attack { units: vec![unit.sim_id], target, queued: false });
+            }
+        }
+    }
+}
+
+impl AiController for SkirmishAi {
+    fn name(&self) -> &str {
+        match self.difficulty {
+            AiDifficulty::Easy => "easy skirmish",
+            AiDifficulty::Normal => "normal skirmish",
+            AiDifficulty::Hard => "hard skirmish",
+        }
+    }
+
+    fn interval(&self) -> u64 {
+        self.profile.interval
+    }
+
+    fn reset(&mut self) {
+        *self = SkirmishAi::with_profile(self.difficulty, self.profile);
+    }
+
+    fn think(&mut self, view: &AiView) -> Vec<PlayerCommand> {
+        // Remember where the enemy base is once seen, and forget it once it is seen to be gone
+        if let Some(structure) = view.enemy_structures.iter().find(|structure| structure.headquarters).or(view.enemy_structures.first()) {
+            self.enemy_base = Some((structure.sim_id, structure.position));
+        } else if let Some((_, base)) = self.enemy_base {
+            if view.units.iter().any(|unit| unit.position.distance(base) <= ENGAGE_RANGE) {
+                self.enemy_base = None;
+            }
+        }
+        self.assigned.clear();
+        let mut commands = Vec::new();
+        self.build(view, &mut commands);
+        self.gather(view, &mut commands);
+        self.scout(view, &mut commands);
+        self.attack(view, &mut commands);
+        commands
+    }
+}
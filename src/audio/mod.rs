@@ -0,0 +1,293 @@
+use bevy::{audio::{SpatialScale, Volume}, prelude::*};
+
+use crate::{controls::{camera::PlayerCamera, selection::Selected}, entities::{combat::WeaponFiredEvent, structures::ConstructionCompleteEvent}, resources::settings::{AudioSettings, Settings}, states::InMatch};
+
+// World units are large compared to the listener's ear gap, scale them down so panning and falloff stay audible
+const AUDIO_SPATIAL_SCALE: f32 = 0.05;
+const LISTENER_EAR_GAP: f32 = 4.;
+// Minimum time between two voice lines, so a large selection answers with a single voice
+const VOICE_COOLDOWN: f32 = 1.5;
+// Cap on overlapping copies of the same effect
+const MAX_EFFECT_INSTANCES: usize = 8;
+const CROSSFADE_DURATION: f32 = 4.;
+
+// Tracks loop until their play time is up, then crossfade into the next one
+const MUSIC_PLAYLIST: [(&str, f32); 2] = [
+    ("audio/music/theme_a.wav", 48.),
+    ("audio/music/theme_b.wav", 48.),
+];
+
+// Mixer channel a sound plays on, each scaled by its own volume and the master volume
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioChannel {
+    Music,
+    Sfx,
+    Voice,
+}
+
+impl AudioChannel {
+    pub fn volume(&self, audio: &AudioSettings) -> f32 {
+        let channel_volume = match self {
+            AudioChannel::Music => audio.music_volume,
+            AudioChannel::Sfx => audio.sfx_volume,
+            AudioChannel::Voice => audio.voice_volume,
+        };
+        audio.master_volume * channel_volume
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoundEffect {
+    ConstructionComplete,
+    UnitAcknowledge,
+    WeaponFire,
+}
+
+impl SoundEffect {
+    pub fn channel(&self) -> AudioChannel {
+        match self {
+            SoundEffect::ConstructionComplete => AudioChannel::Sfx,
+            SoundEffect::UnitAcknowledge => AudioChannel::Voice,
+            SoundEffect::WeaponFire => AudioChannel::Sfx,
+        }
+    }
+}
+
+// Play a positional sound effect in the world
+#[derive(Event)]
+pub struct PlaySoundEvent {
+    pub effect: SoundEffect,
+    pub position: Vec3,
+}
+
+// Volume of a playing sound before channel mixing, used by music fades
+#[derive(Component)]
+pub struct ChannelSound {
+    pub channel: AudioChannel,
+    pub gain: f32,
+}
+
+#[derive(Component)]
+pub struct SoundEffectInstance(pub SoundEffect);
+
+#[derive(Component)]
+pub struct MusicFade {
+    pub target: f32,
+}
+
+#[derive(Default, Resource)]
+pub struct SoundLibrary {
+    pub construction_complete: Handle<AudioSource>,
+    pub unit_acknowledge: Handle<AudioSource>,
+    pub weapon_fire: Handle<AudioSource>,
+}
+
+impl SoundLibrary {
+    pub fn get(&self, effect: SoundEffect) -> Handle<AudioSource> {
+        match effect {
+            SoundEffect::ConstructionComplete => self.construction_complete.clone(),
+            SoundEffect::UnitAcknowledge => self.unit_acknowledge.clone(),
+            SoundEffect::WeaponFire => self.weapon_fire.clone(),
+        }
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct MusicPlaylist {
+    pub current: Option<usize>,
+    pub elapsed: f32,
+}
+
+#[derive(Default, Resource)]
+pub struct VoiceLimiter {
+    pub last_played: Option<f32>,
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<PlaySoundEvent>()
+            .init_resource::<SoundLibrary>()
+            .init_resource::<MusicPlaylist>()
+            .init_resource::<VoiceLimiter>()
+            .add_systems(Startup, setup_sound_library)
+            .add_systems(Update, (
+                attach_listener,
+                (
+                    handle_weapon_fire_sounds,
+                    handle_construction_sounds,
+                    handle_selection_acknowledge,
+                    handle_play_sound_event,
+                ).chain(),
+                update_music_playlist,
+                update_channel_volumes,
+            ).chain());
+    }
+}
+
+pub fn setup_sound_library(
+    mut sound_library: ResMut<SoundLibrary>,
+    asset_server: Res<AssetServer>,
+) {
+    sound_library.construction_complete = asset_server.load("audio/sfx/construction_complete.wav");
+    sound_library.unit_acknowledge = asset_server.load("audio/sfx/acknowledge.wav");
+    sound_library.weapon_fire = asset_server.load("audio/sfx/weapon_fire.wav");
+}
+
+// Spatial sounds are heard from the player's camera
+pub fn attach_listener(
+    mut commands: Commands,
+    q_camera: Query<Entity, Added<PlayerCamera>>,
+) {
+    for camera_entity in q_camera.iter() {
+        commands.entity(camera_entity).insert(SpatialListener::new(LISTENER_EAR_GAP));
+    }
+}
+
+// Every shot is heard from where it was fired, the instance cap keeps big fights bearable
+pub fn handle_weapon_fire_sounds(
+    mut ev_fired: EventReader<WeaponFiredEvent>,
+    mut ev_sound: EventWriter<PlaySoundEvent>,
+) {
+    for fired in ev_fired.read() {
+        ev_sound.send(PlaySoundEvent {
+            effect: SoundEffect::WeaponFire,
+            position: fired.from,
+        });
+    }
+}
+
+pub fn handle_construction_sounds(
+    mut ev_complete: EventReader<ConstructionCompleteEvent>,
+    mut ev_sound: EventWriter<PlaySoundEvent>,
+) {
+    for complete in ev_complete.read() {
+        ev_sound.send(PlaySoundEvent {
+            effect: SoundEffect::ConstructionComplete,
+            position: complete.position,
+        });
+    }
+}
+
+pub fn handle_selection_acknowledge(
+    q_selected: Query<&GlobalTransform, Added<Selected>>,
+    mut ev_sound: EventWriter<PlaySoundEvent>,
+) {
+    for transform in q_selected.iter() {
+        ev_sound.send(PlaySoundEvent {
+            effect: SoundEffect::UnitAcknowledge,
+            position: transform.translation(),
+        });
+    }
+}
+
+pub fn handle_play_sound_event(
+    mut commands: Commands,
+    mut ev_sound: EventReader<PlaySoundEvent>,
+    mut voice_limiter: ResMut<VoiceLimiter>,
+    sound_library: Res<SoundLibrary>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+    q_instances: Query<&SoundEffectInstance>,
+) {
+    let now = time.elapsed_seconds();
+    // Effects spawned this frame aren't queryable yet, so count them separately
+    let mut spawned: Vec<SoundEffect> = Vec::new();
+    for event in ev_sound.read() {
+        let channel = event.effect.channel();
+        if channel == AudioChannel::Voice {
+            if voice_limiter.last_played.is_some_and(|last| now - last < VOICE_COOLDOWN) {
+                continue;
+            }
+            voice_limiter.last_played = Some(now);
+        }
+        let playing = q_instances.iter().filter(|instance| instance.0 == event.effect).count()
+            + spawned.iter().filter(|effect| **effect == event.effect).count();
+        if playing >= MAX_EFFECT_INSTANCES {
+            continue;
+        }
+        spawned.push(event.effect);
+        commands.spawn((
+            StateScoped(InMatch),
+            ChannelSound {
+                channel,
+                gain: 1.,
+            },
+            SoundEffectInstance(event.effect),
+            AudioBundle {
+                source: sound_library.get(event.effect),
+                settings: PlaybackSettings::DESPAWN
+                    .with_spatial(true)
+                    .with_spatial_scale(SpatialScale::new(AUDIO_SPATIAL_SCALE))
+                    .with_volume(Volume::new(channel.volume(&settings.audio))),
+            },
+            SpatialBundle::from_transform(Transform::from_translation(event.position)),
+        ));
+    }
+}
+
+// Real time so music keeps playing and fading while the game is paused
+pub fn update_music_playlist(
+    mut commands: Commands,
+    mut playlist: ResMut<MusicPlaylist>,
+    mut q_music: Query<(Entity, &mut ChannelSound, &mut MusicFade)>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time<Real>>,
+) {
+    let delta = time.delta_seconds();
+    playlist.elapsed += delta;
+
+    let track_finishing = playlist.current
+        .is_some_and(|current| playlist.elapsed >= MUSIC_PLAYLIST[current].1 - CROSSFADE_DURATION);
+    if playlist.current.is_none() || track_finishing {
+        for (_entity, _sound, mut fade) in q_music.iter_mut() {
+            fade.target = 0.;
+        }
+        let next = playlist.current.map_or(0, |current| (current + 1) % MUSIC_PLAYLIST.len());
+        commands.spawn((
+            ChannelSound {
+                channel: AudioChannel::Music,
+                gain: 0.,
+            },
+            MusicFade {
+                target: 1.,
+            },
+            AudioBundle {
+                source: asset_server.load(MUSIC_PLAYLIST[next].0),
+                settings: PlaybackSettings::LOOP.with_volume(Volume::ZERO),
+            },
+        ));
+        playlist.current = Some(next);
+        playlist.elapsed = 0.;
+    }
+
+    let step = delta / CROSSFADE_DURATION;
+    for (entity, mut sound, fade) in q_music.iter_mut() {
+        if sound.gain < fade.target {
+            sound.gain = (sound.gain + step).min(fade.target);
+        } else if sound.gain > fade.target {
+            sound.gain = (sound.gain - step).max(fade.target);
+        }
+        if fade.target == 0. && sound.gain == 0. {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Sinks only take their initial volume from PlaybackSettings, so keep them in sync with the mixer
+pub fn update_channel_volumes(
+    settings: Res<Settings>,
+    q_sounds: Query<(&ChannelSound, Option<&AudioSink>, Option<&SpatialAudioSink>)>,
+) {
+    for (sound, sink, spatial_sink) in q_sounds.iter() {
+        let volume = sound.channel.volume(&settings.audio) * sound.gain;
+        if let Some(sink) = sink {
+            sink.set_volume(volume);
+        }
+        if let Some(spatial_sink) = spatial_sink {
+            spatial_sink.set_volume(volume);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+use rts::{
+    headless::{Headless, HeadlessPlugin},
+    network::server::{GameServer, ServerConfig, ServerPlugin},
+    SimulationPlugins,
+};
+
+// Dedicated server: runs the only simulation of a match at real time for clients joining with
+// --connect <address>. Takes --port <port>, --players <count> and --time-limit <seconds>.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let config = ServerConfig::from_args(&args);
+    let server = match GameServer::bind(&config) {
+        Ok(server) => server,
+        Err(err) => {
+            println!("Failed to start server on port {}: {}", config.port, err);
+            return;
+        },
+    };
+    println!("Server listening on port {}, waiting for {} players", config.port, config.players);
+    App::new()
+        .add_plugins((
+            HeadlessPlugin {
+                config: Headless {
+                    realtime: true,
+                    ..default()
+                }.with_args(&args),
+            },
+            SimulationPlugins,
+            ServerPlugin,
+        ))
+        .insert_resource(server)
+        .run();
+}
@@ -0,0 +1,203 @@
+use avian3d::math::{PI, TAU};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{entities::{combat::WeaponFiredEvent, structures::ConstructionCompleteEvent}, resources::{player::Player, settings::Settings}, states::AppState, ui::cursor::CursorMode};
+
+use super::{camera::{handle_camera_move, handle_camera_terrain, PlayerCamera}, InputMap};
+
+pub const BOOKMARK_SLOTS: usize = 4;
+const TRANSITION_DURATION: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct CameraBookmark {
+    pub location: Vec3,
+    pub pitch: f32,
+    pub rotation: Vec3,
+    pub zoom: f32,
+}
+
+impl CameraBookmark {
+    pub fn from_camera(camera: &PlayerCamera) -> Self {
+        Self {
+            location: camera.location,
+            pitch: camera.pitch,
+            rotation: camera.rotation,
+            zoom: camera.zoom,
+        }
+    }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        // Turn the shortest way around rather than unwinding a full revolution
+        let rotation_delta = (other.rotation.y - self.rotation.y + PI).rem_euclid(TAU) - PI;
+        Self {
+            location: self.location.lerp(other.location, t),
+            pitch: self.pitch.lerp(other.pitch, t),
+            rotation: Vec3::new(self.rotation.x, self.rotation.y + rotation_delta * t, self.rotation.z),
+            zoom: self.zoom.lerp(other.zoom, t),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Resource, Serialize)]
+pub struct CameraBookmarks {
+    pub slots: [Option<CameraBookmark>; BOOKMARK_SLOTS],
+}
+
+#[derive(Component)]
+pub struct CameraTransition {
+    from: CameraBookmark,
+    to: CameraBookmark,
+    elapsed: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AlertKind {
+    Attack,
+    ConstructionComplete,
+}
+
+impl AlertKind {
+    pub fn notice(&self) -> &'static str {
+        match self {
+            AlertKind::Attack => "Under attack",
+            AlertKind::ConstructionComplete => "Construction complete",
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct AlertEvent {
+    pub kind: AlertKind,
+    pub position: Vec3,
+}
+
+#[derive(Default, Resource)]
+pub struct LastAlert {
+    pub alert: Option<(AlertKind, Vec3)>,
+    // Real time in seconds it was raised at
+    pub raised_at: f64,
+}
+
+pub struct BookmarkPlugin;
+
+impl Plugin for BookmarkPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<AlertEvent>()
+            .init_resource::<CameraBookmarks>()
+            .init_resource::<LastAlert>()
+            .add_systems(Update, (
+                raise_alerts,
+                handle_alert_event,
+                handle_bookmark_keys
+                    .run_if(not(in_state(CursorMode::Locked))),
+                handle_camera_transition,
+            ).chain().after(handle_camera_move).before(handle_camera_terrain).run_if(in_state(AppState::InGame)));
+    }
+}
+
+// The player hears about attacks on their units and structures and about their own structures
+// going up
+pub fn raise_alerts(
+    player: Res<Player>,
+    mut ev_fired: EventReader<WeaponFiredEvent>,
+    mut ev_complete: EventReader<ConstructionCompleteEvent>,
+    mut ev_alert: EventWriter<AlertEvent>,
+) {
+    // One alert per frame is plenty, a fight would raise one every shot otherwise
+    if let Some(fired) = ev_fired.read().filter(|fired| fired.victim == Some(player.id)).last() {
+        ev_alert.send(AlertEvent {
+            kind: AlertKind::Attack,
+            position: fired.to,
+        });
+    }
+    for complete in ev_complete.read().filter(|complete| complete.player == player.id) {
+        ev_alert.send(AlertEvent {
+            kind: AlertKind::ConstructionComplete,
+            position: complete.position,
+        });
+    }
+}
+
+pub fn handle_alert_event(
+    time: Res<Time<Real>>,
+    mut ev_alert: EventReader<AlertEvent>,
+    mut last_alert: ResMut<LastAlert>,
+) {
+    if let Some(alert) = ev_alert.read().last() {
+        last_alert.alert = Some((alert.kind, alert.position));
+        last_alert.raised_at = time.elapsed_seconds_f64();
+    }
+}
+
+pub fn handle_bookmark_keys(
+    mut commands: Commands,
+    key: Res<ButtonInput<KeyCode>>,
+    last_alert: Res<LastAlert>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    q_camera: Query<(Entity, &PlayerCamera)>,
+) {
+    let input_map = InputMap::default();
+    let (camera_entity, camera) = q_camera.single();
+    let current = CameraBookmark::from_camera(camera);
+    let storing = key.pressed(input_map.bookmark_modifier);
+
+    let mut target = None;
+    for (slot, bookmark_key) in input_map.bookmarks.iter().enumerate() {
+        if !key.just_pressed(*bookmark_key) {
+            continue;
+        }
+        if storing {
+            bookmarks.slots[slot] = Some(current);
+        } else if let Some(bookmark) = bookmarks.slots[slot] {
+            target = Some(bookmark);
+        }
+    }
+
+    if key.just_pressed(input_map.last_alert) {
+        if let Some((_, position)) = last_alert.alert {
+            target = Some(CameraBookmark {
+                location: position,
+                ..current
+            });
+        }
+    }
+
+    if let Some(to) = target {
+        commands.entity(camera_entity).insert(CameraTransition {
+            from: current,
+            to,
+            elapsed: 0.,
+        });
+    }
+}
+
+pub fn handle_camera_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut q_camera: Query<(Entity, &mut PlayerCamera, &mut CameraTransition)>,
+) {
+    let Ok((camera_entity, mut camera, mut transition)) = q_camera.get_single_mut() else {
+        return;
+    };
+    transition.elapsed += time.delta_seconds();
+    // Reduced motion cuts straight to the destination
+    let t = if settings.accessibility.reduced_motion {
+        1.
+    } else {
+        (transition.elapsed / TRANSITION_DURATION).min(1.)
+    };
+    let eased = t * t * (3. - 2. * t);
+    let state = transition.from.lerp(&transition.to, eased);
+    camera.location = state.location;
+    camera.pitch = state.pitch;
+    camera.rotation = state.rotation;
+    camera.zoom = state.zoom;
+    camera.target_zoom = state.zoom;
+    camera.velocity = Vec3::ZERO;
+    if t >= 1. {
+        commands.entity(camera_entity).remove::<CameraTransition>();
+    }
+}
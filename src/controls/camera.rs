@@ -1,122 +1,324 @@
 use std::f32::consts::E;
 
-use avian3d::math::TAU;
-use bevy::{input::mouse::{MouseMotion, MouseWheel}, prelude::*, time::Time, window::PrimaryWindow};
+use avian3d::{math::{PI, TAU}, prelude::{SpatialQuery, SpatialQueryFilter}};
+use bevy::{input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, prelude::*, time::Time, window::PrimaryWindow};
 
-use crate::ui::cursor::*;
+use crate::{entities::EntityCollisionLayers, resources::settings::Settings, states::AppState, ui::cursor::*};
 
 use super::InputMap;
 
-const SCROLL_SENSITIVITY: f32 = 5.0;
+// Pixel scroll deltas (touchpads) are much finer than line deltas (mouse wheels)
+const SCROLL_PIXELS_PER_LINE: f32 = 100.;
 const TURN_SPEED: f32 = TAU / 4.;
+const PITCH_SPEED: f32 = PI / 4.;
 const MAX_ZOOM: f32 = 10.;
 const MIN_ZOOM: f32 = 1.;
-pub const SCROLL_SPEED: f32 = 50.0;
+const MAX_PITCH: f32 = PI * 0.45;
+const MIN_PITCH: f32 = PI / 8.;
+const DEFAULT_PITCH: f32 = PI / 4.;
+const DEFAULT_ZOOM: f32 = 5.;
+const EDGE_SCROLL_MARGIN: f32 = 24.;
+// Rates for exponential smoothing, higher values converge faster
+const VELOCITY_SMOOTHING: f32 = 8.;
+const ZOOM_SMOOTHING: f32 = 10.;
+const HEIGHT_SMOOTHING: f32 = 6.;
+// Height from which ground probes are cast straight down
+const GROUND_PROBE_HEIGHT: f32 = 1000.;
+// Minimum gap kept between the camera and any static collider
+const CAMERA_COLLISION_PADDING: f32 = 0.5;
+const CAMERA_GROUND_CLEARANCE: f32 = 1.0;
 
 pub static CAMERA_LOOK_POINT: Vec3 = Vec3::new(0.0, 1.0, 0.0);
-pub static CAMERA_MIN_OFFSET_TRANSLATION: Vec3 = Vec3::new(0.0, 5.0, 5.);
-pub static CAMERA_MAX_OFFSET_TRANSLATION: Vec3 = Vec3::new(0.0, 75.0, 100.);
+pub static CAMERA_MIN_DISTANCE: f32 = 7.5;
+pub static CAMERA_MAX_DISTANCE: f32 = 125.;
+const CAMERA_BOUNDS: Rect = Rect {
+    min: Vec2::new(-200., -200.),
+    max: Vec2::new(200., 200.),
+};
 
-#[derive(Component, Default)]
+#[derive(Clone, Resource)]
+pub struct CameraConfig {
+    // Area the camera target is kept within, on the ground plane
+    pub bounds: Rect,
+    // Width of the band along the window edges in which the camera scrolls, in logical pixels
+    pub edge_scroll_margin: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            bounds: CAMERA_BOUNDS,
+            edge_scroll_margin: EDGE_SCROLL_MARGIN,
+        }
+    }
+}
+
+#[derive(Component)]
 pub struct PlayerCamera {
+    // Camera relative movement requested by input this frame, consumed by handle_camera_move
+    pub input_direction: Vec3,
     pub location: Vec3,
     pub offset: Vec3,
+    pub pitch: f32,
     pub rotation: Vec3,
+    pub target_zoom: f32,
+    pub velocity: Vec3,
     pub zoom: f32,
 }
 
-pub fn add_camera_systems(app: &mut App) {
-    app
-        .add_systems(Update, handle_camera_zoom)
-        .add_systems(Update, handle_camera_move)
-        .add_systems(Update, handle_camera_transform);
+impl Default for PlayerCamera {
+    fn default() -> Self {
+        Self {
+            input_direction: Vec3::ZERO,
+            location: Vec3::ZERO,
+            offset: Vec3::ZERO,
+            pitch: DEFAULT_PITCH,
+            rotation: Vec3::ZERO,
+            target_zoom: DEFAULT_ZOOM,
+            velocity: Vec3::ZERO,
+            zoom: DEFAULT_ZOOM,
+        }
+    }
+}
+
+impl PlayerCamera {
+    // Distance from the look point to the camera for the current zoom level
+    pub fn distance(&self) -> f32 {
+        zoom_distance(self.zoom)
+    }
+
+    // Offset from the look point to the camera, accounting for pitch and yaw
+    pub fn compute_offset(&self) -> Vec3 {
+        let direction = Quat::from_rotation_y(self.rotation.y)
+            .mul_vec3(Vec3::new(0.0, self.pitch.sin(), self.pitch.cos()));
+        direction * self.distance()
+    }
+
+    fn scroll_speed(&self, base_speed: f32) -> f32 {
+        base_speed * f32::ln(self.zoom * E)
+    }
 }
 
+fn zoom_distance(zoom: f32) -> f32 {
+    let t = (zoom - MIN_ZOOM) / (MAX_ZOOM - MIN_ZOOM);
+    CAMERA_MIN_DISTANCE + (CAMERA_MAX_DISTANCE - CAMERA_MIN_DISTANCE) * t
+}
+
+// Frame-rate independent interpolation factor for exponential smoothing
+fn smoothing_factor(rate: f32, delta: f32) -> f32 {
+    1.0 - f32::exp(-rate * delta)
+}
+
+// Reduced motion drops the easing on camera input, so the camera moves and zooms without gliding
+fn input_smoothing_factor(settings: &Settings, rate: f32, delta: f32) -> f32 {
+    if settings.accessibility.reduced_motion {
+        1.0
+    } else {
+        smoothing_factor(rate, delta)
+    }
+}
+
+fn ground_filter() -> SpatialQueryFilter {
+    SpatialQueryFilter::from_mask(EntityCollisionLayers::Ground)
+}
+
+// Height of the highest ground collider at the given horizontal position
+pub fn sample_ground_height(spatial_query: &SpatialQuery, position: Vec2) -> Option<f32> {
+    let origin = Vec3::new(position.x, GROUND_PROBE_HEIGHT, position.y);
+    spatial_query.cast_ray(origin, Dir3::NEG_Y, GROUND_PROBE_HEIGHT * 2., true, ground_filter())
+        .map(|hit| GROUND_PROBE_HEIGHT - hit.time_of_impact)
+}
+
+#[derive(Default)]
+pub struct PlayerCameraPlugin {
+    pub config: CameraConfig,
+}
+
+impl Plugin for PlayerCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(self.config.clone())
+            .add_systems(Update, (
+                handle_camera_zoom
+                    .run_if(not(in_state(CursorMode::Locked))),
+                handle_camera_edge_scroll
+                    .run_if(in_state(CursorMode::Idle).or_else(in_state(CursorMode::Selecting)).and_then(edge_scroll_enabled)),
+                handle_camera_control
+                    .run_if(in_state(CursorMode::CameraControl)),
+                handle_camera_move,
+                handle_camera_terrain,
+                handle_camera_transform,
+            ).chain().run_if(in_state(AppState::InGame)));
+    }
+}
 
 pub fn handle_camera_zoom(
     time: Res<Time>,
+    settings: Res<Settings>,
     mut ev_mouse: EventReader<MouseWheel>,
-    mut q_camera: Query<&mut PlayerCamera>
+    mut q_camera: Query<(&mut PlayerCamera, &Camera, &GlobalTransform)>,
+    q_cursor: Query<&Cursor>,
 ) {
-    let mut camera = q_camera.single_mut();
+    let (mut camera, camera_3d, camera_transform) = q_camera.single_mut();
+    let cursor = q_cursor.single();
     let delta = time.delta_seconds();
     for mouse_wheel_event in ev_mouse.read() {
-        let mouse_movement = -mouse_wheel_event.y * delta * SCROLL_SENSITIVITY;
-        if (mouse_movement < 0.0 && camera.zoom >= MIN_ZOOM) || (mouse_movement > 0.0 && camera.zoom <= MAX_ZOOM) {
-            camera.zoom = f32::max(MIN_ZOOM, f32::min(camera.zoom, MAX_ZOOM)) + mouse_movement;
-            println!("X: {}, Y: {}, Zoom: {}", mouse_wheel_event.x, mouse_wheel_event.y, camera.zoom);
-        }
+        let lines = match mouse_wheel_event.unit {
+            MouseScrollUnit::Line => mouse_wheel_event.y,
+            MouseScrollUnit::Pixel => mouse_wheel_event.y / SCROLL_PIXELS_PER_LINE,
+        };
+        camera.target_zoom = (camera.target_zoom - lines * settings.input.zoom_sensitivity).clamp(MIN_ZOOM, MAX_ZOOM);
     }
-    camera.offset = Quat::from_rotation_y(camera.rotation.y).mul_vec3(CAMERA_MIN_OFFSET_TRANSLATION.lerp(CAMERA_MAX_OFFSET_TRANSLATION, camera.zoom / MAX_ZOOM));
+
+    let zoom = camera.zoom + (camera.target_zoom - camera.zoom) * input_smoothing_factor(&settings, ZOOM_SMOOTHING, delta);
+    if zoom == camera.zoom {
+        return;
+    }
+
+    // Scale the camera position about the ground point under the cursor so that point stays fixed on screen
+    let focus = camera_3d.viewport_to_world(camera_transform, cursor.location)
+        .and_then(|ray| {
+            ray.intersect_plane(camera.location, InfinitePlane3d::new(Vec3::Y))
+                .map(|distance| ray.get_point(distance))
+        });
+    if let Some(focus) = focus {
+        let scale = zoom_distance(zoom) / camera.distance();
+        camera.location = focus + (camera.location - focus) * scale;
+    }
+    camera.zoom = zoom;
 }
 
-pub fn handle_camera_move(
+pub fn edge_scroll_enabled(settings: Res<Settings>) -> bool {
+    settings.accessibility.edge_scroll
+}
+
+// Scroll when the cursor is within the margin band along the window edges
+pub fn handle_camera_edge_scroll(
+    config: Res<CameraConfig>,
+    mut q_camera: Query<&mut PlayerCamera>,
+    q_cursor: Query<&Cursor>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let mut camera = q_camera.single_mut();
+    let cursor = q_cursor.single();
+    let window = q_windows.single();
+    // Ramp edge scroll speed up as the cursor approaches the window edge
+    let edge_strength = |distance: f32| -> f32 {
+        (1.0 - distance / config.edge_scroll_margin).clamp(0.0, 1.0)
+    };
+    camera.input_direction.x -= edge_strength(cursor.location.x);
+    camera.input_direction.x += edge_strength(window.width() - cursor.location.x);
+    camera.input_direction.z -= edge_strength(cursor.location.y);
+    camera.input_direction.z += edge_strength(window.height() - cursor.location.y);
+}
+
+// Drag, turn, tilt and pan the camera while camera control is held
+pub fn handle_camera_control(
     time: Res<Time>,
     mut ev_mouse: EventReader<MouseMotion>,
     key: Res<ButtonInput<KeyCode>>,
-    mut q_camera: Query<&mut PlayerCamera, Without<Cursor>>,
-    mut q_cursor: Query<&mut Cursor, Without<PlayerCamera>>,
-    q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut q_camera: Query<&mut PlayerCamera>,
 ) {
     let input_map = InputMap::default();
     let mut camera = q_camera.single_mut();
-    let cursor = q_cursor.single_mut();
     let delta = time.delta_seconds();
     let rotation_quat = Quat::from_rotation_y(camera.rotation.y);
-    let window = q_windows.single();
-    let mut translation = Vec3::ZERO;
-    match cursor.mode {
-        CursorMode::Idle | CursorMode::Selecting => {
-            if cursor.location.x == 0.0 {
-                translation += rotation_quat.mul_vec3(Vec3::NEG_X * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-            if cursor.location.x == window.width() {
-                translation += rotation_quat.mul_vec3(Vec3::X * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-            if cursor.location.y == 0.0 {
-                translation += rotation_quat.mul_vec3(Vec3::NEG_Z * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-            if cursor.location.y == window.height() {
-                translation += rotation_quat.mul_vec3(Vec3::Z * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-        },
-        CursorMode::CameraControl => {
-            for mouse_event in ev_mouse.read() {
-                let motion = mouse_event.delta * delta * f32::ln(camera.zoom * E);
-                let mouse_offset_vec = rotation_quat.mul_vec3(Vec3::new(motion.x, 0.0, motion.y));
-                camera.location += mouse_offset_vec;
-            }
-            if key.pressed(input_map.turn_l) {
-                camera.rotation.y -= TAU * TURN_SPEED * delta;
-            }
-            if key.pressed(input_map.turn_r) {
-                camera.rotation.y += TAU * TURN_SPEED * delta;
-            }
-            if key.pressed(input_map.left) {
-                translation += rotation_quat.mul_vec3(Vec3::NEG_X * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-            if key.pressed(input_map.right) {
-                translation += rotation_quat.mul_vec3(Vec3::X * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-            if key.pressed(input_map.forward) {
-                translation += rotation_quat.mul_vec3(Vec3::NEG_Z * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-            if key.pressed(input_map.backward) {
-                translation += rotation_quat.mul_vec3(Vec3::Z * SCROLL_SPEED * delta * f32::ln(camera.zoom * E));
-            }
-        },
-        _ => {
-            return;
-        }
+    for mouse_event in ev_mouse.read() {
+        let motion = mouse_event.delta * delta * f32::ln(camera.zoom * E);
+        let mouse_offset_vec = rotation_quat.mul_vec3(Vec3::new(motion.x, 0.0, motion.y));
+        camera.location += mouse_offset_vec;
+    }
+    if key.pressed(input_map.turn_l) {
+        camera.rotation.y -= TAU * TURN_SPEED * delta;
+    }
+    if key.pressed(input_map.turn_r) {
+        camera.rotation.y += TAU * TURN_SPEED * delta;
+    }
+    if key.pressed(input_map.pitch_up) {
+        camera.pitch += PITCH_SPEED * delta;
     }
-    camera.location += translation;
+    if key.pressed(input_map.pitch_down) {
+        camera.pitch -= PITCH_SPEED * delta;
+    }
+    if key.pressed(input_map.left) {
+        camera.input_direction += Vec3::NEG_X;
+    }
+    if key.pressed(input_map.right) {
+        camera.input_direction += Vec3::X;
+    }
+    if key.pressed(input_map.forward) {
+        camera.input_direction += Vec3::NEG_Z;
+    }
+    if key.pressed(input_map.backward) {
+        camera.input_direction += Vec3::Z;
+    }
+}
+
+// Integrate the movement requested by the input systems this frame
+pub fn handle_camera_move(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut q_camera: Query<&mut PlayerCamera>,
+) {
+    let mut camera = q_camera.single_mut();
+    let delta = time.delta_seconds();
+    let rotation_quat = Quat::from_rotation_y(camera.rotation.y);
+
+    // Ease the velocity towards the requested direction so the camera accelerates and glides to a stop
+    let direction = std::mem::take(&mut camera.input_direction);
+    let target_velocity = rotation_quat.mul_vec3(direction.clamp_length_max(1.0)) * camera.scroll_speed(settings.input.scroll_speed);
+    camera.velocity = camera.velocity.lerp(target_velocity, input_smoothing_factor(&settings, VELOCITY_SMOOTHING, delta));
+    let velocity = camera.velocity;
+    camera.location += velocity * delta;
     camera.rotation %= TAU;
+    camera.pitch = camera.pitch.clamp(MIN_PITCH, MAX_PITCH);
+}
+
+// Keep the look point riding on top of the terrain under the camera target
+pub fn handle_camera_terrain(
+    time: Res<Time>,
+    spatial_query: SpatialQuery,
+    mut q_camera: Query<&mut PlayerCamera>,
+) {
+    let mut camera = q_camera.single_mut();
+    let Some(ground_height) = sample_ground_height(&spatial_query, camera.location.xz()) else {
+        return;
+    };
+    let delta = time.delta_seconds();
+    camera.location.y = camera.location.y.lerp(ground_height, smoothing_factor(HEIGHT_SMOOTHING, delta));
 }
 
 pub fn handle_camera_transform(
+    config: Res<CameraConfig>,
+    spatial_query: SpatialQuery,
     mut q_camera: Query<(&mut PlayerCamera, &mut Transform)>
 ) {
-    let (camera, mut camera_transform) = q_camera.single_mut();
-    camera_transform.translation =  camera.location + camera.offset;
-    camera_transform.look_at(camera.location + CAMERA_LOOK_POINT, Dir3::Y);
-}
\ No newline at end of file
+    let (mut camera, mut camera_transform) = q_camera.single_mut();
+    let bounded = camera.location.xz().clamp(config.bounds.min, config.bounds.max);
+    if bounded != camera.location.xz() {
+        camera.location.x = bounded.x;
+        camera.location.z = bounded.y;
+        camera.velocity = Vec3::ZERO;
+    }
+    camera.offset = camera.compute_offset();
+
+    let look_point = camera.location + CAMERA_LOOK_POINT;
+    let mut translation = camera.location + camera.offset;
+
+    // Pull the camera in towards the look point if an obstacle sits between them
+    let to_camera = translation - look_point;
+    if let Ok(direction) = Dir3::new(to_camera) {
+        let distance = to_camera.length();
+        if let Some(hit) = spatial_query.cast_ray(look_point, direction, distance, false, ground_filter()) {
+            translation = look_point + direction * (hit.time_of_impact - CAMERA_COLLISION_PADDING).max(0.);
+        }
+    }
+
+    // Raise the camera if it ends up inside or too close to the ground below it
+    if let Some(ground_height) = sample_ground_height(&spatial_query, translation.xz()) {
+        translation.y = translation.y.max(ground_height + CAMERA_GROUND_CLEARANCE);
+    }
+
+    camera_transform.translation = translation;
+    camera_transform.look_at(look_point, Dir3::Y);
+}
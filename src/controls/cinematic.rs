@@ -0,0 +1,214 @@
+use std::io::{Error, ErrorKind};
+
+use bevy::{asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext}, prelude::*};
+use serde::Deserialize;
+
+use crate::{resources::settings::Settings, states::AppState, ui::cursor::{Cursor, CursorMode, CursorModeChangeEvent}};
+
+use super::{bookmarks::CameraBookmark, camera::{handle_camera_move, handle_camera_terrain, PlayerCamera}, InputMap};
+
+// Written by name in a keyframe's `easing` field, Linear when left out
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+// A point on a camera path, reached `time` seconds after the path starts.
+// The easing controls how the camera approaches this keyframe from the previous one.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub camera: CameraBookmark,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+// Keyframes as written in a .campath.ron file
+#[derive(Deserialize)]
+struct CameraPathFile {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+#[derive(Asset, Debug, TypePath)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+    // Catmull-Rom spline through the keyframe locations, one segment between each pair of keyframes
+    curve: CubicCurve<Vec3>,
+}
+
+impl CameraPath {
+    pub fn new(keyframes: Vec<CameraKeyframe>) -> Self {
+        let locations: Vec<Vec3> = keyframes.iter().map(|keyframe| keyframe.camera.location).collect();
+        let curve = CubicCardinalSpline::new_catmull_rom(locations).to_curve();
+        CameraPath { keyframes, curve }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0., |keyframe| keyframe.time)
+    }
+
+    // Camera state at the given time, with locations following a Catmull-Rom spline through the keyframes
+    pub fn sample(&self, time: f32) -> Option<CameraBookmark> {
+        let first = self.keyframes.first()?;
+        if self.keyframes.len() == 1 || time <= first.time {
+            return Some(first.camera);
+        }
+        let segment = self.keyframes.windows(2)
+            .position(|pair| time < pair[1].time)
+            .unwrap_or(self.keyframes.len() - 2);
+        let (from, to) = (&self.keyframes[segment], &self.keyframes[segment + 1]);
+        let span = (to.time - from.time).max(f32::EPSILON);
+        let t = to.easing.apply(((time - from.time) / span).clamp(0., 1.));
+
+        let mut state = from.camera.lerp(&to.camera, t);
+        state.location = self.curve.position(segment as f32 + t);
+        Some(state)
+    }
+}
+
+#[derive(Default)]
+pub struct CameraPathLoader;
+
+impl AssetLoader for CameraPathLoader {
+    type Asset = CameraPath;
+    type Settings = ();
+    type Error = Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file: CameraPathFile = ron::de::from_bytes(&bytes)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        if file.keyframes.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "camera path has no keyframes"));
+        }
+        Ok(CameraPath::new(file.keyframes))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campath.ron"]
+    }
+}
+
+// Sent by scripts to start playing a camera path, locking player input until it finishes
+#[derive(Event)]
+pub struct PlayCameraPathEvent(pub Handle<CameraPath>);
+
+// Ends the camera path early, handing the camera back to the player where it is
+#[derive(Event)]
+pub struct StopCameraPathEvent;
+
+// Sent when a camera path ends, whether it played through or was stopped
+#[derive(Event)]
+pub struct CameraPathFinishedEvent(pub Handle<CameraPath>);
+
+#[derive(Default, Resource)]
+pub struct CameraPathPlayer {
+    pub path: Option<Handle<CameraPath>>,
+    pub elapsed: f32,
+}
+
+pub struct CinematicPlugin;
+
+impl Plugin for CinematicPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<CameraPath>()
+            .init_asset_loader::<CameraPathLoader>()
+            .init_resource::<CameraPathPlayer>()
+            .add_event::<PlayCameraPathEvent>()
+            .add_event::<StopCameraPathEvent>()
+            .add_event::<CameraPathFinishedEvent>()
+            .add_systems(Update, (
+                handle_camera_path_skip,
+                handle_camera_path_events,
+                play_camera_path,
+            ).chain().after(handle_camera_move).before(handle_camera_terrain).run_if(in_state(AppState::InGame)));
+    }
+}
+
+pub fn handle_camera_path_skip(
+    key: Res<ButtonInput<KeyCode>>,
+    player: Res<CameraPathPlayer>,
+    mut ev_stop: EventWriter<StopCameraPathEvent>,
+) {
+    let input_map = InputMap::default();
+    if player.path.is_some() && key.just_pressed(input_map.skip_camera_path) {
+        ev_stop.send(StopCameraPathEvent);
+    }
+}
+
+pub fn handle_camera_path_events(
+    mut player: ResMut<CameraPathPlayer>,
+    mut ev_play: EventReader<PlayCameraPathEvent>,
+    mut ev_stop: EventReader<StopCameraPathEvent>,
+    mut ev_cursor_change: EventWriter<CursorModeChangeEvent>,
+    mut ev_finished: EventWriter<CameraPathFinishedEvent>,
+) {
+    if let Some(PlayCameraPathEvent(path)) = ev_play.read().last() {
+        player.path = Some(path.clone());
+        player.elapsed = 0.;
+        ev_cursor_change.send(CursorModeChangeEvent(CursorMode::Locked));
+    }
+    if ev_stop.read().next().is_some() {
+        if let Some(path) = player.path.take() {
+            ev_finished.send(CameraPathFinishedEvent(path));
+            ev_cursor_change.send(CursorModeChangeEvent(CursorMode::Idle));
+        }
+    }
+}
+
+pub fn play_camera_path(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    paths: Res<Assets<CameraPath>>,
+    mut player: ResMut<CameraPathPlayer>,
+    mut q_camera: Query<&mut PlayerCamera, Without<Cursor>>,
+    mut ev_cursor_change: EventWriter<CursorModeChangeEvent>,
+    mut ev_finished: EventWriter<CameraPathFinishedEvent>,
+) {
+    let Some(handle) = player.path.clone() else { return; };
+    // Wait for the asset to finish loading before starting the clock
+    let Some(path) = paths.get(&handle) else { return; };
+    let mut camera = q_camera.single_mut();
+
+    player.elapsed += time.delta_seconds();
+    // Reduced motion skips the fly-through and settles on the final shot
+    if settings.accessibility.reduced_motion {
+        player.elapsed = path.duration();
+    }
+    if let Some(state) = path.sample(player.elapsed) {
+        camera.location = state.location;
+        camera.pitch = state.pitch;
+        camera.rotation = state.rotation;
+        camera.zoom = state.zoom;
+        camera.target_zoom = state.zoom;
+        camera.velocity = Vec3::ZERO;
+    }
+
+    if player.elapsed >= path.duration() {
+        player.path = None;
+        ev_finished.send(CameraPathFinishedEvent(handle));
+        ev_cursor_change.send(CursorModeChangeEvent(CursorMode::Idle));
+    }
+}
@@ -1,6 +1,12 @@
 use bevy::prelude::{KeyCode, Resource};
+use bookmarks::BOOKMARK_SLOTS;
 
+use crate::ui::replay_viewer::REPLAY_VISION_SLOTS;
+
+pub mod bookmarks;
 pub mod camera;
+pub mod cinematic;
+pub mod orders;
 pub mod selection;
 pub mod window;
 
@@ -13,9 +19,25 @@ pub struct InputMap {
     pub right: KeyCode,
     pub turn_r: KeyCode,
     pub turn_l: KeyCode,
-    pub close: KeyCode,
+    pub pitch_up: KeyCode,
+    pub pitch_down: KeyCode,
+    pub pause: KeyCode,
     pub fullscreen: KeyCode,
-    pub debug_menu: KeyCode,
+    pub bookmarks: [KeyCode; BOOKMARK_SLOTS],
+    pub bookmark_modifier: KeyCode,
+    pub last_alert: KeyCode,
+    pub quick_save: KeyCode,
+    pub quick_load: KeyCode,
+    pub speed_up: KeyCode,
+    pub speed_down: KeyCode,
+    pub train: KeyCode,
+    pub build: KeyCode,
+    pub patrol: KeyCode,
+    pub stance: KeyCode,
+    pub replay_vision: [KeyCode; REPLAY_VISION_SLOTS],
+    pub skip_camera_path: KeyCode,
+    pub debug_camera_path: KeyCode,
+    pub debug_eliminate: KeyCode,
 }
 
 impl Default for InputMap {
@@ -27,11 +49,27 @@ impl Default for InputMap {
             right: KeyCode::KeyD,
             turn_r: KeyCode::KeyQ,
             turn_l: KeyCode::KeyE,
-            close: KeyCode::Escape,
+            pitch_up: KeyCode::PageUp,
+            pitch_down: KeyCode::PageDown,
+            pause: KeyCode::Escape,
             fullscreen: KeyCode::F11,
+            bookmarks: [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4],
+            bookmark_modifier: KeyCode::ControlLeft,
+            last_alert: KeyCode::Space,
+            quick_save: KeyCode::F5,
+            quick_load: KeyCode::F9,
+            speed_up: KeyCode::Equal,
+            speed_down: KeyCode::Minus,
+            train: KeyCode::KeyT,
+            build: KeyCode::KeyB,
+            patrol: KeyCode::KeyP,
+            stance: KeyCode::KeyV,
+            replay_vision: [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4],
+            skip_camera_path: KeyCode::Enter,
 
             // debug keys
-            debug_menu: KeyCode::F3,
+            debug_camera_path: KeyCode::F8,
+            debug_eliminate: KeyCode::F7,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,384 @@
+use avian3d::prelude::PhysicsGizmos;
+use bevy::{input::mouse::MouseMotion, prelude::*, utils::HashMap};
+use bevy_mod_picking::{backend::PointerHits, pointer::PointerId, selection::PointerMultiselect};
+
+use crate::{
+    entities::{
+        structures::{is_valid_site, preview_color, spawn_placement_preview, structure_transform, PlacementPreview, Structure},
+        units::{behaviour::{BuildOrder, GatherOrder, GuardOrder, OrderQueue, PatrolOrder, Stance, UnitOrder}, orders::{ground_position, AttackOrder, MoveOrder}, Unit},
+        world_objects::ResourceNode,
+        Owner,
+    },
+    resources::player::Player,
+    simulation::{commands::{IssueCommandEvent, PlayerCommand}, fixed::FixedVec2, replay::ReplayPlayback, SimId},
+    states::AppState,
+    ui::cursor::Cursor,
+};
+
+use super::{selection::Selected, InputMap};
+
+// Mouse travel in pixels beyond which a right click counts as a camera drag instead of an order
+const ORDER_CLICK_DRAG_THRESHOLD: f32 = 4.;
+// Order paths float just above the ground so they aren't hidden in it
+const ORDER_PATH_HEIGHT: f32 = 0.1;
+const ORDER_MARKER_RADIUS: f32 = 0.5;
+const MOVE_COLOR: Color = Color::hsla(128., 1., 0.5, 0.75);
+const ATTACK_COLOR: Color = Color::hsla(0., 1., 0.5, 0.75);
+const PATROL_COLOR: Color = Color::hsla(200., 1., 0.5, 0.75);
+const GUARD_COLOR: Color = Color::hsla(50., 1., 0.5, 0.75);
+const BUILD_COLOR: Color = Color::hsla(30., 1., 0.5, 0.75);
+const GATHER_COLOR: Color = Color::hsla(160., 1., 0.5, 0.75);
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct OrdersSet;
+
+// Turns the local player's input into commands for the simulation
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Replays are driven by their recorded commands only
+            .configure_sets(Update, OrdersSet.run_if(
+                in_state(AppState::InGame).and_then(not(resource_exists::<ReplayPlayback>))
+            ))
+            .add_systems(Update, (
+                handle_order_click,
+                handle_production_keys,
+                handle_build_placement,
+                handle_behaviour_keys,
+                issue_selection_commands,
+            ).in_set(OrdersSet))
+            // Also shown in replays, to see what players had planned
+            .add_systems(Update, render_order_paths.run_if(in_state(AppState::InGame)));
+    }
+}
+
+// Nearest thing under the cursor this frame, with the world position it was hit at
+fn cursor_hit(
+    ev_pointer_hits: &mut EventReader<PointerHits>,
+    q_pointer: &Query<&PointerId, With<Cursor>>,
+) -> Option<(Entity, Option<Vec3>)> {
+    let pointer_id = q_pointer.get_single().ok()?;
+    ev_pointer_hits.read()
+        .filter(|pointer_hits| pointer_hits.pointer == *pointer_id)
+        .flat_map(|pointer_hits| pointer_hits.picks.iter())
+        .min_by(|(_, a), (_, b)| a.depth.total_cmp(&b.depth))
+        .map(|(entity, hit_data)| (*entity, hit_data.position))
+}
+
+// Shift adds orders to the end of each unit's queue instead of replacing it
+fn is_queueing(q_multiselect: &Query<&PointerMultiselect, With<Cursor>>) -> bool {
+    q_multiselect.get_single().is_ok_and(|multiselect| multiselect.is_pressed)
+}
+
+// Units of the local player's in the selection, in SimId order
+fn selected_units(player: &Player, q_selected: &Query<(&SimId, &Owner), (With<Selected>, With<Unit>)>) -> Vec<SimId> {
+    let mut units: Vec<SimId> = q_selected.iter()
+        .filter(|(_, owner)| owner.0 == player.id)
+        .map(|(sim_id, _)| *sim_id)
+        .collect();
+    units.sort_unstable();
+    units
+}
+
+// Right click without dragging: gather from the resource node under the cursor, attack the enemy
+// under it, guard one of the player's own entities, otherwise move there
+pub fn handle_order_click(
+    mouse: Res<ButtonInput<MouseButton>>,
+    player: Res<Player>,
+    mut drag_distance: Local<Option<f32>>,
+    mut ev_mouse: EventReader<MouseMotion>,
+    mut ev_pointer_hits: EventReader<PointerHits>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_pointer: Query<&PointerId, With<Cursor>>,
+    q_multiselect: Query<&PointerMultiselect, With<Cursor>>,
+    q_selected: Query<(&SimId, &Owner), (With<Selected>, With<Unit>)>,
+    q_targets: Query<(&SimId, &Owner)>,
+    q_resource_nodes: Query<&SimId, With<ResourceNode>>,
+) {
+    if mouse.just_pressed(MouseButton::Right) {
+        *drag_distance = Some(0.);
+    }
+    let motion: f32 = ev_mouse.read().map(|motion| motion.delta.length()).sum();
+    if let Some(distance) = drag_distance.as_mut() {
+        *distance += motion;
+    }
+    let hit = cursor_hit(&mut ev_pointer_hits, &q_pointer);
+    if !mouse.just_released(MouseButton::Right) {
+        return;
+    }
+    let Some(distance) = drag_distance.take() else { return; };
+    if distance > ORDER_CLICK_DRAG_THRESHOLD {
+        return;
+    }
+
+    let units = selected_units(&player, &q_selected);
+    if units.is_empty() {
+        return;
+    }
+    let Some((entity, position)) = hit else { return; };
+    let queued = is_queueing(&q_multiselect);
+
+    if let Ok(node) = q_resource_nodes.get(entity) {
+        ev_issue.send(IssueCommandEvent {
+            player: player.id,
+            command: PlayerCommand::Gather {
+                units,
+                node: *node,
+                queued,
+            },
+        });
+        return;
+    }
+    let command = match q_targets.get(entity) {
+        Ok((target, owner)) if owner.0 != player.id => PlayerCommand::Attack {
+            units,
+            target: *target,
+            queued,
+        },
+        Ok((target, _)) if !units.contains(target) => PlayerCommand::Guard {
+            units,
+            target: *target,
+            queued,
+        },
+        _ => {
+            let Some(position) = position else { return; };
+            PlayerCommand::Move {
+                units,
+                target: FixedVec2::from_vec2(position.xz()),
+                queued,
+            }
+        },
+    };
+    ev_issue.send(IssueCommandEvent {
+        player: player.id,
+        command,
+    });
+}
+
+// Train a unit at each selected structure
+pub fn handle_production_keys(
+    key: Res<ButtonInput<KeyCode>>,
+    player: Res<Player>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_selected: Query<(&SimId, &Owner), (With<Selected>, With<Structure>)>,
+) {
+    let input_map = InputMap::default();
+    if key.just_pressed(input_map.train) {
+        let mut structures: Vec<SimId> = q_selected.iter()
+            .filter(|(_, owner)| owner.0 == player.id)
+            .map(|(sim_id, _)| *sim_id)
+            .collect();
+        structures.sort_unstable();
+        for structure in structures {
+            ev_issue.send(IssueCommandEvent {
+                player: player.id,
+                command: PlayerCommand::Train {
+                    structure,
+                },
+            });
+        }
+    }
+}
+
+// Holding the build key shows a preview of the structure under the cursor, releasing it sends the
+// selected units to build there if it fits. With shift they build it after their other orders.
+pub fn handle_build_placement(
+    mut commands: Commands,
+    key: Res<ButtonInput<KeyCode>>,
+    player: Res<Player>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ev_pointer_hits: EventReader<PointerHits>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_pointer: Query<&PointerId, With<Cursor>>,
+    q_multiselect: Query<&PointerMultiselect, With<Cursor>>,
+    q_selected_units: Query<(&SimId, &Owner), (With<Selected>, With<Unit>)>,
+    q_obstacles: Query<&Transform, (Or<(With<Structure>, With<ResourceNode>)>, Without<PlacementPreview>)>,
+    mut q_preview: Query<(Entity, &mut PlacementPreview, &mut Transform, &mut Visibility, &Handle<StandardMaterial>)>,
+) {
+    let input_map = InputMap::default();
+    let hit = cursor_hit(&mut ev_pointer_hits, &q_pointer);
+    // Structures only go up where a unit builds them
+    let units = selected_units(&player, &q_selected_units);
+
+    if key.just_pressed(input_map.build) && !units.is_empty() && q_preview.is_empty() {
+        spawn_placement_preview(&mut commands, &mut meshes, &mut materials);
+        return;
+    }
+    let Ok((entity, mut preview, mut transform, mut visibility, material)) = q_preview.get_single_mut() else { return; };
+
+    if !key.pressed(input_map.build) || units.is_empty() {
+        commands.entity(entity).despawn_recursive();
+        if preview.valid && !units.is_empty() {
+            ev_issue.send(IssueCommandEvent {
+                player: player.id,
+                command: PlayerCommand::Build {
+                    position: preview.site,
+                    units,
+                    queued: is_queueing(&q_multiselect),
+                },
+            });
+        }
+        return;
+    }
+
+    // Keep the last site while the cursor isn't over anything
+    let Some((_, Some(position))) = hit else { return; };
+    let site = FixedVec2::from_vec2(position.xz());
+    let valid = is_valid_site(site, q_obstacles.iter().map(ground_position));
+    preview.site = site;
+    *transform = structure_transform(Vec3::new(site.x.to_num(), 0., site.y.to_num()));
+    *visibility = Visibility::Inherited;
+    if preview.valid != valid {
+        preview.valid = valid;
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = preview_color(valid);
+        }
+    }
+}
+
+// Patrol to the point under the cursor, or switch the selected units to the next stance
+pub fn handle_behaviour_keys(
+    key: Res<ButtonInput<KeyCode>>,
+    player: Res<Player>,
+    mut ev_pointer_hits: EventReader<PointerHits>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_pointer: Query<&PointerId, With<Cursor>>,
+    q_multiselect: Query<&PointerMultiselect, With<Cursor>>,
+    q_selected: Query<(&SimId, &Owner), (With<Selected>, With<Unit>)>,
+    q_stances: Query<(&SimId, Option<&Stance>), (With<Selected>, With<Unit>)>,
+) {
+    let input_map = InputMap::default();
+    let hit = cursor_hit(&mut ev_pointer_hits, &q_pointer);
+    let units = selected_units(&player, &q_selected);
+    if units.is_empty() {
+        return;
+    }
+
+    if key.just_pressed(input_map.patrol) {
+        if let Some((_, Some(position))) = hit {
+            ev_issue.send(IssueCommandEvent {
+                player: player.id,
+                command: PlayerCommand::Patrol {
+                    units: units.clone(),
+                    target: FixedVec2::from_vec2(position.xz()),
+                    queued: is_queueing(&q_multiselect),
+                },
+            });
+        }
+    }
+
+    if key.just_pressed(input_map.stance) {
+        // Everything selected follows the first unit to its next stance, so mixed selections line up
+        let current = q_stances.iter()
+            .filter(|(sim_id, _)| units.contains(sim_id))
+            .min_by_key(|(sim_id, _)| **sim_id)
+            .and_then(|(_, stance)| stance.copied())
+            .unwrap_or_default();
+        let stance = current.next();
+        println!("Stance: {:?}", stance);
+        ev_issue.send(IssueCommandEvent {
+            player: player.id,
+            command: PlayerCommand::Stance {
+                units,
+                stance,
+            },
+        });
+    }
+}
+
+// Record the selection whenever it changes, so replays can show it
+pub fn issue_selection_commands(
+    player: Res<Player>,
+    mut removed_selected: RemovedComponents<Selected>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_added: Query<(), Added<Selected>>,
+    q_selected: Query<&SimId, With<Selected>>,
+) {
+    let removed = removed_selected.read().count();
+    if removed == 0 && q_added.is_empty() {
+        return;
+    }
+    let mut units: Vec<SimId> = q_selected.iter().copied().collect();
+    units.sort_unstable();
+    ev_issue.send(IssueCommandEvent {
+        player: player.id,
+        command: PlayerCommand::Select {
+            units,
+        },
+    });
+}
+
+// Points an order leads to, and the color it is drawn in
+fn order_waypoints(order: &UnitOrder, targets: &HashMap<SimId, Vec3>) -> Vec<(Vec3, Color)> {
+    match order {
+        UnitOrder::Move(target) => vec![(order_point(*target), MOVE_COLOR)],
+        UnitOrder::Attack(target) => targets.get(target).map(|target| (*target, ATTACK_COLOR)).into_iter().collect(),
+        UnitOrder::Patrol(points) => points.iter().map(|point| (order_point(*point), PATROL_COLOR)).collect(),
+        UnitOrder::Guard(target) => targets.get(target).map(|target| (*target, GUARD_COLOR)).into_iter().collect(),
+        UnitOrder::Build(site) => vec![(order_point(*site), BUILD_COLOR)],
+        UnitOrder::Gather(node) => targets.get(node).map(|node| (*node, GATHER_COLOR)).into_iter().collect(),
+    }
+}
+
+fn order_point(point: FixedVec2) -> Vec3 {
+    let point = point.to_vec2();
+    Vec3::new(point.x, ORDER_PATH_HEIGHT, point.y)
+}
+
+// Path of each selected unit through what it is doing now and every order queued after it
+pub fn render_order_paths(
+    q_selected: Query<(
+        &Transform,
+        Option<&OrderQueue>,
+        Option<&MoveOrder>,
+        Option<&AttackOrder>,
+        Option<&PatrolOrder>,
+        Option<&GuardOrder>,
+        Option<&BuildOrder>,
+        Option<&GatherOrder>,
+    ), (With<Selected>, With<Unit>)>,
+    q_targets: Query<(&SimId, &Transform)>,
+    mut gizmos: Gizmos<PhysicsGizmos>,
+) {
+    if q_selected.is_empty() {
+        return;
+    }
+    let targets: HashMap<SimId, Vec3> = q_targets.iter()
+        .map(|(sim_id, transform)| (*sim_id, order_point(ground_position(transform))))
+        .collect();
+    for (transform, queue, move_order, attack_order, patrol_order, guard_order, build_order, gather_order) in &q_selected {
+        let current = if let Some(attack_order) = attack_order {
+            order_waypoints(&UnitOrder::Attack(attack_order.target), &targets)
+        } else if let Some(build_order) = build_order {
+            order_waypoints(&UnitOrder::Build(build_order.site), &targets)
+        } else if let Some(patrol_order) = patrol_order {
+            // The rest of the round from the waypoint walked to, back to where it started
+            let count = patrol_order.waypoints.len();
+            let round = (0..=count)
+                .map_while(|step| patrol_order.waypoints.get((patrol_order.next + step) % count.max(1)).copied())
+                .collect();
+            order_waypoints(&UnitOrder::Patrol(round), &targets)
+        } else if let Some(guard_order) = guard_order {
+            order_waypoints(&UnitOrder::Guard(guard_order.target), &targets)
+        } else if let Some(gather_order) = gather_order {
+            order_waypoints(&UnitOrder::Gather(gather_order.node), &targets)
+        } else if let Some(move_order) = move_order {
+            order_waypoints(&UnitOrder::Move(move_order.target), &targets)
+        } else {
+            Vec::new()
+        };
+        let queued = queue.into_iter()
+            .flat_map(|queue| queue.orders.iter())
+            .flat_map(|order| order_waypoints(order, &targets));
+
+        let mut from = order_point(ground_position(transform));
+        for (to, color) in current.into_iter().chain(queued) {
+            gizmos.line(from, to, color);
+            gizmos.circle(to, Dir3::Y, ORDER_MARKER_RADIUS, color);
+            from = to;
+        }
+    }
+}
@@ -3,7 +3,7 @@ use backend::PointerHits;
 use bevy::{pbr::{NotShadowCaster, NotShadowReceiver}, prelude::*};
 use bevy_mod_picking::prelude::*;
 
-use crate::{entities::EntityCollisionLayers, ui::cursor::*};
+use crate::{entities::EntityCollisionLayers, resources::settings::Settings, states::AppState, ui::cursor::*};
 
 use super::camera::PlayerCamera;
 
@@ -31,6 +31,13 @@ pub struct Selectable {
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 struct SelectionSet;
 
+// Own gizmo group so selection outlines can be styled apart from physics debug gizmos
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct SelectionGizmos;
+
+const SELECTION_LINE_WIDTH: f32 = 2.;
+const HIGH_CONTRAST_SELECTION_LINE_WIDTH: f32 = 6.;
+
 #[allow(dead_code)]
 #[derive(Default)]
 pub enum SelectionMask {
@@ -42,19 +49,36 @@ pub enum SelectionMask {
     UnitMilitant = 0b0000_1000,
 }
 
-pub fn add_selection_systems(app: &mut App) {
-    app
-        .add_event::<SelectionEvent>()
-        .add_event::<SelectionStartEvent>()
-        .add_systems(Update, (
-            handle_selection_event,
-            handle_selection_start_event,
-            handle_selection,
-            handle_selection_collisions
-                .after(handle_selection),
-            render_selected_entity_aabb,
-            render_selection_collider,
-        ).in_set(SelectionSet));
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<SelectionEvent>()
+            .add_event::<SelectionStartEvent>()
+            .init_gizmo_group::<SelectionGizmos>()
+            .configure_sets(Update, SelectionSet.run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(CursorMode::Selecting), clear_selection_box)
+            .add_systems(Update, apply_selection_gizmo_config
+                .run_if(resource_changed::<Settings>))
+            .add_systems(Update, (
+                handle_selection_start
+                    .run_if(in_state(CursorMode::Idle)),
+                handle_selection_start_event
+                    .after(handle_selection_start),
+                handle_selection
+                    .run_if(in_state(CursorMode::Selecting)),
+                handle_selection_collisions
+                    .after(handle_selection),
+                // Apply clicks and box changes in the frame they happen, so the box doesn't re-send
+                // events for entities whose selection hasn't been applied yet
+                handle_selection_event
+                    .after(handle_selection_start_event)
+                    .after(handle_selection_collisions),
+                render_selected_entity_aabb,
+                render_selection_collider,
+            ).in_set(SelectionSet));
+    }
 }
 
 pub fn handle_selection_collisions(
@@ -94,67 +118,66 @@ pub fn handle_selection_collisions(
     }
 }
 
-pub fn handle_selection(
+pub fn handle_selection_start(
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut ev_selection_start: EventWriter<SelectionStartEvent>,
+) {
+    if mouse.just_pressed(MouseButton::Left) {
+        println!("Sending selection event");
+        ev_selection_start.send(SelectionStartEvent);
+    }
+}
+
+pub fn clear_selection_box(
     mut commands: Commands,
+    q_selection: Query<Entity, With<Selection>>,
+    mut q_cursor: Query<&mut CursorSelection>,
+) {
+    let mut cursor_selection = q_cursor.single_mut();
+    cursor_selection.start = None;
+    for selection_entity in q_selection.iter() {
+        commands.entity(selection_entity).despawn();
+    }
+}
+
+pub fn handle_selection(
     mut q_selection: Query<(Entity, &mut Collider, Mut<Handle<Mesh>>, &mut Transform), With<Selection>>,
-    mut q_cursor: Query<(&mut Cursor, &mut CursorSelection)>,
+    q_cursor: Query<&CursorSelection>,
     q_camera: Query<&PlayerCamera>,
     mut ev_pointer_hits: EventReader<PointerHits>,
     q_collision_layers: Query<&CollisionLayers>,
-    mouse: Res<ButtonInput<MouseButton>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut ev_selection_start: EventWriter<SelectionStartEvent>,
 ) {
-    let (cursor, mut cursor_selection) = q_cursor.single_mut();
-    // Handle selection
-    match cursor.mode {
-        CursorMode::Idle => {
-            if mouse.just_pressed(MouseButton::Left) {
-                println!("Sending selection event");
-                ev_selection_start.send(SelectionStartEvent);
-            }
-            if !mouse.pressed(MouseButton::Left) {
-                cursor_selection.start = None;
-                if let Ok(selection) = q_selection.get_single_mut() {
-                    commands.entity(selection.0).despawn();
-                }
-            }
-        },
-        CursorMode::Selecting => {
-            let camera = q_camera.single();
-            for pointer_hits in ev_pointer_hits.read() {
-                let Some((entity, hit_data)) = pointer_hits.picks.iter().next() else { continue; };
-                let Ok(collision_layers) = q_collision_layers.get(*entity) else { continue; };
-                for selection in q_selection.iter_mut() {
-                    let (
-                        _selection_entity,
-                        mut selection_collider,
-                        selection_mesh,
-                        mut selection_transform
-                    ) = selection;
-                    if collision_layers.memberships & EntityCollisionLayers::Ground == EntityCollisionLayers::Ground {
-                        let (Some(start), Some(position) )= (cursor_selection.start, hit_data.position) else {
-                            return;
-                        };
-                        let (start, end) = (start, position.xz());
-                        let rotation = Quat::from_rotation_y(camera.rotation.y);
-                        let midpoint = start.midpoint(end);
-                        let rot_matrix = Mat2::from_angle(camera.rotation.y);
-                        let (rot_start, rot_end) = (rot_matrix.mul_vec2(start), rot_matrix.mul_vec2(end));
-                        let pos_dif = Vec2::abs(rot_start - rot_end);
-                        meshes.insert(selection_mesh.id(), Cuboid::new(pos_dif.x, 1000.0, pos_dif.y).into());
-                        selection_collider.set_shape(SharedShape::cuboid(pos_dif.x / 2., 500.0, pos_dif.y / 2.));
-                        *selection_transform = Transform {
-                            translation: Vec3::new(midpoint.x, 0.0, midpoint.y),
-                            rotation,
-                            ..default()
-                        }
-                    }
+    let cursor_selection = q_cursor.single();
+    let camera = q_camera.single();
+    for pointer_hits in ev_pointer_hits.read() {
+        let Some((entity, hit_data)) = pointer_hits.picks.iter().next() else { continue; };
+        let Ok(collision_layers) = q_collision_layers.get(*entity) else { continue; };
+        for selection in q_selection.iter_mut() {
+            let (
+                _selection_entity,
+                mut selection_collider,
+                selection_mesh,
+                mut selection_transform
+            ) = selection;
+            if collision_layers.memberships & EntityCollisionLayers::Ground == EntityCollisionLayers::Ground {
+                let (Some(start), Some(position) )= (cursor_selection.start, hit_data.position) else {
+                    return;
+                };
+                let (start, end) = (start, position.xz());
+                let rotation = Quat::from_rotation_y(camera.rotation.y);
+                let midpoint = start.midpoint(end);
+                let rot_matrix = Mat2::from_angle(camera.rotation.y);
+                let (rot_start, rot_end) = (rot_matrix.mul_vec2(start), rot_matrix.mul_vec2(end));
+                let pos_dif = Vec2::abs(rot_start - rot_end);
+                meshes.insert(selection_mesh.id(), Cuboid::new(pos_dif.x, 1000.0, pos_dif.y).into());
+                selection_collider.set_shape(SharedShape::cuboid(pos_dif.x / 2., 500.0, pos_dif.y / 2.));
+                *selection_transform = Transform {
+                    translation: Vec3::new(midpoint.x, 0.0, midpoint.y),
+                    rotation,
+                    ..default()
                 }
             }
-        }, 
-        _ => {
-            return;
         }
     }
 }
@@ -263,18 +286,39 @@ pub fn render_selection_collider(
     }
 }
 
+pub fn apply_selection_gizmo_config(
+    settings: Res<Settings>,
+    mut config_store: ResMut<GizmoConfigStore>,
+) {
+    let (config, _) = config_store.config_mut::<SelectionGizmos>();
+    if settings.accessibility.high_contrast_selection {
+        config.line_width = HIGH_CONTRAST_SELECTION_LINE_WIDTH;
+        // Draw over any geometry in front of the selection
+        config.depth_bias = -1.;
+    } else {
+        config.line_width = SELECTION_LINE_WIDTH;
+        config.depth_bias = 0.;
+    }
+}
+
 pub fn render_selected_entity_aabb(
     aabbs: Query<(
         Entity,
         &ColliderAabb
     ), With<Selected>>,
-    mut gizmos: Gizmos<PhysicsGizmos>,
+    settings: Res<Settings>,
+    mut gizmos: Gizmos<SelectionGizmos>,
 ) {
+    let color = if settings.accessibility.high_contrast_selection {
+        Color::srgb(1., 1., 0.)
+    } else {
+        Color::hsla(0., 100.0, 0.5, 0.75)
+    };
     for (_entity, aabb) in &aabbs {
         gizmos.cuboid(
             Transform::from_scale(Vector::from(aabb.size()).f32())
                 .with_translation(Vector::from(aabb.center()).f32()),
-            Color::hsla(0., 100.0, 0.5, 0.75),
+            color,
         );
     }
 }
\ No newline at end of file
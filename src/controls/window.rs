@@ -1,30 +1,50 @@
-use bevy::{prelude::*, window::{PrimaryWindow, WindowMode}};
+use bevy::{prelude::*, window::WindowMode};
+
+use crate::{resources::settings::Settings, states::AppState, ui::pause::PauseMenu};
 
 use super::InputMap;
 
+pub struct WindowControlsPlugin;
+
+impl Plugin for WindowControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_key_window_functions);
+    }
+}
+
 pub fn handle_key_window_functions(
-    mut app_exit_events: EventWriter<AppExit>,
-    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut settings: ResMut<Settings>,
     key: Res<ButtonInput<KeyCode>>,
+    app_state: Res<State<AppState>>,
+    pause_menu: Option<Res<State<PauseMenu>>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_pause_menu: ResMut<NextState<PauseMenu>>,
 ) {
-    let mut primary_window = q_windows.single_mut();
     let input_map = InputMap::default();
 
-    if key.just_pressed(input_map.close) {
-        app_exit_events.send(AppExit::Success);
+    if key.just_pressed(input_map.pause) {
+        match app_state.get() {
+            AppState::InGame => next_app_state.set(AppState::Paused),
+            AppState::Paused => {
+                // Escape backs out of a sub page first, then resumes
+                match pause_menu.as_deref().map(State::get) {
+                    Some(PauseMenu::Main) | None => next_app_state.set(AppState::InGame),
+                    Some(_) => next_pause_menu.set(PauseMenu::Main),
+                }
+            },
+            _ => {},
+        }
     }
 
     if key.just_pressed(input_map.fullscreen) {
-        match primary_window.mode {
-            WindowMode::Windowed => {
-                primary_window.mode = WindowMode::BorderlessFullscreen;
-            },
-            WindowMode::BorderlessFullscreen => {
-                primary_window.mode = WindowMode::Windowed;
-            },
-            _ => {
-                primary_window.mode = WindowMode::Windowed;
-            }
-        }
+        // Applied to the window by apply_video_settings
+        settings.video.window_mode = toggle_window_mode(settings.video.window_mode);
+    }
+}
+
+pub fn toggle_window_mode(mode: WindowMode) -> WindowMode {
+    match mode {
+        WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+        _ => WindowMode::Windowed,
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,16 @@
+use avian3d::prelude::{PhysicsDebugPlugin, PhysicsGizmos};
 use bevy::prelude::*;
-use bevy_mod_picking::prelude::Pickable;
+use bevy_mod_picking::{debug::DebugPickingMode, prelude::Pickable};
 
-use crate::{controls::InputMap, ui::cursor::Cursor, Game};
+use crate::{
+    controls::{cinematic::{CameraPathFinishedEvent, PlayCameraPathEvent}, InputMap},
+    entities::Owner,
+    resources::{player::{Player, PlayerId}, settings::Settings},
+    simulation::{commands::{IssueCommandEvent, PlayerCommand}, replay::ReplayPlayback},
+    states::AppState,
+    ui::cursor::CursorMode,
+    Game,
+};
 
 #[derive(Component, Default)]
 pub struct DebugDisplay {
@@ -14,11 +23,31 @@ pub struct CursorModeDebugDisplay;
 #[derive(Component)]
 pub struct KeyPressDebugDisplay;
 
-pub fn add_debug_systems(app: &mut App) {
-    app
-        .add_systems(Startup, setup_debug_screen)
-        .add_systems(Update, handle_debug_keys)
-        .add_systems(Update, update_debug_screen);
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Game>()
+            .add_plugins(PhysicsDebugPlugin::default());
+        if !cfg!(debug_assertions) {
+            // Keep the physics gizmo group registered but draw nothing outside of debug builds
+            app.insert_gizmo_config(
+                PhysicsGizmos::none(),
+                GizmoConfig::default(),
+            );
+            return;
+        }
+        app
+            .insert_resource(DebugPickingMode::Normal)
+            .add_systems(Startup, setup_debug_screen)
+            .add_systems(Update, handle_debug_keys)
+            // Replays are driven by their recorded commands only
+            .add_systems(Update, handle_debug_eliminate
+                .run_if(in_state(AppState::InGame).and_then(not(resource_exists::<ReplayPlayback>))))
+            .add_systems(Update, report_camera_path_finished)
+            .add_systems(Update, update_debug_screen);
+    }
 }
 
 
@@ -57,12 +86,15 @@ pub fn setup_debug_screen(
 pub fn handle_debug_keys(
     mut commands: Commands,
     key: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
     mut q_debug_menu: Query<(Entity, &mut DebugDisplay)>,
+    mut ev_play_camera_path: EventWriter<PlayCameraPathEvent>,
 ) {
     let input_map = InputMap::default();
     let (debug_menu_entity, mut debug_display) = q_debug_menu.single_mut();
 
-    if key.just_pressed(input_map.debug_menu) {
+    if key.just_pressed(settings.input.debug_menu_key) {
         let mut visibility: Visibility = Visibility::Visible;
         let mut debug_menu_commands = commands.entity(debug_menu_entity);
         if debug_display.visibility == Visibility::Visible {
@@ -80,11 +112,52 @@ pub fn handle_debug_keys(
             ..default()
         });
     }
+
+    if key.just_pressed(input_map.debug_camera_path) {
+        ev_play_camera_path.send(PlayCameraPathEvent(asset_server.load("camera_paths/intro.campath.ron")));
+    }
+}
+
+// Wipe out every opponent to test the end of match flow. Each of them resigns through the
+// simulation like any other command, so replays see it happen too. Networked matches only take
+// the local player's commands, so it does nothing there.
+pub fn handle_debug_eliminate(
+    key: Res<ButtonInput<KeyCode>>,
+    player: Res<Player>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    q_owned: Query<&Owner>,
+) {
+    let input_map = InputMap::default();
+    if key.just_pressed(input_map.debug_eliminate) {
+        let mut opponents: Vec<PlayerId> = q_owned.iter()
+            .map(|owner| owner.0)
+            .filter(|owner| *owner != player.id)
+            .collect();
+        opponents.sort_unstable();
+        opponents.dedup();
+        for opponent in opponents {
+            ev_issue.send(IssueCommandEvent {
+                player: opponent,
+                command: PlayerCommand::Eliminate {
+                    player: opponent,
+                },
+            });
+        }
+    }
+}
+
+// Camera paths are started from the debug keys, so say when one is done
+pub fn report_camera_path_finished(
+    mut ev_finished: EventReader<CameraPathFinishedEvent>,
+) {
+    for CameraPathFinishedEvent(path) in ev_finished.read() {
+        println!("Camera path {:?} finished", path.path());
+    }
 }
 
 pub fn update_debug_screen(
     mut commands: Commands,
-    q_cursor: Query<&Cursor>,
+    cursor_mode: Option<Res<State<CursorMode>>>,
     asset_server: Res<AssetServer>,
     key: Res<ButtonInput<KeyCode>>,
     mut q_cursor_mode_debug_display: Query<Entity, With<CursorModeDebugDisplay>>,
@@ -110,7 +183,7 @@ pub fn update_debug_screen(
         text_style.to_owned()
     ));
 
-    let cursor_mode = q_cursor.single().mode;
+    let cursor_mode = cursor_mode.map_or("-".to_owned(), |cursor_mode| cursor_mode.get().to_string());
     let cursor_mode_debug_display = q_cursor_mode_debug_display.single_mut();
 
     // Create cursor mode display
@@ -0,0 +1,135 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    resources::{player::PlayerId, stats::KillEvent},
+    simulation::{fixed::FixedPoint, SimId, SimulationSet},
+};
+
+use super::{units::orders::{ground_position, pursue_targets, AttackOrder, ATTACK_RANGE}, Owner};
+
+// Damage a unit's weapon deals per shot
+pub const WEAPON_DAMAGE: u32 = 10;
+// Ticks between shots
+pub const WEAPON_COOLDOWN: u32 = 15;
+// Attackers stop at attack range, the slack covers being pushed around by other units
+const WEAPON_RANGE_SLACK: FixedPoint = FixedPoint::from_int(1);
+
+// Damage an entity can take before it is destroyed
+#[derive(Clone, Copy, Component, Debug)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+    // Credited with the kill once it is destroyed
+    pub last_attacker: Option<PlayerId>,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Health {
+            current: max,
+            max,
+            last_attacker: None,
+        }
+    }
+}
+
+// Fires at whatever the unit is ordered to attack once it is in range
+#[derive(Clone, Copy, Component, Debug)]
+pub struct Weapon {
+    pub damage: u32,
+    pub cooldown: u32,
+    // Ticks until the next shot
+    pub ready_in: u32,
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Weapon {
+            damage: WEAPON_DAMAGE,
+            cooldown: WEAPON_COOLDOWN,
+            ready_in: 0,
+        }
+    }
+}
+
+// Sent for every shot, for alerts and sound
+#[derive(Event)]
+pub struct WeaponFiredEvent {
+    pub attacker: PlayerId,
+    // None when shooting at something nobody owns
+    pub victim: Option<PlayerId>,
+    pub from: Vec3,
+    pub to: Vec3,
+}
+
+// Damage dealt and entities destroyed as part of the simulation
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<WeaponFiredEvent>()
+            .add_systems(FixedUpdate, (
+                fire_weapons,
+                resolve_deaths,
+            ).chain().after(pursue_targets).in_set(SimulationSet::Logic));
+    }
+}
+
+// Attackers fire in SimId order, so when two shots finish off the same target every peer agrees
+// on who got it
+pub fn fire_weapons(
+    mut ev_fired: EventWriter<WeaponFiredEvent>,
+    mut q_attackers: Query<(&SimId, &Owner, &Transform, Option<&AttackOrder>, &mut Weapon)>,
+    mut q_targets: Query<(Entity, &SimId, Option<&Owner>, &Transform, &mut Health)>,
+) {
+    let mut attackers: Vec<_> = q_attackers.iter_mut().collect();
+    attackers.sort_unstable_by_key(|(sim_id, ..)| **sim_id);
+    let targets: HashMap<SimId, Entity> = q_targets.iter()
+        .map(|(entity, sim_id, ..)| (*sim_id, entity))
+        .collect();
+    let range = ATTACK_RANGE + WEAPON_RANGE_SLACK;
+    for (_, owner, transform, attack_order, mut weapon) in attackers {
+        if weapon.ready_in > 0 {
+            weapon.ready_in -= 1;
+            continue;
+        }
+        let Some(entity) = attack_order.and_then(|attack_order| targets.get(&attack_order.target)) else { continue; };
+        let Ok((_, _, target_owner, target_transform, mut health)) = q_targets.get_mut(*entity) else { continue; };
+        if target_owner == Some(owner) || health.current == 0 {
+            continue;
+        }
+        if ground_position(transform).distance(ground_position(target_transform)) > range {
+            continue;
+        }
+        health.current = health.current.saturating_sub(weapon.damage);
+        health.last_attacker = Some(owner.0);
+        weapon.ready_in = weapon.cooldown;
+        ev_fired.send(WeaponFiredEvent {
+            attacker: owner.0,
+            victim: target_owner.map(|owner| owner.0),
+            from: transform.translation,
+            to: target_transform.translation,
+        });
+    }
+}
+
+// Remove everything that ran out of health, crediting whoever dealt the last blow
+pub fn resolve_deaths(
+    mut commands: Commands,
+    mut ev_kill: EventWriter<KillEvent>,
+    q_health: Query<(Entity, &Health, Option<&Owner>)>,
+) {
+    for (entity, health, owner) in q_health.iter() {
+        if health.current > 0 {
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+        if let (Some(killer), Some(victim)) = (health.last_attacker, owner) {
+            ev_kill.send(KillEvent {
+                killer,
+                victim: victim.0,
+            });
+        }
+    }
+}
@@ -2,8 +2,11 @@ use avian3d::prelude::*;
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
-use crate::controls::selection::Selectable;
+use crate::{controls::selection::Selectable, resources::player::PlayerId};
 
+pub mod combat;
+pub mod structures;
+pub mod units;
 pub mod world_objects;
 
 #[derive(Copy, Clone, PhysicsLayer)]
@@ -13,6 +16,14 @@ pub enum EntityCollisionLayers {
     Selectable,
 }
 
+// The player an entity belongs to
+#[derive(Clone, Copy, Component, Debug, Default, PartialEq, Eq)]
+pub struct Owner(pub PlayerId);
+
+// Resources spent to produce an entity, also used to value armies
+#[derive(Clone, Copy, Component, Debug, Default)]
+pub struct Cost(pub u32);
+
 #[derive(Bundle)]
 pub struct SelectableActorBundle {
     avian_pickable: AvianPickable,
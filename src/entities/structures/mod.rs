@@ -0,0 +1,115 @@
+use avian3d::prelude::{Collider, CollisionLayers, LayerMask, RigidBody};
+use bevy::prelude::*;
+use bevy_mod_picking::{prelude::{AvianPickable, Pickable}, PickableBundle};
+
+use crate::{controls::selection::{Selectable, SelectionMask}, resources::{player::PlayerId, settings::Settings}, simulation::{fixed::{FixedPoint, FixedVec2}, SimId}, states::InMatch};
+
+use super::{combat::Health, Cost, EntityCollisionLayers, Owner};
+
+// Resources spent to build a structure
+pub const STRUCTURE_COST: u32 = 150;
+pub const STRUCTURE_HEALTH: u32 = 500;
+// Headquarters take a lot longer to bring down than anything built during the match
+pub const HQ_HEALTH: u32 = 1000;
+const STRUCTURE_SIZE: Vec3 = Vec3::new(3.0, 2.0, 3.0);
+// Room a structure needs from other structures and resource nodes
+pub const BUILD_SPACING: FixedPoint = FixedPoint::from_int(4);
+const PREVIEW_VALID_COLOR: Color = Color::srgba(0.2, 1.0, 0.2, 0.4);
+const PREVIEW_INVALID_COLOR: Color = Color::srgba(1.0, 0.2, 0.2, 0.4);
+
+// Buildings, which count towards elimination separately from units
+#[derive(Component, Default)]
+pub struct Structure;
+
+// Ghost of a structure being positioned by the player before construction
+#[derive(Component, Default)]
+pub struct PlacementPreview {
+    pub site: FixedVec2,
+    pub valid: bool,
+}
+
+// Sent when builders put a structure up
+#[derive(Event)]
+pub struct ConstructionCompleteEvent {
+    pub player: PlayerId,
+    pub position: Vec3,
+}
+
+// Whether a structure fits at the site, given the ground positions of the structures and resource
+// nodes already there
+pub fn is_valid_site(site: FixedVec2, obstacles: impl IntoIterator<Item = FixedVec2>) -> bool {
+    obstacles.into_iter().all(|obstacle| obstacle.distance(site) >= BUILD_SPACING)
+}
+
+// Tint for a placement preview, depending on whether the structure fits where it stands
+pub fn preview_color(valid: bool) -> Color {
+    if valid { PREVIEW_VALID_COLOR } else { PREVIEW_INVALID_COLOR }
+}
+
+// Hidden until it is first moved to a site
+pub fn spawn_placement_preview(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) -> Entity {
+    commands.spawn((
+        StateScoped(InMatch),
+        PlacementPreview::default(),
+        PbrBundle {
+            mesh: meshes.add(Cuboid::from_size(STRUCTURE_SIZE)),
+            material: materials.add(StandardMaterial {
+                base_color: preview_color(false),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    )).id()
+}
+
+// Where a structure on the ground at the given position stands
+pub fn structure_transform(translation: Vec3) -> Transform {
+    Transform::from_translation(translation + Vec3::Y * STRUCTURE_SIZE.y / 2.)
+}
+
+// Structures built during the match, standing on the ground at the given position
+pub fn spawn_structure(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    settings: &Settings,
+    sim_id: SimId,
+    player_id: PlayerId,
+    translation: Vec3,
+) -> Entity {
+    commands.spawn((
+        StateScoped(InMatch),
+        AvianPickable,
+        PickableBundle {
+            pickable: Pickable {
+                should_block_lower: true,
+                is_hoverable: true,
+            },
+            ..default()
+        },
+        RigidBody::Static,
+        Collider::cuboid(STRUCTURE_SIZE.x, STRUCTURE_SIZE.y, STRUCTURE_SIZE.z),
+        CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+        sim_id,
+        Owner(player_id),
+        Structure,
+        Cost(STRUCTURE_COST),
+        Health::new(STRUCTURE_HEALTH),
+        Selectable {
+            selection_mask: SelectionMask::Structure
+        },
+        PbrBundle {
+            mesh: meshes.add(Cuboid::from_size(STRUCTURE_SIZE)),
+            material: materials.add(settings.accessibility.team_palette.color(player_id)),
+            transform: structure_transform(translation),
+            ..default()
+        },
+    )).id()
+}
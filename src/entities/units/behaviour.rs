@@ -0,0 +1,490 @@
+use std::collections::VecDeque;
+
+use avian3d::prelude::LinearVelocity;
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::{structures::{is_valid_site, spawn_structure, ConstructionCompleteEvent, Structure, STRUCTURE_COST}, world_objects::ResourceNode, Owner},
+    resources::{player::{PlayerId, Stockpiles}, settings::Settings, stats::ResourcesGatheredEvent},
+    simulation::{fixed::{FixedPoint, FixedVec2}, SimId, SimIdAllocator, SimulationSet},
+};
+
+use super::{orders::{ground_position, move_units, pursue_targets, AttackOrder, MoveOrder}, Unit};
+
+// Close enough to a patrol waypoint to head for the next one
+const WAYPOINT_RADIUS: FixedPoint = FixedPoint::from_int(2);
+// Guards stay within this distance of what they guard
+const GUARD_DISTANCE: FixedPoint = FixedPoint::from_int(3);
+// Close enough to a resource node or drop-off structure to use it
+const GATHER_RANGE: FixedPoint = FixedPoint::from_int(4);
+// Resources a gatherer brings back per trip
+pub const CARRY_CAPACITY: u32 = 20;
+// Ticks spent at the node filling up
+const HARVEST_TICKS: u32 = 45;
+
+// How a unit reacts to enemies it wasn't ordered to attack
+#[derive(Clone, Copy, Component, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Stance {
+    // Chases enemies that come near, then returns
+    #[default]
+    Aggressive,
+    // Only fights enemies close by and doesn't stray far
+    Defensive,
+    // Fights enemies in range without moving
+    HoldPosition,
+    // Ignores enemies
+    Passive,
+}
+
+impl Stance {
+    pub const ALL: [Stance; 4] = [Stance::Aggressive, Stance::Defensive, Stance::HoldPosition, Stance::Passive];
+
+    // Distance at which idle units notice enemies, None if they never do
+    pub fn acquire_range(&self) -> Option<FixedPoint> {
+        match self {
+            Stance::Aggressive => Some(FixedPoint::from_int(10)),
+            Stance::Defensive => Some(FixedPoint::from_int(6)),
+            Stance::HoldPosition => Some(FixedPoint::from_int(5)),
+            Stance::Passive => None,
+        }
+    }
+
+    // Distance a unit chases from where it noticed an enemy before giving up
+    pub fn leash(&self) -> FixedPoint {
+        match self {
+            Stance::Aggressive => FixedPoint::from_int(15),
+            Stance::Defensive => FixedPoint::from_int(4),
+            Stance::HoldPosition | Stance::Passive => FixedPoint::ZERO,
+        }
+    }
+
+    // The one after this, for cycling through stances with a single key
+    pub fn next(&self) -> Stance {
+        let index = Stance::ALL.iter().position(|stance| stance == self).unwrap_or(0);
+        Stance::ALL[(index + 1) % Stance::ALL.len()]
+    }
+}
+
+// Anything a unit can be told to do, as kept in its order queue
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnitOrder {
+    Move(FixedVec2),
+    Attack(SimId),
+    // Walk between where the patrol starts and each of the points, over and over
+    Patrol(Vec<FixedVec2>),
+    // Follow a friendly entity and fight what comes near it
+    Guard(SimId),
+    // Walk to the site and put up a structure there
+    Build(FixedVec2),
+    // Carry resources from a node to the nearest of the player's structures until it runs out
+    Gather(SimId),
+}
+
+// Orders waiting for the unit, given while holding shift. An order given without shift replaces
+// everything, including what the unit is doing right now.
+#[derive(Clone, Component, Debug, Default)]
+pub struct OrderQueue {
+    pub orders: VecDeque<UnitOrder>,
+    // The first order starts right away instead of after the current one
+    pub interrupt: bool,
+}
+
+impl OrderQueue {
+    // Patrol points queued one after another make a single route
+    pub fn give(&mut self, order: UnitOrder, queued: bool) {
+        if !queued {
+            self.orders.clear();
+            self.interrupt = true;
+        }
+        if let (UnitOrder::Patrol(points), Some(UnitOrder::Patrol(route))) = (&order, self.orders.back_mut()) {
+            route.extend(points.iter().copied());
+            return;
+        }
+        self.orders.push_back(order);
+    }
+}
+
+#[derive(Clone, Component, Debug)]
+pub struct PatrolOrder {
+    pub waypoints: Vec<FixedVec2>,
+    // Waypoint currently walked to
+    pub next: usize,
+}
+
+#[derive(Clone, Copy, Component, Debug)]
+pub struct GuardOrder {
+    pub target: SimId,
+}
+
+// Paid for once the unit reaches the site
+#[derive(Clone, Copy, Component, Debug)]
+pub struct BuildOrder {
+    pub site: FixedVec2,
+}
+
+#[derive(Clone, Copy, Component, Debug)]
+pub struct GatherOrder {
+    pub node: SimId,
+    pub carrying: u32,
+    // Ticks spent filling up at the node this trip
+    pub progress: u32,
+}
+
+// Fighting an enemy the unit noticed by itself. It goes back to the anchor once the enemy is gone
+// or the chase leads too far away.
+#[derive(Clone, Copy, Component, Debug)]
+pub struct Engagement {
+    pub anchor: FixedVec2,
+    pub returning: bool,
+}
+
+pub struct UnitBehaviourPlugin;
+
+impl Plugin for UnitBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ConstructionCompleteEvent>()
+            .add_systems(FixedUpdate, (
+                leash_engagements,
+                build_structures,
+                gather_resources,
+                follow_order_queues,
+                patrol_waypoints,
+                guard_targets,
+                acquire_targets,
+            ).chain().before(move_units).before(pursue_targets).in_set(SimulationSet::Logic));
+    }
+}
+
+// Drop whatever the unit was doing and start on the order
+fn start_order(commands: &mut Commands, entity: Entity, order: UnitOrder, position: FixedVec2) {
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.remove::<(MoveOrder, AttackOrder, PatrolOrder, GuardOrder, BuildOrder, GatherOrder, Engagement)>();
+    match order {
+        UnitOrder::Move(target) => {
+            entity_commands.insert(MoveOrder { target });
+        },
+        UnitOrder::Attack(target) => {
+            entity_commands.insert(AttackOrder { target });
+        },
+        UnitOrder::Patrol(points) => {
+            let mut waypoints = vec![position];
+            waypoints.extend(points);
+            entity_commands.insert(PatrolOrder { waypoints, next: 1 });
+        },
+        UnitOrder::Guard(target) => {
+            entity_commands.insert(GuardOrder { target });
+        },
+        UnitOrder::Build(site) => {
+            entity_commands.insert((BuildOrder { site }, MoveOrder { target: site }));
+        },
+        UnitOrder::Gather(node) => {
+            entity_commands.insert(GatherOrder { node, carrying: 0, progress: 0 });
+        },
+    }
+}
+
+// Give up chases that went too far, then walk back to where they started
+pub fn leash_engagements(
+    mut commands: Commands,
+    mut q_engaged: Query<(Entity, &Transform, &mut Engagement, &mut LinearVelocity, Option<&AttackOrder>, Option<&Stance>, Has<MoveOrder>, Has<GuardOrder>)>,
+    q_targets: Query<(&SimId, &Transform)>,
+) {
+    if q_engaged.is_empty() {
+        return;
+    }
+    let targets: HashMap<SimId, FixedVec2> = q_targets.iter()
+        .map(|(sim_id, transform)| (*sim_id, ground_position(transform)))
+        .collect();
+    for (entity, transform, mut engagement, mut velocity, attack_order, stance, moving, guarding) in q_engaged.iter_mut() {
+        if engagement.returning {
+            if !moving {
+                commands.entity(entity).remove::<Engagement>();
+            }
+            continue;
+        }
+        let stance = stance.copied().unwrap_or_default();
+        let target = attack_order.and_then(|attack_order| targets.get(&attack_order.target));
+        let in_reach = match (target, stance.acquire_range()) {
+            (Some(target), Some(range)) => {
+                // Units holding position never chase, being pushed off the spot doesn't count as straying
+                let stayed = stance == Stance::HoldPosition
+                    || ground_position(transform).distance(engagement.anchor) <= stance.leash();
+                stayed && target.distance(engagement.anchor) <= range + stance.leash()
+            },
+            _ => false,
+        };
+        if in_reach {
+            continue;
+        }
+        commands.entity(entity).remove::<AttackOrder>();
+        // Guards go back to following, units holding position never left it
+        if guarding || stance.leash() == FixedPoint::ZERO {
+            velocity.x = 0.;
+            velocity.z = 0.;
+            commands.entity(entity).remove::<Engagement>();
+        } else {
+            engagement.returning = true;
+            commands.entity(entity).insert(MoveOrder {
+                target: engagement.anchor,
+            });
+        }
+    }
+}
+
+// Builders that reached their site put the structure up. Units sent to the same site together
+// only build it once, the others are done as soon as it stands.
+pub fn build_structures(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut stockpiles: ResMut<Stockpiles>,
+    mut sim_ids: ResMut<SimIdAllocator>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ev_complete: EventWriter<ConstructionCompleteEvent>,
+    mut q_builders: Query<(Entity, &SimId, &Owner, &BuildOrder, &mut LinearVelocity, Has<MoveOrder>)>,
+    q_obstacles: Query<&Transform, Or<(With<Structure>, With<ResourceNode>)>>,
+) {
+    if q_builders.is_empty() {
+        return;
+    }
+    let mut obstacles: Vec<FixedVec2> = q_obstacles.iter().map(ground_position).collect();
+    let mut builders: Vec<_> = q_builders.iter_mut().collect();
+    builders.sort_unstable_by_key(|(_, sim_id, ..)| **sim_id);
+    for (entity, _, owner, build_order, mut velocity, moving) in builders {
+        let taken = !is_valid_site(build_order.site, obstacles.iter().copied());
+        if moving && !taken {
+            continue;
+        }
+        commands.entity(entity).remove::<(BuildOrder, MoveOrder)>();
+        if taken {
+            velocity.x = 0.;
+            velocity.z = 0.;
+            continue;
+        }
+        if !stockpiles.spend(owner.0, STRUCTURE_COST) {
+            println!("Player {} can't afford a structure", owner.0);
+            continue;
+        }
+        let translation = Vec3::new(build_order.site.x.to_num(), 0., build_order.site.y.to_num());
+        spawn_structure(&mut commands, &mut meshes, &mut materials, &settings, sim_ids.next(), owner.0, translation);
+        ev_complete.send(ConstructionCompleteEvent {
+            player: owner.0,
+            position: translation,
+        });
+        obstacles.push(build_order.site);
+    }
+}
+
+// Gatherers fill up at their node, carry it to the nearest of their player's structures and go
+// back for more. Nodes are drained in SimId order of the gatherers, so every peer empties them alike.
+pub fn gather_resources(
+    mut commands: Commands,
+    mut stockpiles: ResMut<Stockpiles>,
+    mut ev_gathered: EventWriter<ResourcesGatheredEvent>,
+    mut q_gatherers: Query<(Entity, &SimId, &Owner, &Transform, &mut GatherOrder, &mut LinearVelocity, Option<&MoveOrder>)>,
+    mut q_nodes: Query<(Entity, &SimId, &Transform, &mut ResourceNode)>,
+    q_structures: Query<(&SimId, &Owner, &Transform), With<Structure>>,
+) {
+    let mut gatherers: Vec<_> = q_gatherers.iter_mut().collect();
+    if gatherers.is_empty() {
+        return;
+    }
+    gatherers.sort_unstable_by_key(|(_, sim_id, ..)| **sim_id);
+    let nodes: HashMap<SimId, (Entity, FixedVec2)> = q_nodes.iter()
+        .map(|(entity, sim_id, transform, _)| (*sim_id, (entity, ground_position(transform))))
+        .collect();
+    let mut structures: Vec<(SimId, PlayerId, FixedVec2)> = q_structures.iter()
+        .map(|(sim_id, owner, transform)| (*sim_id, owner.0, ground_position(transform)))
+        .collect();
+    structures.sort_unstable_by_key(|(sim_id, ..)| *sim_id);
+    // Drained this tick, despawning only happens once the tick's commands are applied
+    let mut depleted: Vec<SimId> = Vec::new();
+
+    for (entity, _, owner, transform, mut gather_order, mut velocity, move_order) in gatherers {
+        let position = ground_position(transform);
+        let node = nodes.get(&gather_order.node).filter(|_| !depleted.contains(&gather_order.node));
+        let full = gather_order.carrying >= CARRY_CAPACITY || (node.is_none() && gather_order.carrying > 0);
+        let target = if full {
+            let drop_off = structures.iter()
+                .filter(|(_, structure_owner, _)| *structure_owner == owner.0)
+                .map(|(_, _, structure_position)| (position.distance(*structure_position), *structure_position))
+                .min_by_key(|(distance, _)| *distance);
+            let Some((distance, drop_off)) = drop_off else {
+                // Nowhere to bring it
+                commands.entity(entity).remove::<(GatherOrder, MoveOrder)>();
+                continue;
+            };
+            if distance <= GATHER_RANGE {
+                stockpiles.add(owner.0, gather_order.carrying);
+                ev_gathered.send(ResourcesGatheredEvent {
+                    player: owner.0,
+                    amount: gather_order.carrying,
+                });
+                gather_order.carrying = 0;
+                if node.is_none() {
+                    velocity.x = 0.;
+                    velocity.z = 0.;
+                    commands.entity(entity).remove::<(GatherOrder, MoveOrder)>();
+                }
+                continue;
+            }
+            drop_off
+        } else {
+            let Some((node_entity, node_position)) = node.copied() else {
+                velocity.x = 0.;
+                velocity.z = 0.;
+                commands.entity(entity).remove::<(GatherOrder, MoveOrder)>();
+                continue;
+            };
+            if position.distance(node_position) <= GATHER_RANGE {
+                if move_order.is_some() {
+                    velocity.x = 0.;
+                    velocity.z = 0.;
+                    commands.entity(entity).remove::<MoveOrder>();
+                }
+                gather_order.progress += 1;
+                if gather_order.progress < HARVEST_TICKS {
+                    continue;
+                }
+                gather_order.progress = 0;
+                let Ok((_, _, _, mut resource_node)) = q_nodes.get_mut(node_entity) else { continue; };
+                let harvested = resource_node.amount.min(CARRY_CAPACITY);
+                resource_node.amount -= harvested;
+                gather_order.carrying = harvested;
+                if resource_node.amount == 0 {
+                    depleted.push(gather_order.node);
+                    commands.entity(node_entity).despawn_recursive();
+                }
+                continue;
+            }
+            node_position
+        };
+        if move_order.map(|move_order| move_order.target) != Some(target) {
+            commands.entity(entity).insert(MoveOrder { target });
+        }
+    }
+}
+
+// Start on the next order once the current one is done, or straight away if it replaces it
+pub fn follow_order_queues(
+    mut commands: Commands,
+    mut q_queues: Query<(Entity, &Transform, &mut OrderQueue, Option<&mut PatrolOrder>, Has<MoveOrder>, Has<AttackOrder>, Has<GuardOrder>, Has<GatherOrder>, Has<Engagement>)>,
+) {
+    for (entity, transform, mut queue, patrol, moving, attacking, guarding, gathering, engaged) in q_queues.iter_mut() {
+        if queue.orders.is_empty() {
+            continue;
+        }
+        let patrolling = patrol.is_some();
+        // Patrol points queued during a patrol extend its route
+        if let (false, Some(mut patrol), Some(UnitOrder::Patrol(_))) = (queue.interrupt, patrol, queue.orders.front()) {
+            if let Some(UnitOrder::Patrol(points)) = queue.orders.pop_front() {
+                patrol.waypoints.extend(points);
+            }
+            continue;
+        }
+        // Fights the unit picked itself don't hold up orders
+        let busy = guarding || gathering || ((moving || attacking) && !engaged);
+        if !queue.interrupt && (busy || patrolling) {
+            continue;
+        }
+        queue.interrupt = false;
+        let Some(order) = queue.orders.pop_front() else { continue; };
+        start_order(&mut commands, entity, order, ground_position(transform));
+    }
+}
+
+// Walk to the next waypoint whenever the last one is reached, or after a fight on the way
+pub fn patrol_waypoints(
+    mut commands: Commands,
+    mut q_patrolling: Query<(Entity, &Transform, &mut PatrolOrder), (Without<MoveOrder>, Without<AttackOrder>, Without<Engagement>)>,
+) {
+    for (entity, transform, mut patrol) in q_patrolling.iter_mut() {
+        if patrol.waypoints.is_empty() {
+            commands.entity(entity).remove::<PatrolOrder>();
+            continue;
+        }
+        let position = ground_position(transform);
+        let next = patrol.next % patrol.waypoints.len();
+        patrol.next = if position.distance(patrol.waypoints[next]) <= WAYPOINT_RADIUS {
+            (next + 1) % patrol.waypoints.len()
+        } else {
+            next
+        };
+        commands.entity(entity).insert(MoveOrder {
+            target: patrol.waypoints[patrol.next],
+        });
+    }
+}
+
+// Keep close to the guarded entity, and stop guarding once it is gone
+pub fn guard_targets(
+    mut commands: Commands,
+    mut q_guards: Query<(Entity, &Transform, &GuardOrder, &mut LinearVelocity, Has<MoveOrder>), (Without<AttackOrder>, Without<Engagement>)>,
+    q_targets: Query<(&SimId, &Transform)>,
+) {
+    if q_guards.is_empty() {
+        return;
+    }
+    let targets: HashMap<SimId, FixedVec2> = q_targets.iter()
+        .map(|(sim_id, transform)| (*sim_id, ground_position(transform)))
+        .collect();
+    for (entity, transform, guard_order, mut velocity, moving) in q_guards.iter_mut() {
+        let Some(target) = targets.get(&guard_order.target) else {
+            commands.entity(entity).remove::<(GuardOrder, MoveOrder)>();
+            continue;
+        };
+        if ground_position(transform).distance(*target) > GUARD_DISTANCE {
+            commands.entity(entity).insert(MoveOrder {
+                target: *target,
+            });
+        } else if moving {
+            velocity.x = 0.;
+            velocity.z = 0.;
+            commands.entity(entity).remove::<MoveOrder>();
+        }
+    }
+}
+
+// Units without anything better to do attack the nearest enemy their stance lets them notice
+pub fn acquire_targets(
+    mut commands: Commands,
+    q_units: Query<(Entity, &SimId, &Owner, &Transform, Option<&Stance>, Has<MoveOrder>, Has<PatrolOrder>, Has<GuardOrder>), (With<Unit>, Without<AttackOrder>, Without<GatherOrder>, Without<Engagement>)>,
+    q_enemies: Query<(&SimId, &Owner, &Transform)>,
+) {
+    let mut units: Vec<_> = q_units.iter()
+        // Moving on the player's say-so is left alone, only patrols and guards fight on the way
+        .filter(|(_, _, _, _, _, moving, patrolling, guarding)| !moving || *patrolling || *guarding)
+        .filter_map(|(entity, sim_id, owner, transform, stance, ..)| {
+            let range = stance.copied().unwrap_or_default().acquire_range()?;
+            Some((*sim_id, entity, owner.0, ground_position(transform), range))
+        })
+        .collect();
+    if units.is_empty() {
+        return;
+    }
+    units.sort_unstable_by_key(|(sim_id, ..)| *sim_id);
+    let mut enemies: Vec<(SimId, PlayerId, FixedVec2)> = q_enemies.iter()
+        .map(|(sim_id, owner, transform)| (*sim_id, owner.0, ground_position(transform)))
+        .collect();
+    enemies.sort_unstable_by_key(|(sim_id, ..)| *sim_id);
+
+    for (_, entity, owner, position, range) in units {
+        let nearest = enemies.iter()
+            .filter(|(_, enemy_owner, _)| *enemy_owner != owner)
+            .map(|(sim_id, _, enemy_position)| (position.distance(*enemy_position), *sim_id))
+            .filter(|(distance, _)| *distance <= range)
+            .min();
+        let Some((_, target)) = nearest else { continue; };
+        commands.entity(entity)
+            .remove::<MoveOrder>()
+            .insert((
+                AttackOrder { target },
+                Engagement {
+                    anchor: position,
+                    returning: false,
+                },
+            ));
+    }
+}
@@ -0,0 +1,59 @@
+use avian3d::prelude::{Collider, CollisionLayers, LayerMask, RigidBody};
+use bevy::prelude::*;
+use bevy_mod_picking::{prelude::{AvianPickable, Pickable}, PickableBundle};
+
+use crate::{controls::selection::{Selectable, SelectionMask}, resources::{player::PlayerId, settings::Settings}, simulation::SimId, states::InMatch};
+
+use super::{combat::{Health, Weapon}, Cost, EntityCollisionLayers, Owner};
+
+pub mod behaviour;
+pub mod orders;
+
+// Resources spent to train a unit
+pub const UNIT_COST: u32 = 50;
+pub const UNIT_HEALTH: u32 = 100;
+
+// Mobile entities that count towards a player's population
+#[derive(Component, Default)]
+pub struct Unit;
+
+// Trained units, the same for every player apart from their color
+pub fn spawn_unit(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    settings: &Settings,
+    sim_id: SimId,
+    player_id: PlayerId,
+    translation: Vec3,
+) -> Entity {
+    commands.spawn((
+        StateScoped(InMatch),
+        AvianPickable,
+        PickableBundle {
+            pickable: Pickable {
+                should_block_lower: false,
+                is_hoverable: true,
+            },
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(1.0, 1.0, 1.0),
+        CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+        sim_id,
+        Owner(player_id),
+        Unit,
+        Cost(UNIT_COST),
+        Health::new(UNIT_HEALTH),
+        Weapon::default(),
+        Selectable {
+            selection_mask: SelectionMask::UnitMilitant
+        },
+        PbrBundle {
+            mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+            material: materials.add(settings.accessibility.team_palette.color(player_id)),
+            transform: Transform::from_translation(translation + Vec3::Y),
+            ..default()
+        },
+    )).id()
+}
@@ -0,0 +1,94 @@
+use avian3d::prelude::LinearVelocity;
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::simulation::{fixed::{FixedPoint, FixedVec2}, SimId, SimulationSet};
+
+use super::behaviour::{Engagement, Stance};
+
+// Ground speed of units in world units per second
+const UNIT_SPEED: FixedPoint = FixedPoint::from_int(6);
+// Close enough to a move target to count as arrived
+const ARRIVAL_RADIUS: FixedPoint = FixedPoint::from_int(1);
+// Distance attackers close to before holding position
+pub const ATTACK_RANGE: FixedPoint = FixedPoint::from_int(5);
+
+// Walking to a point on the ground
+#[derive(Clone, Copy, Component, Debug)]
+pub struct MoveOrder {
+    pub target: FixedVec2,
+}
+
+// Closing in on an enemy until it is in range
+#[derive(Clone, Copy, Component, Debug)]
+pub struct AttackOrder {
+    pub target: SimId,
+}
+
+pub struct UnitOrdersPlugin;
+
+impl Plugin for UnitOrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            move_units,
+            pursue_targets,
+        ).in_set(SimulationSet::Logic));
+    }
+}
+
+// Positions go through fixed point so units steer the same way on every peer
+pub fn ground_position(transform: &Transform) -> FixedVec2 {
+    FixedVec2::from_vec2(transform.translation.xz())
+}
+
+// Head towards the target at unit speed, or stop and return true once within range
+fn steer(velocity: &mut LinearVelocity, from: FixedVec2, to: FixedVec2, range: FixedPoint) -> bool {
+    if from.distance(to) <= range {
+        velocity.x = 0.;
+        velocity.z = 0.;
+        return true;
+    }
+    let heading = ((to - from).normalize_or_zero() * UNIT_SPEED).to_vec2();
+    velocity.x = heading.x;
+    velocity.z = heading.y;
+    false
+}
+
+pub fn move_units(
+    mut commands: Commands,
+    mut q_moving: Query<(Entity, &Transform, &MoveOrder, &mut LinearVelocity)>,
+) {
+    for (entity, transform, move_order, mut velocity) in q_moving.iter_mut() {
+        if steer(&mut velocity, ground_position(transform), move_order.target, ARRIVAL_RADIUS) {
+            commands.entity(entity).remove::<MoveOrder>();
+        }
+    }
+}
+
+pub fn pursue_targets(
+    mut commands: Commands,
+    mut q_attackers: Query<(Entity, &Transform, &AttackOrder, &mut LinearVelocity, Option<&Stance>, Has<Engagement>)>,
+    q_targets: Query<(&SimId, &Transform)>,
+) {
+    if q_attackers.is_empty() {
+        return;
+    }
+    let targets: HashMap<SimId, FixedVec2> = q_targets.iter()
+        .map(|(sim_id, transform)| (*sim_id, ground_position(transform)))
+        .collect();
+    for (entity, transform, attack_order, mut velocity, stance, engaged) in q_attackers.iter_mut() {
+        let Some(target) = targets.get(&attack_order.target) else {
+            // Target is gone, stand down
+            velocity.x = 0.;
+            velocity.z = 0.;
+            commands.entity(entity).remove::<AttackOrder>();
+            continue;
+        };
+        // Units holding position only fight what they picked themselves from where they stand
+        if engaged && stance == Some(&Stance::HoldPosition) {
+            velocity.x = 0.;
+            velocity.z = 0.;
+            continue;
+        }
+        steer(&mut velocity, ground_position(transform), *target, ATTACK_RANGE);
+    }
+}
@@ -0,0 +1,7 @@
+use bevy::prelude::*;
+
+// A harvestable deposit in the world
+#[derive(Component, Default)]
+pub struct ResourceNode {
+    pub amount: u32,
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use bevy::{app::ScheduleRunnerPlugin, asset::AssetPlugin, input::InputPlugin, prelude::*, scene::ScenePlugin, state::app::StatesPlugin, time::TimeUpdateStrategy};
+
+use crate::{resources::stats::MatchStats, simulation::{physics_plugins, SIMULATION_TIMESTEP}, states::{match_rules::MatchOutcome, AppState}};
+
+pub const HEADLESS_ARG: &str = "--headless";
+const TIME_LIMIT_ARG: &str = "--time-limit";
+// Seconds of game time before an undecided match is called a draw
+const TIME_LIMIT_DEFAULT: f32 = 3600.;
+
+// Present when the game runs without a window, renderer or UI
+#[derive(Clone, Resource)]
+pub struct Headless {
+    pub timestep: Duration,
+    pub time_limit: f32,
+    // Follow the clock instead of running as fast as possible, for servers that clients play on
+    pub realtime: bool,
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Headless {
+            // One simulation tick per update
+            timestep: SIMULATION_TIMESTEP,
+            time_limit: TIME_LIMIT_DEFAULT,
+            realtime: false,
+        }
+    }
+}
+
+impl Headless {
+    // None unless --headless was passed, optionally followed by --time-limit <seconds>
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        if !args.iter().any(|arg| arg == HEADLESS_ARG) {
+            return None;
+        }
+        Some(Headless::default().with_args(args))
+    }
+
+    // Time limit overridden by --time-limit <seconds>, if passed
+    pub fn with_args(mut self, args: &[String]) -> Self {
+        if let Some(position) = args.iter().position(|arg| arg == TIME_LIMIT_ARG) {
+            match args.get(position + 1).map(|value| value.parse::<f32>()) {
+                Some(Ok(time_limit)) => self.time_limit = time_limit,
+                _ => println!("Ignoring {}, expected a number of seconds", TIME_LIMIT_ARG),
+            }
+        }
+        self
+    }
+}
+
+// Just enough of Bevy to run the simulation: no window, rendering, audio or UI
+pub struct HeadlessPlugin {
+    pub config: Headless,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        // Don't wait between updates unless following the clock, game time advances by the timestep below instead
+        let wait = if self.config.realtime { self.config.timestep } else { Duration::ZERO };
+        app
+            .add_plugins((
+                MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(wait)),
+                StatesPlugin,
+                TransformPlugin,
+                HierarchyPlugin,
+                InputPlugin,
+                AssetPlugin::default(),
+                ScenePlugin,
+                physics_plugins(),
+            ))
+            // Match setup still creates meshes and materials, they are just never drawn
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .insert_resource(self.config.clone())
+            .add_systems(Update, enforce_time_limit.run_if(in_state(AppState::InGame)))
+            .add_systems(OnEnter(AppState::GameOver), exit_headless);
+        if !self.config.realtime {
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(self.config.timestep));
+        }
+    }
+}
+
+pub fn enforce_time_limit(
+    headless: Res<Headless>,
+    stats: Res<MatchStats>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if stats.elapsed >= headless.time_limit {
+        println!("Time limit of {}s reached, ending the match as a draw", headless.time_limit);
+        next_app_state.set(AppState::GameOver);
+    }
+}
+
+// Statistics are exported when the match ends, so there is nothing left to do
+pub fn exit_headless(
+    outcome: Res<MatchOutcome>,
+    stats: Res<MatchStats>,
+    mut ev_exit: EventWriter<AppExit>,
+) {
+    match outcome.winner {
+        Some(winner) => println!("Headless match finished after {:.0}s, player {} wins", stats.elapsed, winner),
+        None => println!("Headless match finished after {:.0}s in a draw", stats.elapsed),
+    }
+    ev_exit.send(AppExit::Success);
+}
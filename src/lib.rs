@@ -0,0 +1,92 @@
+use ai::AiPlugin;
+use audio::GameAudioPlugin;
+use bevy::{app::PluginGroupBuilder, prelude::*};
+use controls::{bookmarks::BookmarkPlugin, camera::PlayerCameraPlugin, cinematic::CinematicPlugin, orders::OrdersPlugin, selection::SelectionPlugin, window::WindowControlsPlugin};
+use debug::debug::DebugPlugin;
+use entities::{combat::CombatPlugin, units::{behaviour::UnitBehaviourPlugin, orders::UnitOrdersPlugin}};
+use map::MapPlugin;
+use network::{client::ServerConnectionPlugin, NetworkPlugin};
+use resources::{save::SavePlugin, settings::{SettingsDisplayPlugin, SettingsPlugin}, stats::StatsPlugin, ResourcesPlugin};
+use simulation::{commands::CommandPlugin, replay::ReplayPlugin, SimulationPlugin};
+use states::AppStatePlugin;
+use ui::{cursor::CursorPlugin, hud::HudPlugin, lobby::LobbyPlugin, menu::MenuPlugin, pause::PausePlugin, replay_viewer::ReplayViewerPlugin, settings_menu::SettingsMenuPlugin};
+
+pub mod ai;
+pub mod audio;
+pub mod controls;
+pub mod debug;
+pub mod entities;
+pub mod headless;
+pub mod map;
+pub mod network;
+pub mod resources;
+pub mod simulation;
+pub mod states;
+pub mod ui;
+
+#[derive(Resource)]
+pub struct Game {
+    dev_mode: bool
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        let mut dev_mode = false;
+        if cfg!(debug_assertions) {
+            dev_mode = true;
+        }
+        Game {
+            dev_mode
+        }
+    }
+}
+
+// Game state, rules and world, shared by the client and headless runs.
+// Expects the platform plugins (DefaultPlugins or HeadlessPlugin) and avian physics to be added first.
+pub struct SimulationPlugins;
+
+impl PluginGroup for SimulationPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(SimulationPlugin)
+            .add(CommandPlugin)
+            .add(ReplayPlugin::default())
+            .add(NetworkPlugin)
+            .add(ServerConnectionPlugin)
+            .add(AppStatePlugin)
+            .add(ResourcesPlugin)
+            .add(SettingsPlugin::default())
+            .add(StatsPlugin::default())
+            .add(MapPlugin)
+            .add(UnitOrdersPlugin)
+            .add(UnitBehaviourPlugin)
+            .add(CombatPlugin)
+            .add(AiPlugin)
+    }
+}
+
+// Everything that needs a window, renderer or audio device
+pub struct ClientPlugins;
+
+impl PluginGroup for ClientPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(MenuPlugin)
+            .add(LobbyPlugin)
+            .add(HudPlugin)
+            .add(PausePlugin)
+            .add(SettingsMenuPlugin)
+            .add(SettingsDisplayPlugin)
+            .add(SavePlugin)
+            .add(WindowControlsPlugin)
+            .add(PlayerCameraPlugin::default())
+            .add(BookmarkPlugin)
+            .add(CinematicPlugin)
+            .add(CursorPlugin)
+            .add(SelectionPlugin)
+            .add(OrdersPlugin)
+            .add(ReplayViewerPlugin)
+            .add(GameAudioPlugin)
+            .add(DebugPlugin)
+    }
+}
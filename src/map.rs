@@ -0,0 +1,253 @@
+use avian3d::{math::*, prelude::{AngularVelocity, Collider, CollisionLayers, Friction, LayerMask, RigidBody}};
+use bevy::{pbr::CascadeShadowConfigBuilder, prelude::*, render::mesh::ConeMeshBuilder};
+use bevy_mod_picking::{prelude::{AvianPickable, Pickable}, PickableBundle};
+
+use crate::{controls::{camera::PlayerCamera, selection::{Selectable, SelectionMask}}, entities::{combat::{Health, Weapon}, structures::{Structure, HQ_HEALTH}, units::{Unit, UNIT_HEALTH}, world_objects::ResourceNode, Cost, EntityCollisionLayers, Owner}, resources::settings::Settings, simulation::SimIdAllocator, states::{loading::GameAssets, InMatch}};
+
+// Name of the only map so far, recorded in replays
+pub const MAP_NAME: &str = "default";
+
+// Spawns the match world when a match starts
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(InMatch), setup);
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut sim_ids: ResMut<SimIdAllocator>,
+) {
+    // // spawn generator
+    // commands.spawn(SceneBundle {
+    //     scene: game_assets.generator.clone(),
+    //     ..default()
+    // });
+
+    commands.spawn((
+        StateScoped(InMatch),
+        AvianPickable,
+        PlayerCamera::default(),
+        Camera3dBundle::default()
+    ));
+    
+    // Static physics object with a collision shape
+    commands.spawn((
+        StateScoped(InMatch),
+        RigidBody::Static,
+        AvianPickable,
+        Pickable {
+            should_block_lower: true,
+            is_hoverable: false,
+        },
+        Collider::cylinder(200.0, 0.1),
+        CollisionLayers::new(EntityCollisionLayers::Ground, LayerMask::ALL),
+        Friction::new(0.5),
+        PbrBundle {
+            mesh: meshes.add(Cylinder::new(200.0, 0.1)),
+            material: game_assets.ground_material.clone(),
+            transform: Transform::from_xyz(0.0, -0.05, 0.0),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        StateScoped(InMatch),
+        RigidBody::Static,
+        Collider::cuboid(10.0, 10.0, 10.0),
+        CollisionLayers::new(EntityCollisionLayers::Ground, LayerMask::ALL),
+        PbrBundle {
+            mesh: meshes.add(Cuboid::new(10.0, 10.0, 10.0)),
+            material: materials.add(Color::srgb_u8(124, 144, 255)),
+            transform: Transform::from_xyz(0.0, 5.0, -20.0),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        StateScoped(InMatch),
+        RigidBody::Static,
+        Collider::cone(10.0, 1.0),
+        CollisionLayers::new(EntityCollisionLayers::Ground, LayerMask::ALL),
+        PbrBundle {
+            mesh: meshes.add(ConeMeshBuilder::new(10.0, 1.0, 16)),
+            material: materials.add(Color::srgb_u8(124, 144, 255)),
+            transform: Transform::from_xyz(20.0, 0.5, -20.0),
+            ..default()
+        },
+    ));
+
+    // Dynamic physics object with a collision shape and initial angular velocity
+    for _i in 0..10 {
+        commands.spawn((
+            StateScoped(InMatch),
+            AvianPickable,
+            PickableBundle {
+                pickable: Pickable {
+                    should_block_lower: false,
+                    is_hoverable: true,
+                },
+                ..default()
+            },
+            RigidBody::Dynamic,
+            Collider::cuboid(1.0, 1.0, 1.0),
+            CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+            AngularVelocity(Vec3::new(2.5, 3.5, 1.5)),
+            sim_ids.next(),
+            Owner(0),
+            Unit,
+            Cost(50),
+            Health::new(UNIT_HEALTH),
+            Weapon::default(),
+            Selectable {
+                selection_mask: SelectionMask::UnitPassive
+            },
+            PbrBundle {
+                mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+                material: materials.add(settings.accessibility.team_palette.color(0)),
+                transform: Transform::from_xyz(0.0, 4.0, 0.0),
+                ..default()
+            },
+        ));
+    }
+
+    // Opposing units
+    for i in 0..3 {
+        commands.spawn((
+            StateScoped(InMatch),
+            AvianPickable,
+            PickableBundle {
+                pickable: Pickable {
+                    should_block_lower: false,
+                    is_hoverable: true,
+                },
+                ..default()
+            },
+            RigidBody::Dynamic,
+            Collider::cuboid(1.0, 1.0, 1.0),
+            CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+            sim_ids.next(),
+            Owner(1),
+            Unit,
+            Cost(50),
+            Health::new(UNIT_HEALTH),
+            Weapon::default(),
+            // Commanded by the second player in LAN games
+            Selectable {
+                selection_mask: SelectionMask::UnitPassive
+            },
+            PbrBundle {
+                mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+                material: materials.add(settings.accessibility.team_palette.color(1)),
+                transform: Transform::from_xyz(15.0 + i as f32 * 2.0, 1.0, 10.0),
+                ..default()
+            },
+        ));
+    }
+
+    // Headquarters, one per player
+    for (player_id, position) in [(0, Vec3::new(-5.0, 1.5, 5.0)), (1, Vec3::new(20.0, 1.5, 15.0))] {
+        commands.spawn((
+            StateScoped(InMatch),
+            AvianPickable,
+            PickableBundle {
+                pickable: Pickable {
+                    should_block_lower: true,
+                    is_hoverable: true,
+                },
+                ..default()
+            },
+            RigidBody::Static,
+            Collider::cuboid(4.0, 3.0, 4.0),
+            CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+            sim_ids.next(),
+            Owner(player_id),
+            Structure,
+            Cost(400),
+            Health::new(HQ_HEALTH),
+            Selectable {
+                selection_mask: SelectionMask::Hq
+            },
+            PbrBundle {
+                mesh: meshes.add(Cuboid::new(4.0, 3.0, 4.0)),
+                material: materials.add(settings.accessibility.team_palette.color(player_id)),
+                transform: Transform::from_translation(position),
+                ..default()
+            },
+        ));
+    }
+
+    // Resource deposits, one near each HQ
+    for position in [Vec3::new(-15.0, 0.75, 10.0), Vec3::new(30.0, 0.75, 10.0)] {
+        commands.spawn((
+            StateScoped(InMatch),
+            AvianPickable,
+            PickableBundle {
+                pickable: Pickable {
+                    should_block_lower: true,
+                    is_hoverable: true,
+                },
+                ..default()
+            },
+            RigidBody::Static,
+            Collider::cylinder(2.0, 1.5),
+            CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+            sim_ids.next(),
+            ResourceNode {
+                amount: 1000,
+            },
+            PbrBundle {
+                mesh: meshes.add(Cylinder::new(2.0, 1.5)),
+                material: materials.add(Color::srgb_u8(96, 220, 140)),
+                transform: Transform::from_translation(position),
+                ..default()
+            },
+        ));
+    }
+
+    // Light
+    commands.spawn((
+        StateScoped(InMatch),
+        PointLightBundle {
+            point_light: PointLight {
+                shadows_enabled: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(4.0, 8.0, 4.0),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        StateScoped(InMatch),
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                color: Color::linear_rgb(255. / 255., 209. / 255., 178. / 255.),
+                illuminance: light_consts::lux::CLEAR_SUNRISE,
+                shadows_enabled: true,
+                ..default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0.0, 2.0, 0.0),
+                rotation: Quat::from_rotation_x(-PI / 4.),
+                ..default()
+            },
+            // The default cascade config is designed to handle large scenes.
+            // As this example has a much smaller world, we can tighten the shadow
+            // bounds for better visual quality.
+            cascade_shadow_config: CascadeShadowConfigBuilder {
+                first_cascade_far_bound: 4.0,
+                maximum_distance: 10.0,
+                ..default()
+            }
+            .into(),
+            ..default()
+        },
+    ));
+}
\ No newline at end of file
@@ -0,0 +1,411 @@
+use std::{collections::VecDeque, io, net::{SocketAddr, UdpSocket}};
+
+use avian3d::prelude::{AngularVelocity, LinearVelocity, RigidBody};
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    entities::{structures::spawn_structure, units::spawn_unit},
+    headless::Headless,
+    resources::{player::{Player, PlayerId, Stockpiles}, settings::{Settings, NETWORK_PORT_DEFAULT}},
+    simulation::{commands::{IssueCommandEvent, PlayerCommand}, SimId, SIMULATION_HZ},
+    states::{match_rules::{ActiveMatchRules, MatchOutcome}, AppState, InMatch},
+};
+
+use super::{
+    in_match,
+    protocol::{bind_socket, receive_messages, send_message, ClientMessage, ServerMessage, PROTOCOL_VERSION},
+    snapshot::{apply, merge, EntityKind, Snapshot, SnapshotHistory, WorldState},
+    DISCONNECT_TIMEOUT, JOIN_INTERVAL,
+};
+
+pub const CONNECT_ARG: &str = "--connect";
+// Ticks the shown world trails the latest snapshot, so there is usually a later one to move towards
+pub const INTERPOLATION_DELAY_TICKS: f64 = 4.;
+// Ticks the interpolation clock may drift from where it should be before jumping there
+const MAX_CLOCK_DRIFT: f64 = 15.;
+// Share of the drift corrected every frame, small enough not to be noticed
+const CLOCK_CORRECTION: f64 = 0.1;
+// Snapshots kept per entity to interpolate between
+const SAMPLES_PER_ENTITY: usize = 8;
+
+// Connection to a dedicated server. Nothing is simulated locally while it exists, the world is
+// only mirrored from snapshots.
+#[derive(Resource)]
+pub struct ServerConnection {
+    socket: UdpSocket,
+    pub server: SocketAddr,
+    pub accepted: bool,
+    pub rejected: Option<String>,
+    // Timed out or sent a disconnect, no longer talked to
+    pub lost: bool,
+    // Players connected and needed while waiting in the server's lobby
+    pub lobby: Option<(u8, u8)>,
+    pub last_heard: f64,
+    last_connect: f64,
+    // Commands the server hasn't acknowledged yet, with their sequence numbers
+    unacked: Vec<(u64, PlayerCommand)>,
+    next_command: u64,
+    // Tick of the last snapshot applied
+    pub latest: Option<u64>,
+    history: SnapshotHistory,
+    // Parts of a split snapshot received so far
+    parts: Vec<Snapshot>,
+    // Reconstructed states waiting to be applied to the world
+    pending: Vec<(u64, WorldState)>,
+}
+
+impl ServerConnection {
+    pub fn connect(server: SocketAddr) -> io::Result<Self> {
+        Ok(ServerConnection {
+            socket: bind_socket(0)?,
+            server,
+            accepted: false,
+            rejected: None,
+            lost: false,
+            lobby: None,
+            last_heard: 0.,
+            last_connect: f64::NEG_INFINITY,
+            unacked: Vec::new(),
+            next_command: 1,
+            latest: None,
+            history: SnapshotHistory::default(),
+            parts: Vec::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    // None unless --connect <address> was passed and the socket could be opened
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let position = args.iter().position(|arg| arg == CONNECT_ARG)?;
+        let Some(address) = args.get(position + 1).and_then(|address| address.parse().ok()) else {
+            println!("Ignoring {}, expected the address of a server like 127.0.0.1:{}", CONNECT_ARG, NETWORK_PORT_DEFAULT);
+            return None;
+        };
+        match ServerConnection::connect(address) {
+            Ok(connection) => Some(connection),
+            Err(err) => {
+                println!("Failed to connect to {}: {}", address, err);
+                None
+            },
+        }
+    }
+
+    pub fn send(&self, message: &ClientMessage) {
+        send_message(&self.socket, self.server, message);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.accepted && !self.lost
+    }
+
+    // The whole snapshot once every part of it arrived. Parts of a newer snapshot replace the ones
+    // held, as the older one can't be applied after it anyway.
+    fn assemble(&mut self, part: Snapshot) -> Option<Snapshot> {
+        if part.parts <= 1 {
+            return Some(part);
+        }
+        if let Some(held) = self.parts.first() {
+            if part.tick < held.tick {
+                return None;
+            }
+            if part.tick > held.tick {
+                self.parts.clear();
+            }
+        }
+        if !self.parts.iter().any(|held| held.part == part.part) {
+            self.parts.push(part);
+        }
+        if self.parts.len() < self.parts[0].parts as usize {
+            return None;
+        }
+        self.parts.sort_unstable_by_key(|held| held.part);
+        merge(std::mem::take(&mut self.parts))
+    }
+}
+
+// Recent snapshot transforms of an entity mirrored from the server
+#[derive(Component, Default)]
+pub struct SnapshotSamples {
+    samples: VecDeque<(u64, Vec3, Quat)>,
+}
+
+impl SnapshotSamples {
+    pub fn extend(&mut self, samples: impl IntoIterator<Item = (u64, Vec3, Quat)>) {
+        for sample in samples {
+            if self.samples.len() == SAMPLES_PER_ENTITY {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(sample);
+        }
+    }
+}
+
+// Tick of the server's simulation currently shown, fractional between snapshots
+#[derive(Default, Resource)]
+pub struct InterpolationClock {
+    pub tick: Option<f64>,
+}
+
+// Mirrors a match simulated by a dedicated server. Only does anything while a ServerConnection exists.
+pub struct ServerConnectionPlugin;
+
+impl Plugin for ServerConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InterpolationClock>()
+            .add_systems(PreUpdate, receive_server_messages.run_if(resource_exists::<ServerConnection>))
+            .add_systems(Update, (
+                collect_server_commands
+                    .run_if(in_state(AppState::InGame)),
+                send_client_messages,
+                (
+                    make_bodies_kinematic,
+                    apply_snapshots,
+                    interpolate_transforms,
+                ).chain().run_if(in_state(InMatch)),
+            ).chain().run_if(resource_exists::<ServerConnection>))
+            .add_systems(OnEnter(InMatch), reset_interpolation.run_if(resource_exists::<ServerConnection>))
+            .add_systems(OnEnter(AppState::MainMenu), close_server_connection.run_if(resource_exists::<ServerConnection>));
+    }
+}
+
+pub fn receive_server_messages(
+    time: Res<Time<Real>>,
+    app_state: Res<State<AppState>>,
+    headless: Option<Res<Headless>>,
+    mut connection: ResMut<ServerConnection>,
+    mut player: ResMut<Player>,
+    mut rules: ResMut<ActiveMatchRules>,
+    mut stockpiles: ResMut<Stockpiles>,
+    mut outcome: ResMut<MatchOutcome>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if connection.lost {
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    let server = connection.server;
+    for (address, message) in receive_messages::<ServerMessage>(&connection.socket) {
+        if address != server {
+            continue;
+        }
+        connection.last_heard = now;
+        match message {
+            ServerMessage::Accepted => {
+                if !connection.accepted {
+                    println!("Connected to server at {}", server);
+                }
+                connection.accepted = true;
+            },
+            ServerMessage::Rejected { reason } => {
+                println!("Server turned us away: {}", reason);
+                connection.rejected = Some(reason);
+                connection.lost = true;
+            },
+            ServerMessage::Lobby { players, required } => connection.lobby = Some((players, required)),
+            ServerMessage::Start { player: player_id, settings: game_settings } => {
+                if *app_state.get() != AppState::Lobby {
+                    continue;
+                }
+                println!("Match starting, playing as player {}", player_id);
+                connection.accepted = true;
+                player.id = player_id;
+                // Played under the server's rules, the player's own settings stay as they are
+                rules.incoming = Some(game_settings);
+                // Headless runs have no assets to load
+                next_app_state.set(if headless.is_some() { AppState::InGame } else { AppState::Loading });
+            },
+            ServerMessage::Snapshot(snapshot) => {
+                // Snapshots before the world exists can't be applied, the server resends everything
+                // until one is acknowledged
+                if !in_match(app_state.get()) || connection.latest.is_some_and(|latest| snapshot.tick <= latest) {
+                    continue;
+                }
+                let Some(snapshot) = connection.assemble(snapshot) else { continue; };
+                let baseline = match snapshot.baseline {
+                    Some(baseline) => match connection.history.get(baseline) {
+                        Some(state) => Some(state),
+                        None => continue,
+                    },
+                    None => None,
+                };
+                let Some(state) = apply(baseline, &snapshot.entities, &snapshot.removed) else { continue; };
+                connection.unacked.retain(|(sequence, _)| *sequence > snapshot.command_ack);
+                connection.latest = Some(snapshot.tick);
+                connection.history.push(snapshot.tick, state.clone());
+                connection.pending.push((snapshot.tick, state));
+                for (player_id, amount) in snapshot.stockpiles {
+                    stockpiles.set(player_id, amount);
+                }
+            },
+            ServerMessage::MatchOver { winner } => {
+                if !in_match(app_state.get()) {
+                    continue;
+                }
+                outcome.winner = winner;
+                next_app_state.set(AppState::GameOver);
+            },
+        }
+    }
+
+    if connection.accepted && now - connection.last_heard > DISCONNECT_TIMEOUT {
+        println!("Lost connection to server at {}", server);
+        connection.lost = true;
+    }
+}
+
+// Commands go to the server, which applies them and shows the outcome in later snapshots
+pub fn collect_server_commands(
+    player: Res<Player>,
+    mut connection: ResMut<ServerConnection>,
+    mut ev_issue: EventReader<IssueCommandEvent>,
+) {
+    for event in ev_issue.read() {
+        if event.player != player.id {
+            continue;
+        }
+        let sequence = connection.next_command;
+        connection.next_command += 1;
+        connection.unacked.push((sequence, event.command.clone()));
+    }
+}
+
+pub fn send_client_messages(
+    time: Res<Time<Real>>,
+    mut connection: ResMut<ServerConnection>,
+) {
+    if connection.lost {
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    if !connection.accepted {
+        if now - connection.last_connect >= JOIN_INTERVAL {
+            connection.last_connect = now;
+            connection.send(&ClientMessage::Connect { version: PROTOCOL_VERSION });
+        }
+        return;
+    }
+    connection.send(&ClientMessage::Input {
+        commands: connection.unacked.clone(),
+        snapshot_ack: connection.latest,
+    });
+}
+
+// Mirrored bodies are moved by snapshots, not by local physics
+pub fn make_bodies_kinematic(
+    mut q_bodies: Query<(&mut RigidBody, Option<&mut LinearVelocity>, Option<&mut AngularVelocity>), Added<RigidBody>>,
+) {
+    for (mut rigid_body, linear_velocity, angular_velocity) in q_bodies.iter_mut() {
+        if *rigid_body != RigidBody::Dynamic {
+            continue;
+        }
+        *rigid_body = RigidBody::Kinematic;
+        if let Some(mut linear_velocity) = linear_velocity {
+            *linear_velocity = LinearVelocity::ZERO;
+        }
+        if let Some(mut angular_velocity) = angular_velocity {
+            *angular_velocity = AngularVelocity::ZERO;
+        }
+    }
+}
+
+// Spawn what the server trained or built, despawn what it lost and record where everything is
+pub fn apply_snapshots(
+    mut commands: Commands,
+    mut connection: ResMut<ServerConnection>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<Settings>,
+    mut q_mirrored: Query<(Entity, &SimId, Option<&mut SnapshotSamples>)>,
+) {
+    let pending: Vec<(u64, WorldState)> = connection.pending.drain(..).collect();
+    let Some((_, latest)) = pending.last() else { return; };
+    // Where an entity was in each snapshot received since the last frame
+    let samples_of = |sim_id: &SimId| pending.iter()
+        .filter_map(|(tick, state)| state.get(sim_id).map(|entity_state| (*tick, entity_state.translation(), entity_state.rotation())))
+        .collect::<Vec<_>>();
+
+    let mut known = HashSet::new();
+    for (entity, sim_id, samples) in q_mirrored.iter_mut() {
+        if !latest.contains_key(sim_id) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        known.insert(*sim_id);
+        match samples {
+            Some(mut samples) => samples.extend(samples_of(sim_id)),
+            None => {
+                let mut samples = SnapshotSamples::default();
+                samples.extend(samples_of(sim_id));
+                commands.entity(entity).insert(samples);
+            },
+        }
+    }
+    for (sim_id, entity_state) in latest.iter() {
+        if known.contains(sim_id) {
+            continue;
+        }
+        let owner: PlayerId = entity_state.owner.unwrap_or_default();
+        let translation = entity_state.translation();
+        let entity = match entity_state.kind {
+            EntityKind::Unit => spawn_unit(&mut commands, &mut meshes, &mut materials, &settings, *sim_id, owner, translation),
+            EntityKind::Structure => spawn_structure(&mut commands, &mut meshes, &mut materials, &settings, *sim_id, owner, translation),
+            // The map places everything else the same way on every machine
+            EntityKind::Other => continue,
+        };
+        let mut samples = SnapshotSamples::default();
+        samples.extend(samples_of(sim_id));
+        commands.entity(entity).insert((
+            Transform::from_translation(translation).with_rotation(entity_state.rotation()),
+            samples,
+        ));
+    }
+}
+
+pub fn reset_interpolation(mut clock: ResMut<InterpolationClock>) {
+    clock.tick = None;
+}
+
+// Show the world a little behind the latest snapshot, blending between the two around the clock
+pub fn interpolate_transforms(
+    time: Res<Time>,
+    connection: Res<ServerConnection>,
+    mut clock: ResMut<InterpolationClock>,
+    mut q_mirrored: Query<(&SnapshotSamples, &mut Transform)>,
+) {
+    let Some(latest) = connection.latest else { return; };
+    let target = latest as f64 - INTERPOLATION_DELAY_TICKS;
+    let mut tick = clock.tick.unwrap_or(target) + time.delta_seconds_f64() * SIMULATION_HZ as f64;
+    if (target - tick).abs() > MAX_CLOCK_DRIFT {
+        tick = target;
+    } else {
+        tick += (target - tick) * CLOCK_CORRECTION;
+    }
+    clock.tick = Some(tick);
+
+    for (samples, mut transform) in q_mirrored.iter_mut() {
+        let samples = &samples.samples;
+        let Some(first) = samples.front() else { continue; };
+        let from = samples.iter().rev().find(|(sample_tick, _, _)| *sample_tick as f64 <= tick).unwrap_or(first);
+        let to = samples.iter().find(|(sample_tick, _, _)| *sample_tick as f64 > tick).unwrap_or(from);
+        let blend = if to.0 > from.0 {
+            ((tick - from.0 as f64) / (to.0 - from.0) as f64).clamp(0., 1.) as f32
+        } else {
+            0.
+        };
+        transform.translation = from.1.lerp(to.1, blend);
+        transform.rotation = from.2.slerp(to.2, blend);
+    }
+}
+
+pub fn close_server_connection(
+    mut commands: Commands,
+    connection: Res<ServerConnection>,
+    mut player: ResMut<Player>,
+) {
+    if connection.is_connected() {
+        connection.send(&ClientMessage::Disconnect);
+    }
+    commands.remove_resource::<ServerConnection>();
+    *player = Player::default();
+}
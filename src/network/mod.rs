@@ -0,0 +1,306 @@
+use avian3d::prelude::{Physics, PhysicsTime};
+use bevy::prelude::*;
+
+use crate::{
+    headless::Headless,
+    resources::{player::Player, settings::Settings},
+    simulation::{commands::{CommandQueue, IssueCommandEvent}, record_checksum, ChecksumHistory, SimulationSet, SimulationStall, SimulationTick},
+    states::{match_rules::{ActiveMatchRules, MatchOutcome}, AppState, InMatch},
+};
+use protocol::{NetMessage, PROTOCOL_VERSION};
+use session::{NetworkRole, NetworkSession, Peer, CLIENT_PLAYER};
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+pub mod session;
+pub mod snapshot;
+
+// Ticks per lockstep turn. Commands are exchanged once per turn and applied on its first tick.
+pub const TURN_TICKS: u64 = 3;
+// Seconds without hearing from the other end before it is considered gone
+const DISCONNECT_TIMEOUT: f64 = 10.;
+const JOIN_INTERVAL: f64 = 1.;
+const PING_INTERVAL: f64 = 1.;
+
+// Lockstep over UDP for two players. Only does anything while a NetworkSession exists.
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(PreUpdate, receive_network_messages.run_if(resource_exists::<NetworkSession>))
+            .add_systems(Update, (
+                start_headless_match
+                    .run_if(in_state(AppState::Lobby).and_then(resource_exists::<Headless>)),
+                collect_local_commands
+                    .run_if(in_state(AppState::InGame)),
+                send_network_messages,
+            ).chain().run_if(resource_exists::<NetworkSession>))
+            .add_systems(OnEnter(InMatch), start_lockstep.run_if(resource_exists::<NetworkSession>))
+            .add_systems(OnEnter(AppState::MainMenu), close_network_session.run_if(resource_exists::<NetworkSession>))
+            .add_systems(FixedUpdate, (
+                lockstep_gate
+                    .before(SimulationSet::Tick)
+                    .run_if(in_state(AppState::InGame)),
+                compare_checksums
+                    .after(record_checksum)
+                    .in_set(SimulationSet::Checksum),
+            ).run_if(resource_exists::<NetworkSession>));
+    }
+}
+
+fn in_match(app_state: &AppState) -> bool {
+    matches!(app_state, AppState::InGame | AppState::Paused)
+}
+
+// Forget a peer that left before the match, or stop waiting on one that left during it
+fn drop_peer(session: &mut NetworkSession, in_match: bool) {
+    if session.role == NetworkRole::Host && !in_match {
+        session.peer = None;
+    } else if let Some(peer) = session.peer.as_mut() {
+        peer.dropped = true;
+    }
+}
+
+pub fn receive_network_messages(
+    time: Res<Time<Real>>,
+    app_state: Res<State<AppState>>,
+    headless: Option<Res<Headless>>,
+    mut session: ResMut<NetworkSession>,
+    mut player: ResMut<Player>,
+    mut rules: ResMut<ActiveMatchRules>,
+    mut queue: ResMut<CommandQueue>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let in_match = in_match(app_state.get());
+    for (address, message) in session.receive() {
+        if let NetMessage::Join { version } = message {
+            if session.role != NetworkRole::Host {
+                continue;
+            }
+            let known = session.peer.as_ref().map(|peer| peer.address == address);
+            let rejection = if version != PROTOCOL_VERSION {
+                Some(format!("host runs protocol version {}, not {}", PROTOCOL_VERSION, version))
+            } else if known == Some(false) {
+                Some("game is full".to_string())
+            } else if known.is_none() && in_match {
+                Some("match already started".to_string())
+            } else {
+                None
+            };
+            if let Some(reason) = rejection {
+                session.send(address, &NetMessage::Reject { reason });
+                continue;
+            }
+            if known.is_none() {
+                println!("Player joined from {}", address);
+            }
+            // Joins are resent until welcomed, so an already known client is just welcomed again
+            let peer = session.peer.get_or_insert_with(|| Peer::new(address, CLIENT_PLAYER, now));
+            peer.connected = true;
+            peer.last_heard = now;
+            session.send(address, &NetMessage::Welcome { player: CLIENT_PLAYER });
+            continue;
+        }
+
+        // Everything else only counts coming from the peer
+        let Some(peer) = session.peer.as_mut().filter(|peer| peer.address == address && !peer.dropped) else {
+            continue;
+        };
+        peer.last_heard = now;
+        match message {
+            NetMessage::Join { .. } => {},
+            NetMessage::Welcome { player: player_id } => {
+                if !peer.connected {
+                    println!("Joined game at {} as player {}", address, player_id);
+                }
+                peer.connected = true;
+                player.id = player_id;
+                session.local_player = player_id;
+            },
+            NetMessage::Reject { reason } => {
+                println!("Host turned us away: {}", reason);
+                peer.dropped = true;
+                session.rejected = Some(reason);
+            },
+            NetMessage::Start { settings: game_settings, input_delay } => {
+                if session.role != NetworkRole::Client || *app_state.get() != AppState::Lobby {
+                    continue;
+                }
+                // Played under the host's rules, the client's own settings stay as they are
+                rules.incoming = Some(game_settings);
+                session.set_input_delay(input_delay);
+                // Headless runs have no assets to load
+                next_app_state.set(if headless.is_some() { AppState::InGame } else { AppState::Loading });
+            },
+            NetMessage::Turns { batches, ack, checksum } => {
+                // The client waits for Start, the host's turns are resent once it is in the match
+                if !in_match {
+                    continue;
+                }
+                peer.in_match = true;
+                session.acknowledge(ack);
+                for batch in batches {
+                    session.receive_batch(batch, &mut queue);
+                }
+                if let Some((tick, checksum)) = checksum {
+                    session.push_remote_checksum(tick, checksum);
+                }
+            },
+            NetMessage::Ping { sent } => session.send(address, &NetMessage::Pong { sent }),
+            NetMessage::Pong { sent } => peer.round_trip = Some(now - sent),
+            NetMessage::Leave => {
+                println!("Player {} left", peer.player);
+                drop_peer(&mut session, in_match);
+            },
+        }
+    }
+
+    let timed_out = session.peer.as_ref()
+        .is_some_and(|peer| peer.connected && !peer.dropped && now - peer.last_heard > DISCONNECT_TIMEOUT);
+    if timed_out {
+        println!("Lost connection to player {}", session.peer.as_ref().map_or(0, |peer| peer.player));
+        drop_peer(&mut session, in_match);
+    }
+}
+
+// Nobody is there to press start in a headless lobby, so the host starts once someone joined
+pub fn start_headless_match(
+    session: Res<NetworkSession>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if session.role == NetworkRole::Host && session.is_connected() {
+        next_app_state.set(AppState::InGame);
+    }
+}
+
+// Local commands are held back for the next turn instead of being queued straight away
+pub fn collect_local_commands(
+    mut session: ResMut<NetworkSession>,
+    mut ev_issue: EventReader<IssueCommandEvent>,
+) {
+    for event in ev_issue.read() {
+        // Commands for other players arrive from their own peer
+        if event.player == session.local_player {
+            session.push_local(event.command.clone());
+        }
+    }
+}
+
+pub fn send_network_messages(
+    time: Res<Time<Real>>,
+    app_state: Res<State<AppState>>,
+    rules: Res<ActiveMatchRules>,
+    history: Res<ChecksumHistory>,
+    mut session: ResMut<NetworkSession>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let Some(peer) = session.peer.clone() else { return; };
+    if peer.dropped {
+        return;
+    }
+    if !peer.connected {
+        if session.role == NetworkRole::Client && now - session.last_join >= JOIN_INTERVAL {
+            session.last_join = now;
+            session.send_to_peer(&NetMessage::Join { version: PROTOCOL_VERSION });
+        }
+        return;
+    }
+    if now - session.last_ping >= PING_INTERVAL {
+        session.last_ping = now;
+        session.send_to_peer(&NetMessage::Ping { sent: now });
+    }
+    // Turns keep going after the match ends, in case the peer still needs them to get there
+    let app_state = app_state.get();
+    if !in_match(app_state) && *app_state != AppState::GameOver {
+        return;
+    }
+    if session.role == NetworkRole::Host && in_match(app_state) && !peer.in_match {
+        session.send_to_peer(&NetMessage::Start {
+            settings: rules.game.clone(),
+            input_delay: session.input_delay,
+        });
+    }
+    session.send_to_peer(&session.turns_message(history.latest()));
+}
+
+pub fn start_lockstep(
+    settings: Res<Settings>,
+    mut session: ResMut<NetworkSession>,
+) {
+    // Clients were told the input delay when the host started the match
+    if session.role == NetworkRole::Host {
+        session.set_input_delay(settings.network.input_delay);
+    }
+    session.reset_lockstep();
+}
+
+pub fn close_network_session(
+    mut commands: Commands,
+    session: Res<NetworkSession>,
+    mut player: ResMut<Player>,
+) {
+    session.leave();
+    commands.remove_resource::<NetworkSession>();
+    *player = Player::default();
+}
+
+// Holds the simulation at the start of each turn until the peer's commands for it are in
+pub fn lockstep_gate(
+    time: Res<Time<Real>>,
+    tick: Res<SimulationTick>,
+    mut session: ResMut<NetworkSession>,
+    mut queue: ResMut<CommandQueue>,
+    mut stall: ResMut<SimulationStall>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    stall.stalled = false;
+    if tick.0 % TURN_TICKS == 0 {
+        let turn = tick.0 / TURN_TICKS;
+        if session.turn_ready(turn) {
+            let sealed = turn + session.input_delay;
+            session.seal_turn(sealed, &mut queue);
+        } else {
+            stall.stalled = true;
+        }
+    }
+    // Physics steps after FixedUpdate and would otherwise move units during a skipped tick
+    if physics_time.is_paused() != stall.stalled {
+        if stall.stalled {
+            physics_time.pause();
+        } else {
+            physics_time.unpause();
+        }
+    }
+    session.stalled_since = match (stall.stalled, session.stalled_since) {
+        (true, None) => Some(time.elapsed_seconds_f64()),
+        (true, since) => since,
+        (false, _) => None,
+    };
+}
+
+// Peers simulating the same commands have to end up with the same checksums, a match that
+// diverged can't be continued
+pub fn compare_checksums(
+    tick: Res<SimulationTick>,
+    history: Res<ChecksumHistory>,
+    mut session: ResMut<NetworkSession>,
+    mut outcome: ResMut<MatchOutcome>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if session.desync.is_some() {
+        return;
+    }
+    for (remote_tick, remote) in session.take_remote_checksums(tick.0) {
+        let Some(local) = history.get(remote_tick) else { continue; };
+        if local != remote {
+            println!("Desync at tick {}: checksum {:016x}, peer has {:016x}", remote_tick, local, remote);
+            session.desync = Some(remote_tick);
+            outcome.desync = Some(remote_tick);
+            next_app_state.set(AppState::GameOver);
+            return;
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{io, net::{SocketAddr, UdpSocket}};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{resources::{player::PlayerId, settings::GameSettings}, simulation::commands::{PlayerCommand, TimedCommand}};
+
+use super::snapshot::Snapshot;
+
+// Bumped whenever messages change, peers on different versions can't play together
+pub const PROTOCOL_VERSION: u32 = 2;
+// Largest datagram sent or read. Well under the 65507 bytes UDP can carry, as anything past the
+// MTU only gets through in IP fragments that are each lost on their own. Snapshots are split to fit.
+pub const MAX_DATAGRAM_SIZE: usize = 16 * 1024;
+
+// Commands one player issued for one turn, empty when they did nothing
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TurnBatch {
+    pub turn: u64,
+    pub commands: Vec<TimedCommand>,
+}
+
+// Between the two peers of a lockstep game
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum NetMessage {
+    // Client asking the host to join
+    Join { version: u32 },
+    // Host accepting a client, with the player it controls
+    Welcome { player: PlayerId },
+    // Host turning a client away
+    Reject { reason: String },
+    // Host starting the match under the given rules, resent until the client is in it
+    Start { settings: GameSettings, input_delay: u64 },
+    // Turn batches the peer hasn't acknowledged yet, resent until it does. Also acknowledges
+    // every turn before ack and carries the sender's latest checksum.
+    Turns { batches: Vec<TurnBatch>, ack: u64, checksum: Option<(u64, u64)> },
+    Ping { sent: f64 },
+    Pong { sent: f64 },
+    Leave,
+}
+
+// From a client to a dedicated server
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ClientMessage {
+    Connect { version: u32 },
+    // Commands the server hasn't acknowledged yet, numbered from 1 in the order they were issued,
+    // and the last snapshot applied. Sent every frame, so it doubles as a keepalive.
+    Input { commands: Vec<(u64, PlayerCommand)>, snapshot_ack: Option<u64> },
+    Disconnect,
+}
+
+// From a dedicated server to its clients
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ServerMessage {
+    Accepted,
+    Rejected { reason: String },
+    // Players connected while the server waits for enough of them to start
+    Lobby { players: u8, required: u8 },
+    // Match started with the client controlling the given player, resent until it applies a snapshot
+    Start { player: PlayerId, settings: GameSettings },
+    Snapshot(Snapshot),
+    MatchOver { winner: Option<PlayerId> },
+}
+
+// Non-blocking, as everything reading the socket runs once a frame
+pub fn bind_socket(port: u16) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+// Bytes the message takes up in a datagram
+pub fn encoded_size<T: Serialize>(message: &T) -> usize {
+    serde_json::to_vec(message).map_or(usize::MAX, |bytes| bytes.len())
+}
+
+pub fn send_message<T: Serialize>(socket: &UdpSocket, address: SocketAddr, message: &T) {
+    let Ok(bytes) = serde_json::to_vec(message) else { return; };
+    if bytes.len() > MAX_DATAGRAM_SIZE {
+        println!("Not sending {} bytes to {}, datagrams are limited to {}", bytes.len(), address, MAX_DATAGRAM_SIZE);
+        return;
+    }
+    if let Err(err) = socket.send_to(&bytes, address) {
+        if err.kind() != io::ErrorKind::WouldBlock {
+            println!("Failed to send to {}: {}", address, err);
+        }
+    }
+}
+
+// Every datagram waiting on the socket, skipping ones that aren't messages
+pub fn receive_messages<T: DeserializeOwned>(socket: &UdpSocket) -> Vec<(SocketAddr, T)> {
+    let mut messages = Vec::new();
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((length, address)) => {
+                if let Ok(message) = serde_json::from_slice(&buffer[..length]) {
+                    messages.push((address, message));
+                }
+            },
+            // Windows reports an earlier send to a closed port on the next receive
+            Err(err) if err.kind() == io::ErrorKind::ConnectionReset => continue,
+            Err(err) => {
+                if err.kind() != io::ErrorKind::WouldBlock {
+                    println!("Failed to receive: {}", err);
+                }
+                break;
+            },
+        }
+    }
+    messages
+}
@@ -0,0 +1,324 @@
+use std::{io, net::{SocketAddr, UdpSocket}};
+
+use bevy::prelude::*;
+
+use crate::{
+    entities::{structures::Structure, units::Unit, Owner},
+    resources::{player::{PlayerId, Stockpiles}, settings::NETWORK_PORT_DEFAULT},
+    simulation::{commands::{IssueCommandEvent, PlayerCommand}, record_checksum, SimId, SimulationSet, SimulationTick},
+    states::{match_rules::{ActiveMatchRules, MatchOutcome}, AppState, InMatch},
+};
+
+use super::{
+    in_match,
+    protocol::{bind_socket, encoded_size, receive_messages, send_message, ClientMessage, ServerMessage, MAX_DATAGRAM_SIZE, PROTOCOL_VERSION},
+    snapshot::{diff, split, EntityKind, EntityState, Snapshot, SnapshotHistory, WorldState},
+    DISCONNECT_TIMEOUT,
+};
+
+pub const PORT_ARG: &str = "--port";
+pub const PLAYERS_ARG: &str = "--players";
+// Ticks between snapshots, clients interpolate in between
+pub const SNAPSHOT_INTERVAL_TICKS: u64 = 2;
+const LOBBY_INTERVAL: f64 = 1.;
+// Match results are only sent once, so a few copies make up for datagrams getting lost
+const MATCH_OVER_REPEATS: usize = 3;
+
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub port: u16,
+    // Clients needed before the match starts
+    pub players: u8,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            port: NETWORK_PORT_DEFAULT,
+            players: 2,
+        }
+    }
+}
+
+impl ServerConfig {
+    // Defaults unless overridden by --port <port> and --players <count>
+    pub fn from_args(args: &[String]) -> Self {
+        let mut config = ServerConfig::default();
+        let value = |name: &str| args.iter()
+            .position(|arg| arg == name)
+            .and_then(|position| args.get(position + 1));
+        if let Some(port) = value(PORT_ARG) {
+            match port.parse() {
+                Ok(port) => config.port = port,
+                Err(_) => println!("Ignoring {}, expected a port number", PORT_ARG),
+            }
+        }
+        if let Some(players) = value(PLAYERS_ARG) {
+            match players.parse() {
+                Ok(players) if players > 0 => config.players = players,
+                _ => println!("Ignoring {}, expected a number of players", PLAYERS_ARG),
+            }
+        }
+        config
+    }
+}
+
+pub struct ServerClient {
+    pub address: SocketAddr,
+    // Handed out by join order once the match starts
+    pub player: PlayerId,
+    pub last_heard: f64,
+    // Last command received, later ones are new
+    pub last_command: u64,
+    // Last snapshot the client applied, deltas are made against it
+    pub snapshot_ack: Option<u64>,
+    pub dropped: bool,
+}
+
+// Runs the only simulation of the match. Clients send it commands and are sent back snapshots
+// of the world, which only carry what changed since the last snapshot they acknowledged.
+#[derive(Resource)]
+pub struct GameServer {
+    socket: UdpSocket,
+    pub required_players: u8,
+    pub clients: Vec<ServerClient>,
+    history: SnapshotHistory,
+    last_lobby: f64,
+}
+
+impl GameServer {
+    pub fn bind(config: &ServerConfig) -> io::Result<Self> {
+        Ok(GameServer {
+            socket: bind_socket(config.port)?,
+            required_players: config.players,
+            clients: Vec::new(),
+            history: SnapshotHistory::default(),
+            last_lobby: f64::NEG_INFINITY,
+        })
+    }
+
+    pub fn local_port(&self) -> Option<u16> {
+        self.socket.local_addr().ok().map(|address| address.port())
+    }
+
+    pub fn send(&self, address: SocketAddr, message: &ServerMessage) {
+        send_message(&self.socket, address, message);
+    }
+
+    fn connected(&self) -> impl Iterator<Item = &ServerClient> {
+        self.clients.iter().filter(|client| !client.dropped)
+    }
+}
+
+// Added by the server binary only, clients mirror the match through ServerConnectionPlugin instead
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(PreUpdate, receive_client_messages.run_if(resource_exists::<GameServer>))
+            .add_systems(Update, (
+                start_server_match.run_if(in_state(AppState::Lobby)),
+                send_server_messages,
+            ).chain().run_if(resource_exists::<GameServer>))
+            .add_systems(OnEnter(InMatch), reset_server_history.run_if(resource_exists::<GameServer>))
+            .add_systems(OnEnter(AppState::GameOver), announce_match_over.run_if(resource_exists::<GameServer>))
+            .add_systems(FixedUpdate, broadcast_snapshots
+                .after(record_checksum)
+                .in_set(SimulationSet::Checksum)
+                .run_if(resource_exists::<GameServer>));
+    }
+}
+
+pub fn receive_client_messages(
+    time: Res<Time<Real>>,
+    app_state: Res<State<AppState>>,
+    mut server: ResMut<GameServer>,
+    mut ev_issue: EventWriter<IssueCommandEvent>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let in_match = in_match(app_state.get());
+    for (address, message) in receive_messages::<ClientMessage>(&server.socket) {
+        let index = server.clients.iter().position(|client| client.address == address && !client.dropped);
+        match (message, index) {
+            (ClientMessage::Connect { version }, _) if version != PROTOCOL_VERSION => {
+                let reason = format!("server runs protocol version {}, not {}", PROTOCOL_VERSION, version);
+                server.send(address, &ServerMessage::Rejected { reason });
+            },
+            // Connects are resent until accepted
+            (ClientMessage::Connect { .. }, Some(_)) => server.send(address, &ServerMessage::Accepted),
+            (ClientMessage::Connect { .. }, None) => {
+                let reason = if in_match || *app_state.get() == AppState::GameOver {
+                    Some("match already started")
+                } else if server.connected().count() >= server.required_players as usize {
+                    Some("server is full")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    server.send(address, &ServerMessage::Rejected { reason: reason.to_string() });
+                    continue;
+                }
+                println!("Client connected from {}", address);
+                server.clients.push(ServerClient {
+                    address,
+                    player: 0,
+                    last_heard: now,
+                    last_command: 0,
+                    snapshot_ack: None,
+                    dropped: false,
+                });
+                server.send(address, &ServerMessage::Accepted);
+            },
+            (ClientMessage::Input { commands, snapshot_ack }, Some(index)) => {
+                let client = &mut server.clients[index];
+                client.last_heard = now;
+                client.snapshot_ack = client.snapshot_ack.max(snapshot_ack);
+                // Commands are resent until acknowledged, only new ones are issued
+                if !in_match {
+                    continue;
+                }
+                for (sequence, command) in commands {
+                    if sequence <= client.last_command {
+                        continue;
+                    }
+                    client.last_command = sequence;
+                    // Debug cheats aren't taken from clients
+                    if matches!(command, PlayerCommand::Eliminate { .. }) {
+                        continue;
+                    }
+                    ev_issue.send(IssueCommandEvent {
+                        player: client.player,
+                        command,
+                    });
+                }
+            },
+            (ClientMessage::Disconnect, Some(index)) => {
+                println!("Client {} disconnected", address);
+                server.clients[index].dropped = true;
+            },
+            (_, None) => {},
+        }
+    }
+
+    for client in server.clients.iter_mut() {
+        if !client.dropped && now - client.last_heard > DISCONNECT_TIMEOUT {
+            println!("Lost connection to client {}", client.address);
+            client.dropped = true;
+        }
+    }
+    // Before the match nobody owns anything yet, so clients that left are simply forgotten
+    if !in_match {
+        server.clients.retain(|client| !client.dropped);
+    } else if server.connected().count() == 0 {
+        println!("Every client left, ending the match");
+        next_app_state.set(AppState::GameOver);
+    }
+}
+
+// Players are numbered in join order once enough clients are there
+pub fn start_server_match(
+    mut server: ResMut<GameServer>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if server.clients.len() < server.required_players as usize {
+        return;
+    }
+    for (player, client) in server.clients.iter_mut().enumerate() {
+        client.player = player as PlayerId;
+    }
+    println!("Starting match with {} players", server.clients.len());
+    next_app_state.set(AppState::InGame);
+}
+
+pub fn send_server_messages(
+    time: Res<Time<Real>>,
+    app_state: Res<State<AppState>>,
+    rules: Res<ActiveMatchRules>,
+    mut server: ResMut<GameServer>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if *app_state.get() == AppState::Lobby {
+        if now - server.last_lobby < LOBBY_INTERVAL {
+            return;
+        }
+        server.last_lobby = now;
+        let message = ServerMessage::Lobby {
+            players: server.clients.len() as u8,
+            required: server.required_players,
+        };
+        for client in server.clients.iter() {
+            server.send(client.address, &message);
+        }
+    } else if in_match(app_state.get()) {
+        for client in server.connected().filter(|client| client.snapshot_ack.is_none()) {
+            server.send(client.address, &ServerMessage::Start {
+                player: client.player,
+                settings: rules.game.clone(),
+            });
+        }
+    }
+}
+
+pub fn reset_server_history(mut server: ResMut<GameServer>) {
+    server.history.clear();
+}
+
+pub fn broadcast_snapshots(
+    tick: Res<SimulationTick>,
+    stockpiles: Res<Stockpiles>,
+    mut server: ResMut<GameServer>,
+    q_simulated: Query<(&SimId, Option<&Owner>, &Transform, Has<Unit>, Has<Structure>)>,
+) {
+    if tick.0 % SNAPSHOT_INTERVAL_TICKS != 0 {
+        return;
+    }
+    let state: WorldState = q_simulated.iter()
+        .map(|(sim_id, owner, transform, is_unit, is_structure)| {
+            let kind = if is_unit {
+                EntityKind::Unit
+            } else if is_structure {
+                EntityKind::Structure
+            } else {
+                EntityKind::Other
+            };
+            (*sim_id, EntityState::new(kind, owner.map(|owner| owner.0), transform))
+        })
+        .collect();
+    let stockpiles = stockpiles.amounts();
+    for client in server.connected() {
+        // Clients that fell too far behind get everything again
+        let baseline = client.snapshot_ack.and_then(|ack| server.history.get(ack).map(|state| (ack, state)));
+        let (entities, removed) = diff(baseline.map(|(_, state)| state), &state);
+        let snapshot = Snapshot {
+            tick: tick.0,
+            baseline: baseline.map(|(ack, _)| ack),
+            command_ack: client.last_command,
+            part: 0,
+            parts: 1,
+            entities,
+            removed,
+            stockpiles: stockpiles.clone(),
+        };
+        let fits = |part: &Snapshot| encoded_size(&ServerMessage::Snapshot(part.clone())) <= MAX_DATAGRAM_SIZE;
+        for part in split(snapshot, fits) {
+            server.send(client.address, &ServerMessage::Snapshot(part));
+        }
+    }
+    server.history.push(tick.0, state);
+}
+
+pub fn announce_match_over(
+    server: Res<GameServer>,
+    outcome: Res<MatchOutcome>,
+) {
+    let message = ServerMessage::MatchOver {
+        winner: outcome.winner,
+    };
+    for client in server.connected() {
+        for _ in 0..MATCH_OVER_REPEATS {
+            server.send(client.address, &message);
+        }
+    }
+}
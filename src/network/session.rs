@@ -0,0 +1,280 @@
+use std::{collections::{BTreeMap, BTreeSet}, io, net::{SocketAddr, UdpSocket}};
+
+use bevy::prelude::*;
+
+use crate::{
+    resources::{player::PlayerId, settings::{NetworkSettings, NETWORK_PORT_DEFAULT}},
+    simulation::commands::{CommandQueue, PlayerCommand, TimedCommand},
+};
+
+use super::{protocol::{bind_socket, receive_messages, send_message, NetMessage, TurnBatch}, TURN_TICKS};
+
+pub const HOST_ARG: &str = "--host";
+pub const JOIN_ARG: &str = "--join";
+// Players are numbered in the order they joined, the host always being 0
+pub const HOST_PLAYER: PlayerId = 0;
+pub const CLIENT_PLAYER: PlayerId = 1;
+// Remote checksums kept for comparing against ticks not simulated locally yet
+const REMOTE_CHECKSUM_HISTORY: usize = 256;
+// With no delay nobody's turn is ready before it has to be simulated, and lockstep never starts
+const MIN_INPUT_DELAY: u64 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NetworkRole {
+    Host,
+    Client,
+}
+
+// The other end of a two player game
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub address: SocketAddr,
+    pub player: PlayerId,
+    // Set once the host has welcomed the client
+    pub connected: bool,
+    // Left or timed out, no longer waited for
+    pub dropped: bool,
+    // Heard sending turns, so it has started the match
+    pub in_match: bool,
+    // Real time in seconds the peer was last heard from
+    pub last_heard: f64,
+    // Round trip time in seconds, once measured
+    pub round_trip: Option<f64>,
+}
+
+impl Peer {
+    pub fn new(address: SocketAddr, player: PlayerId, now: f64) -> Self {
+        Peer {
+            address,
+            player,
+            connected: false,
+            dropped: false,
+            in_match: false,
+            last_heard: now,
+            round_trip: None,
+        }
+    }
+}
+
+// A LAN game over UDP, hosted or joined. Both sides run the same simulation and only exchange
+// commands, batched into turns that every player has to have before the turn can be simulated.
+#[derive(Resource)]
+pub struct NetworkSession {
+    socket: UdpSocket,
+    pub role: NetworkRole,
+    pub local_player: PlayerId,
+    pub peer: Option<Peer>,
+    // Turns between a command being issued and it taking effect
+    pub input_delay: u64,
+    // Why the host turned this client away
+    pub rejected: Option<String>,
+    // Tick a desync was detected on
+    pub desync: Option<u64>,
+    // Real time in seconds the simulation has been waiting on the peer since
+    pub stalled_since: Option<f64>,
+    // Commands issued locally since the last turn was sealed
+    pending: Vec<PlayerCommand>,
+    // Local batches the peer hasn't acknowledged yet
+    unacked: BTreeMap<u64, Vec<TimedCommand>>,
+    // Turns received from the peer past next_turn
+    received: BTreeSet<u64>,
+    // Every turn of the peer's before this one has been received
+    next_turn: u64,
+    remote_checksums: BTreeMap<u64, u64>,
+    pub last_join: f64,
+    pub last_ping: f64,
+}
+
+impl NetworkSession {
+    fn new(socket: UdpSocket, role: NetworkRole, local_player: PlayerId, input_delay: u64) -> Self {
+        let input_delay = input_delay.max(MIN_INPUT_DELAY);
+        NetworkSession {
+            socket,
+            role,
+            local_player,
+            peer: None,
+            input_delay,
+            rejected: None,
+            desync: None,
+            stalled_since: None,
+            pending: Vec::new(),
+            unacked: BTreeMap::new(),
+            received: BTreeSet::new(),
+            next_turn: input_delay,
+            remote_checksums: BTreeMap::new(),
+            last_join: f64::NEG_INFINITY,
+            last_ping: f64::NEG_INFINITY,
+        }
+    }
+
+    // Listen for a client on the given port, 0 for any free one. The input delay is taken from
+    // the settings when the match starts.
+    pub fn host(port: u16) -> io::Result<Self> {
+        let socket = bind_socket(port)?;
+        Ok(NetworkSession::new(socket, NetworkRole::Host, HOST_PLAYER, NetworkSettings::default().input_delay))
+    }
+
+    // Join the host at the given address. Rules and player come from the host once it answers.
+    pub fn join(address: SocketAddr) -> io::Result<Self> {
+        let socket = bind_socket(0)?;
+        let mut session = NetworkSession::new(socket, NetworkRole::Client, CLIENT_PLAYER, NetworkSettings::default().input_delay);
+        session.peer = Some(Peer::new(address, HOST_PLAYER, 0.));
+        Ok(session)
+    }
+
+    // None unless --host [port] or --join <address> was passed and the socket could be opened
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let result = if let Some(position) = args.iter().position(|arg| arg == HOST_ARG) {
+            let port = args.get(position + 1)
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(NETWORK_PORT_DEFAULT);
+            NetworkSession::host(port)
+        } else if let Some(position) = args.iter().position(|arg| arg == JOIN_ARG) {
+            let Some(address) = args.get(position + 1).and_then(|address| address.parse().ok()) else {
+                println!("Ignoring {}, expected the address of a host like 127.0.0.1:{}", JOIN_ARG, NETWORK_PORT_DEFAULT);
+                return None;
+            };
+            NetworkSession::join(address)
+        } else {
+            return None;
+        };
+        match result {
+            Ok(session) => Some(session),
+            Err(err) => {
+                println!("Failed to open network session: {}", err);
+                None
+            },
+        }
+    }
+
+    pub fn local_port(&self) -> Option<u16> {
+        self.socket.local_addr().ok().map(|address| address.port())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.peer.as_ref().is_some_and(|peer| peer.connected && !peer.dropped)
+    }
+
+    pub fn peer_dropped(&self) -> bool {
+        self.peer.as_ref().is_some_and(|peer| peer.dropped)
+    }
+
+    pub fn round_trip_ms(&self) -> Option<u32> {
+        self.peer.as_ref()
+            .and_then(|peer| peer.round_trip)
+            .map(|round_trip| (round_trip * 1000.).round() as u32)
+    }
+
+    pub fn send(&self, address: SocketAddr, message: &NetMessage) {
+        send_message(&self.socket, address, message);
+    }
+
+    pub fn send_to_peer(&self, message: &NetMessage) {
+        if let Some(peer) = self.peer.as_ref() {
+            self.send(peer.address, message);
+        }
+    }
+
+    pub fn receive(&self) -> Vec<(SocketAddr, NetMessage)> {
+        receive_messages(&self.socket)
+    }
+
+    pub fn leave(&self) {
+        self.send_to_peer(&NetMessage::Leave);
+    }
+
+    // Settings files and hosts can ask for any delay, but it has to be at least a turn
+    pub fn set_input_delay(&mut self, turns: u64) {
+        if turns < MIN_INPUT_DELAY {
+            println!("Input delay of {} turns is too short, using {}", turns, MIN_INPUT_DELAY);
+        }
+        self.input_delay = turns.max(MIN_INPUT_DELAY);
+    }
+
+    // Forget the turns of the last match
+    pub fn reset_lockstep(&mut self) {
+        self.desync = None;
+        self.stalled_since = None;
+        self.pending.clear();
+        self.unacked.clear();
+        self.received.clear();
+        self.next_turn = self.input_delay;
+        self.remote_checksums.clear();
+        if let Some(peer) = self.peer.as_mut() {
+            peer.in_match = false;
+        }
+    }
+
+    pub fn push_local(&mut self, command: PlayerCommand) {
+        self.pending.push(command);
+    }
+
+    // Commands for a turn are in once the peer sent them, or when nobody could have issued any
+    pub fn turn_ready(&self, turn: u64) -> bool {
+        match self.peer.as_ref() {
+            Some(peer) if peer.dropped => true,
+            Some(peer) if peer.connected => turn < self.next_turn,
+            _ => false,
+        }
+    }
+
+    // Close the local batch for a turn, queueing it to be simulated and sent
+    pub fn seal_turn(&mut self, turn: u64, queue: &mut CommandQueue) {
+        let tick = turn * TURN_TICKS + 1;
+        let commands: Vec<TimedCommand> = self.pending.drain(..)
+            .map(|command| TimedCommand {
+                tick,
+                player: self.local_player,
+                command,
+            })
+            .collect();
+        queue.extend(commands.iter().cloned());
+        // Nobody is left to send it to
+        if !self.peer_dropped() {
+            self.unacked.insert(turn, commands);
+        }
+    }
+
+    // Queue a batch from the peer, unless it already arrived. Commands can only be for the peer's player.
+    pub fn receive_batch(&mut self, batch: TurnBatch, queue: &mut CommandQueue) {
+        let Some(player) = self.peer.as_ref().map(|peer| peer.player) else { return; };
+        if batch.turn < self.next_turn || !self.received.insert(batch.turn) {
+            return;
+        }
+        queue.extend(batch.commands.into_iter().filter(|command| command.player == player));
+        while self.received.remove(&self.next_turn) {
+            self.next_turn += 1;
+        }
+    }
+
+    pub fn acknowledge(&mut self, ack: u64) {
+        self.unacked.retain(|turn, _| *turn >= ack);
+    }
+
+    pub fn turns_message(&self, checksum: Option<(u64, u64)>) -> NetMessage {
+        NetMessage::Turns {
+            batches: self.unacked.iter()
+                .map(|(turn, commands)| TurnBatch {
+                    turn: *turn,
+                    commands: commands.clone(),
+                })
+                .collect(),
+            ack: self.next_turn,
+            checksum,
+        }
+    }
+
+    pub fn push_remote_checksum(&mut self, tick: u64, checksum: u64) {
+        self.remote_checksums.insert(tick, checksum);
+        while self.remote_checksums.len() > REMOTE_CHECKSUM_HISTORY {
+            self.remote_checksums.pop_first();
+        }
+    }
+
+    // Remote checksums for ticks up to the given one, removed as they are handed out
+    pub fn take_remote_checksums(&mut self, tick: u64) -> Vec<(u64, u64)> {
+        let later = self.remote_checksums.split_off(&(tick + 1));
+        let due = std::mem::replace(&mut self.remote_checksums, later);
+        due.into_iter().collect()
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{resources::player::PlayerId, simulation::{fixed::FixedPoint, SimId}};
+
+// States kept to diff against or reconstruct from, enough to cover a couple of seconds of acks
+const SNAPSHOT_HISTORY: usize = 32;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum EntityKind {
+    Unit,
+    Structure,
+    Other,
+}
+
+// Transform quantized to fixed point, so entities that haven't moved compare equal
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EntityState {
+    pub kind: EntityKind,
+    pub owner: Option<PlayerId>,
+    pub translation: [i32; 3],
+    pub rotation: [i32; 4],
+}
+
+impl EntityState {
+    pub fn new(kind: EntityKind, owner: Option<PlayerId>, transform: &Transform) -> Self {
+        EntityState {
+            kind,
+            owner,
+            translation: transform.translation.to_array().map(|value| FixedPoint::from_num(value).to_bits()),
+            rotation: transform.rotation.to_array().map(|value| FixedPoint::from_num(value).to_bits()),
+        }
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        Vec3::from_array(self.translation.map(|bits| FixedPoint::from_bits(bits).to_num()))
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_array(self.rotation.map(|bits| FixedPoint::from_bits(bits).to_num())).normalize()
+    }
+}
+
+// Every simulated entity at one tick
+pub type WorldState = BTreeMap<SimId, EntityState>;
+
+// What changed about an entity since the baseline. Kind and owner never change, so they are only
+// sent for entities the baseline doesn't have.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EntityDelta {
+    pub sim_id: SimId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<(EntityKind, Option<PlayerId>)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<[i32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[i32; 4]>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub tick: u64,
+    // Tick of the state the deltas are relative to, None when they describe every entity
+    pub baseline: Option<u64>,
+    // Last command of the receiving client's that was applied
+    pub command_ack: u64,
+    // Snapshots too large for one datagram are sent in parts, each with some of the entities
+    pub part: u32,
+    pub parts: u32,
+    pub entities: Vec<EntityDelta>,
+    pub removed: Vec<SimId>,
+    pub stockpiles: Vec<(PlayerId, u32)>,
+}
+
+// Deltas turning the baseline into the current state, or describing all of it without one
+pub fn diff(baseline: Option<&WorldState>, current: &WorldState) -> (Vec<EntityDelta>, Vec<SimId>) {
+    let mut entities = Vec::new();
+    for (sim_id, state) in current.iter() {
+        let previous = baseline.and_then(|baseline| baseline.get(sim_id));
+        let delta = EntityDelta {
+            sim_id: *sim_id,
+            spawn: previous.is_none().then_some((state.kind, state.owner)),
+            translation: (previous.map(|previous| previous.translation) != Some(state.translation)).then_some(state.translation),
+            rotation: (previous.map(|previous| previous.rotation) != Some(state.rotation)).then_some(state.rotation),
+        };
+        if delta.spawn.is_some() || delta.translation.is_some() || delta.rotation.is_some() {
+            entities.push(delta);
+        }
+    }
+    let removed = baseline.map_or(Vec::new(), |baseline| baseline.keys()
+        .filter(|sim_id| !current.contains_key(sim_id))
+        .copied()
+        .collect());
+    (entities, removed)
+}
+
+// The state the deltas describe, None if they change an entity neither they nor the baseline define
+pub fn apply(baseline: Option<&WorldState>, entities: &[EntityDelta], removed: &[SimId]) -> Option<WorldState> {
+    let mut state = baseline.cloned().unwrap_or_default();
+    for sim_id in removed {
+        state.remove(sim_id);
+    }
+    for delta in entities {
+        if !state.contains_key(&delta.sim_id) {
+            let (kind, owner) = delta.spawn?;
+            state.insert(delta.sim_id, EntityState {
+                kind,
+                owner,
+                translation: [0; 3],
+                rotation: [0; 4],
+            });
+        }
+        let entity = state.get_mut(&delta.sim_id)?;
+        if let Some(translation) = delta.translation {
+            entity.translation = translation;
+        }
+        if let Some(rotation) = delta.rotation {
+            entity.rotation = rotation;
+        }
+    }
+    Some(state)
+}
+
+// Halve the snapshot until every part fits, numbering the parts. Parts keep everything but the
+// entities and removals, which are shared out between them.
+pub fn split(snapshot: Snapshot, fits: impl Fn(&Snapshot) -> bool) -> Vec<Snapshot> {
+    let mut parts = Vec::new();
+    let mut pending = vec![snapshot];
+    while let Some(mut part) = pending.pop() {
+        let items = part.entities.len() + part.removed.len();
+        if items <= 1 || fits(&part) {
+            parts.push(part);
+            continue;
+        }
+        let half = items / 2;
+        let (entities, removed) = if half <= part.entities.len() {
+            (part.entities.split_off(half), std::mem::take(&mut part.removed))
+        } else {
+            (Vec::new(), part.removed.split_off(half - part.entities.len()))
+        };
+        let second = Snapshot {
+            entities,
+            removed,
+            stockpiles: part.stockpiles.clone(),
+            ..part
+        };
+        // Popped first, so the parts come out in order
+        pending.push(second);
+        pending.push(part);
+    }
+    let count = parts.len() as u32;
+    for (index, part) in parts.iter_mut().enumerate() {
+        part.part = index as u32;
+        part.parts = count;
+    }
+    parts
+}
+
+// The snapshot the parts were split from, None without any
+pub fn merge(parts: Vec<Snapshot>) -> Option<Snapshot> {
+    let mut parts = parts.into_iter();
+    let mut snapshot = parts.next()?;
+    for part in parts {
+        snapshot.entities.extend(part.entities);
+        snapshot.removed.extend(part.removed);
+    }
+    snapshot.part = 0;
+    snapshot.parts = 1;
+    Some(snapshot)
+}
+
+// Recent world states by tick
+#[derive(Default)]
+pub struct SnapshotHistory {
+    states: VecDeque<(u64, WorldState)>,
+}
+
+impl SnapshotHistory {
+    pub fn push(&mut self, tick: u64, state: WorldState) {
+        if self.states.len() == SNAPSHOT_HISTORY {
+            self.states.pop_front();
+        }
+        self.states.push_back((tick, state));
+    }
+
+    pub fn get(&self, tick: u64) -> Option<&WorldState> {
+        self.states.iter()
+            .find(|(state_tick, _)| *state_tick == tick)
+            .map(|(_, state)| state)
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
@@ -1,15 +1,22 @@
 use bevy::app::*;
-use player::Player;
+use player::{Player, Stockpiles};
 use selection::{setup_selection_resource, Selection};
 
 pub mod materials;
 pub mod player;
+pub mod save;
 pub mod selection;
 pub mod settings;
+pub mod stats;
 
-pub fn initialize_resources(app: &mut App) {
-    app
-        .init_resource::<Player>()
-        .init_resource::<Selection>()
-        .add_systems(Startup, setup_selection_resource);
-}
\ No newline at end of file
+pub struct ResourcesPlugin;
+
+impl Plugin for ResourcesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Player>()
+            .init_resource::<Stockpiles>()
+            .init_resource::<Selection>()
+            .add_systems(Startup, setup_selection_resource);
+    }
+}
@@ -1,11 +1,65 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 
+pub type PlayerId = u8;
+
+// The player controlling this client
 #[derive(Default, Resource)]
 pub struct Player {
+    pub id: PlayerId,
+}
+
+// Resources each player has to spend on units and structures, reset every match
+#[derive(Default, Resource)]
+pub struct Stockpiles {
+    pub starting: u32,
+    amounts: HashMap<PlayerId, u32>,
+}
+
+impl Stockpiles {
+    pub fn reset(&mut self, starting: u32) {
+        self.starting = starting;
+        self.amounts.clear();
+    }
+
+    // Players that haven't gained or spent anything yet still hold the starting amount
+    pub fn get(&self, player_id: PlayerId) -> u32 {
+        self.amounts.get(&player_id).copied().unwrap_or(self.starting)
+    }
+
+    pub fn add(&mut self, player_id: PlayerId, amount: u32) {
+        let starting = self.starting;
+        let stockpile = self.amounts.entry(player_id).or_insert(starting);
+        *stockpile = stockpile.saturating_add(amount);
+    }
+
+    // Mirror a stockpile simulated elsewhere
+    pub fn set(&mut self, player_id: PlayerId, amount: u32) {
+        self.amounts.insert(player_id, amount);
+    }
+
+    // Stockpiles touched since the match started, in player order
+    pub fn amounts(&self) -> Vec<(PlayerId, u32)> {
+        let mut amounts: Vec<(PlayerId, u32)> = self.amounts.iter()
+            .map(|(player_id, amount)| (*player_id, *amount))
+            .collect();
+        amounts.sort_unstable();
+        amounts
+    }
+
+    // Spend if the player can afford it, returning whether they could
+    pub fn spend(&mut self, player_id: PlayerId, amount: u32) -> bool {
+        let starting = self.starting;
+        let stockpile = self.amounts.entry(player_id).or_insert(starting);
+        if *stockpile < amount {
+            return false;
+        }
+        *stockpile -= amount;
+        true
+    }
 }
 
 pub fn setup_player_resource(
     player: ResMut<Player>
 ) {
     
-}
\ No newline at end of file
+}
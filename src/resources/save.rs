@@ -0,0 +1,91 @@
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{controls::{bookmarks::CameraBookmarks, InputMap}, states::AppState};
+
+pub const SAVE_DIRECTORY: &str = "saves";
+pub const QUICK_SAVE_FILE: &str = "saves/quicksave.ron";
+
+#[derive(Event)]
+pub struct SaveGameEvent;
+
+#[derive(Event)]
+pub struct LoadGameEvent;
+
+// Everything persisted between sessions, serialized as RON
+#[derive(Default, Deserialize, Serialize)]
+pub struct SaveData {
+    pub camera_bookmarks: CameraBookmarks,
+}
+
+impl SaveData {
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        fs::create_dir_all(SAVE_DIRECTORY).map_err(|err| err.to_string())?;
+        fs::write(path, contents).map_err(|err| err.to_string())
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        ron::from_str(&contents).map_err(|err| err.to_string())
+    }
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_systems(Update, (
+                handle_save_keys
+                    .run_if(in_state(AppState::InGame)),
+                handle_save_game_event,
+                handle_load_game_event,
+            ).chain());
+    }
+}
+
+pub fn handle_save_keys(
+    key: Res<ButtonInput<KeyCode>>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+    mut ev_load: EventWriter<LoadGameEvent>,
+) {
+    let input_map = InputMap::default();
+    if key.just_pressed(input_map.quick_save) {
+        ev_save.send(SaveGameEvent);
+    }
+    if key.just_pressed(input_map.quick_load) {
+        ev_load.send(LoadGameEvent);
+    }
+}
+
+pub fn handle_save_game_event(
+    mut ev_save: EventReader<SaveGameEvent>,
+    bookmarks: Res<CameraBookmarks>,
+) {
+    let Some(_) = ev_save.read().last() else { return; };
+    let save_data = SaveData {
+        camera_bookmarks: CameraBookmarks {
+            slots: bookmarks.slots,
+        },
+    };
+    if let Err(err) = save_data.write(QUICK_SAVE_FILE) {
+        println!("Failed to save game to {}: {}", QUICK_SAVE_FILE, err);
+    }
+}
+
+pub fn handle_load_game_event(
+    mut ev_load: EventReader<LoadGameEvent>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+) {
+    let Some(_) = ev_load.read().last() else { return; };
+    match SaveData::read(QUICK_SAVE_FILE) {
+        Ok(save_data) => *bookmarks = save_data.camera_bookmarks,
+        Err(err) => println!("Failed to load game from {}: {}", QUICK_SAVE_FILE, err),
+    }
+}
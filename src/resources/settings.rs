@@ -1,34 +1,388 @@
-#![allow(unused)]
-use bevy::{pbr::{ShadowFilteringMethod, ShadowSamplers}, prelude::*};
+use std::fs;
 
-#[derive(Resource)]
+use bevy::{pbr::ShadowFilteringMethod, prelude::*, window::{PrimaryWindow, WindowMode}};
+use serde::{Deserialize, Serialize};
+
+use crate::{entities::Owner, ui::pause::PauseMenu};
+
+use super::player::PlayerId;
+
+pub const SETTINGS_FILE: &str = "settings.ron";
+pub const CURSOR_SIZE_DEFAULT: f32 = 24.;
+pub const MOUSE_SENSITIVITY_DEFAULT: f32 = 10.;
+pub const SCROLL_SPEED_DEFAULT: f32 = 50.;
+pub const ZOOM_SENSITIVITY_DEFAULT: f32 = 0.5;
+pub const UI_SCALE_DEFAULT: f32 = 1.;
+pub const GAME_SPEED_OPTIONS: [f32; 6] = [0.5, 0.75, 1., 1.5, 2., 3.];
+pub const STARTING_RESOURCES_OPTIONS: [u32; 4] = [500, 1000, 2500, 5000];
+pub const POPULATION_CAP_OPTIONS: [u32; 4] = [50, 100, 150, 200];
+// Input delay in lockstep turns
+pub const INPUT_DELAY_OPTIONS: [u64; 5] = [1, 2, 3, 4, 6];
+pub const NETWORK_PORT_DEFAULT: u16 = 7777;
+
+#[derive(Clone, Default, Deserialize, Resource, Serialize)]
+#[serde(default)]
 pub struct Settings {
-    accessibility: AccessibilitySettings,
-    audio: AudioSettings,
-    game: GameSettings,
-    input: InputSettings,
-    video: VideoSettings,
+    pub accessibility: AccessibilitySettings,
+    pub audio: AudioSettings,
+    pub game: GameSettings,
+    pub input: InputSettings,
+    pub network: NetworkSettings,
+    pub video: VideoSettings,
 }
 
-#[derive(Resource)]
-pub struct AccessibilitySettings;
+impl Settings {
+    pub fn write(&self) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        fs::write(SETTINGS_FILE, contents).map_err(|err| err.to_string())
+    }
+
+    pub fn read() -> Result<Self, String> {
+        let contents = fs::read_to_string(SETTINGS_FILE).map_err(|err| err.to_string())?;
+        ron::from_str(&contents).map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Clone, Deserialize, Resource, Serialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub team_palette: TeamPalette,
+    // Multiplier applied to all UI through UiScale
+    pub ui_scale: f32,
+    // Thick outlines drawn on top of geometry around selected entities
+    pub high_contrast_selection: bool,
+    pub edge_scroll: bool,
+    pub camera_drag: DragMode,
+    // Replaces animated camera moves with cuts
+    pub reduced_motion: bool,
+}
 
-#[derive(Resource)]
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            team_palette: TeamPalette::default(),
+            ui_scale: UI_SCALE_DEFAULT,
+            high_contrast_selection: false,
+            edge_scroll: true,
+            camera_drag: DragMode::default(),
+            reduced_motion: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum TeamPalette {
+    #[default]
+    Standard,
+    // Blue/orange based palette that stays distinct for red-green color blindness
+    Deuteranopia,
+    // Blue/red based palette that stays distinct for blue-yellow color blindness
+    Tritanopia,
+}
+
+impl TeamPalette {
+    pub fn color(&self, player_id: PlayerId) -> Color {
+        let colors = match self {
+            TeamPalette::Standard => [
+                Color::srgb_u8(124, 144, 255),
+                Color::srgb_u8(255, 96, 96),
+                Color::srgb_u8(255, 220, 96),
+                Color::srgb_u8(200, 120, 255),
+            ],
+            TeamPalette::Deuteranopia => [
+                Color::srgb_u8(0, 114, 178),
+                Color::srgb_u8(230, 159, 0),
+                Color::srgb_u8(240, 228, 66),
+                Color::srgb_u8(204, 121, 167),
+            ],
+            TeamPalette::Tritanopia => [
+                Color::srgb_u8(0, 158, 115),
+                Color::srgb_u8(213, 94, 0),
+                Color::srgb_u8(204, 121, 167),
+                Color::srgb_u8(240, 240, 240),
+            ],
+        };
+        colors[player_id as usize % colors.len()]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum DragMode {
+    // Camera drags while the button is held
+    #[default]
+    Hold,
+    // One click starts dragging, the next click stops
+    Toggle,
+}
+
+#[derive(Clone, Deserialize, Resource, Serialize)]
+#[serde(default)]
 pub struct AudioSettings {
-    master_volume: f32,
-    music_volume: f32,
-    sfx_volume: f32,
-    voice_volume: f32,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub voice_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.,
+            music_volume: 1.,
+            sfx_volume: 1.,
+            voice_volume: 1.,
+        }
+    }
+}
+
+// Match rules picked in the lobby, kept between sessions as the defaults for the next match
+#[derive(Clone, Deserialize, Resource, Serialize)]
+#[serde(default)]
+pub struct GameSettings {
+    // Relative speed of virtual and physics time, from 0.5 to 3
+    pub game_speed: f32,
+    pub starting_resources: u32,
+    pub population_cap: u32,
+    pub victory_condition: VictoryCondition,
+    // Computer player taking the second seat in local matches, None for an empty one
+    pub opponent: Option<AiDifficulty>,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            game_speed: 1.,
+            starting_resources: 1000,
+            population_cap: 100,
+            victory_condition: VictoryCondition::default(),
+            opponent: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum VictoryCondition {
+    // A player is out once their HQ structures are destroyed
+    #[default]
+    DestroyHq,
+    // A player is out once they have no units left
+    EliminateAllUnits,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AiDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl AiDifficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "easy" => Some(AiDifficulty::Easy),
+            "normal" => Some(AiDifficulty::Normal),
+            "hard" => Some(AiDifficulty::Hard),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Resource, Serialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    // Port to host LAN games on
+    pub port: u16,
+    // Turns between issuing a command and it taking effect. Higher hides more latency.
+    pub input_delay: u64,
+    // Host address last typed in the join screen
+    pub join_address: String,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            port: NETWORK_PORT_DEFAULT,
+            input_delay: 2,
+            join_address: format!("127.0.0.1:{}", NETWORK_PORT_DEFAULT),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum CursorKind {
+    // OS cursor, positioned by the windowing system
+    #[default]
+    Hardware,
+    // Cursor image drawn by the UI and moved by raw mouse motion
+    Software,
 }
 
-#[derive(Resource)]
-pub struct GameSettings;
+#[derive(Clone, Deserialize, Resource, Serialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub cursor_kind: CursorKind,
+    // Size of the software cursor in logical pixels, so it follows the display scale factor
+    pub cursor_size: f32,
+    // Software cursor speed relative to raw mouse motion
+    pub mouse_sensitivity: f32,
+    // Camera pan speed at the closest zoom level
+    pub scroll_speed: f32,
+    // Zoom levels per mouse wheel line
+    pub zoom_sensitivity: f32,
+    // Shows and hides the debug overlay in debug builds. Shares F3 with the third camera bookmark
+    // by default, move it to keep the two apart.
+    pub debug_menu_key: KeyCode,
+}
 
-#[derive(Resource)]
-pub struct InputSettings;
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            cursor_kind: CursorKind::default(),
+            cursor_size: CURSOR_SIZE_DEFAULT,
+            mouse_sensitivity: MOUSE_SENSITIVITY_DEFAULT,
+            scroll_speed: SCROLL_SPEED_DEFAULT,
+            zoom_sensitivity: ZOOM_SENSITIVITY_DEFAULT,
+            debug_menu_key: KeyCode::F3,
+        }
+    }
+}
 
-#[derive(Resource)]
+#[derive(Clone, Default, Deserialize, Resource, Serialize)]
+#[serde(default)]
 pub struct VideoSettings {
-    anti_aliasing: Msaa,
-    shadow_filtering_method: ShadowFilteringMethod,
-}
\ No newline at end of file
+    #[serde(with = "MsaaDef")]
+    pub anti_aliasing: Msaa,
+    #[serde(with = "ShadowFilteringMethodDef")]
+    pub shadow_filtering_method: ShadowFilteringMethod,
+    pub window_mode: WindowMode,
+}
+
+// Serde mirrors of render types that don't implement serde themselves
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "Msaa")]
+enum MsaaDef {
+    Off,
+    Sample2,
+    Sample4,
+    Sample8,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "ShadowFilteringMethod")]
+enum ShadowFilteringMethodDef {
+    Hardware2x2,
+    Gaussian,
+    Temporal,
+}
+
+// Read the persisted settings, falling back to defaults when there are none yet
+pub fn load_settings() -> Settings {
+    match Settings::read() {
+        Ok(settings) => settings,
+        Err(err) => {
+            println!("Using default settings, failed to read {}: {}", SETTINGS_FILE, err);
+            Settings::default()
+        },
+    }
+}
+
+pub struct SettingsPlugin {
+    // Read and write settings.ron, otherwise run on defaults and leave the file alone
+    pub persist: bool,
+}
+
+impl Default for SettingsPlugin {
+    fn default() -> Self {
+        SettingsPlugin {
+            persist: true,
+        }
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if !self.persist {
+            app.init_resource::<Settings>();
+            return;
+        }
+        app
+            .insert_resource(load_settings())
+            // Hold off while the settings page is open so dragging a slider doesn't write every frame
+            .add_systems(Update, persist_settings
+                .run_if(not(in_state(PauseMenu::Settings)).and_then(resource_changed::<Settings>)));
+    }
+}
+
+// Applies settings to the window, renderer and UI, left out when running headless
+pub struct SettingsDisplayPlugin;
+
+impl Plugin for SettingsDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                apply_video_settings,
+                apply_accessibility_settings
+                    .run_if(resource_changed::<Settings>),
+            ));
+    }
+}
+
+pub fn apply_video_settings(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut msaa: ResMut<Msaa>,
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    q_cameras: Query<(Entity, Ref<Camera3d>)>,
+) {
+    let changed = settings.is_changed();
+    if changed {
+        // Only write on a real difference, touching these triggers pipeline and window updates
+        if *msaa != settings.video.anti_aliasing {
+            *msaa = settings.video.anti_aliasing;
+        }
+        if let Ok(mut window) = q_windows.get_single_mut() {
+            if window.mode != settings.video.window_mode {
+                window.mode = settings.video.window_mode;
+            }
+        }
+    }
+    // Shadow filtering is per camera, so cameras spawned later pick it up too
+    for (entity, camera) in q_cameras.iter() {
+        if changed || camera.is_added() {
+            commands.entity(entity).insert(settings.video.shadow_filtering_method);
+        }
+    }
+}
+
+pub fn apply_accessibility_settings(
+    settings: Res<Settings>,
+    // Palette the team materials were last tinted with, entities spawn with the current one
+    mut applied_palette: Local<Option<TeamPalette>>,
+    mut ui_scale: ResMut<UiScale>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_owned: Query<(&Owner, &Handle<StandardMaterial>)>,
+) {
+    if ui_scale.0 != settings.accessibility.ui_scale {
+        ui_scale.0 = settings.accessibility.ui_scale;
+    }
+    let palette = settings.accessibility.team_palette;
+    if applied_palette.replace(palette).unwrap_or(palette) == palette {
+        return;
+    }
+    for (owner, material_handle) in q_owned.iter() {
+        let Some(material) = materials.get_mut(material_handle) else { continue; };
+        material.base_color = settings.accessibility.team_palette.color(owner.0);
+    }
+}
+
+pub fn persist_settings(settings: Res<Settings>) {
+    // Skip the initial insertion, the file already matches it
+    if settings.is_added() {
+        return;
+    }
+    if let Err(err) = settings.write() {
+        println!("Failed to save settings to {}: {}", SETTINGS_FILE, err);
+    }
+}
@@ -0,0 +1,334 @@
+use std::{fmt::Write as _, fs, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::Serialize;
+
+use crate::{controls::selection::{Selected, SelectionEvent}, entities::{structures::Structure, units::Unit, Cost, Owner}, simulation::SimulationSet, states::{AppState, InMatch}};
+
+use super::player::{Player, PlayerId, Stockpiles};
+
+pub const STATS_DIRECTORY: &str = "stats";
+const TIMELINE_INTERVAL: f32 = 5.;
+
+// Sent by gatherers when they drop resources off
+#[derive(Event)]
+pub struct ResourcesGatheredEvent {
+    pub player: PlayerId,
+    pub amount: u32,
+}
+
+// Sent by combat when a unit or structure is destroyed, crediting the player who destroyed it
+#[derive(Event)]
+pub struct KillEvent {
+    pub killer: PlayerId,
+    pub victim: PlayerId,
+}
+
+// Sent whenever a player gives an order, logged for balance analysis
+#[derive(Event)]
+pub struct OrderIssuedEvent {
+    pub player: PlayerId,
+    pub order: String,
+    pub units: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct PlayerStats {
+    pub units_built: u32,
+    pub units_lost: u32,
+    pub structures_lost: u32,
+    pub resources_gathered: u32,
+    pub kills: u32,
+    // Mouse and key presses, only counted for the local player
+    pub actions: u32,
+}
+
+// State of one player at one point in the match
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct PlayerSample {
+    pub resources: u32,
+    // Summed cost of all living units
+    pub army_value: u32,
+    // Number of living units
+    pub supply: u32,
+    pub kills: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TimelineSample {
+    pub time: f32,
+    pub players: HashMap<PlayerId, PlayerSample>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderLogEntry {
+    pub time: f32,
+    pub player: PlayerId,
+    pub order: String,
+    pub units: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SelectionLogEntry {
+    pub time: f32,
+    // Selection events handled this frame
+    pub changes: u32,
+    // Entities selected once they were applied
+    pub selected: u32,
+}
+
+// Everything that happened during the current or last match, kept after it ends for the summary
+#[derive(Default, Resource, Serialize)]
+pub struct MatchStats {
+    pub elapsed: f32,
+    pub players: Vec<PlayerId>,
+    pub per_player: HashMap<PlayerId, PlayerStats>,
+    pub timeline: Vec<TimelineSample>,
+    pub orders: Vec<OrderLogEntry>,
+    pub selections: Vec<SelectionLogEntry>,
+    // Owners of tracked entities, since they can't be queried after despawning
+    #[serde(skip)]
+    owners: HashMap<Entity, (PlayerId, bool)>,
+    #[serde(skip)]
+    next_sample: f32,
+}
+
+impl MatchStats {
+    pub fn player(&self, player_id: PlayerId) -> PlayerStats {
+        self.per_player.get(&player_id).copied().unwrap_or_default()
+    }
+
+    fn player_mut(&mut self, player_id: PlayerId) -> &mut PlayerStats {
+        if !self.players.contains(&player_id) {
+            self.players.push(player_id);
+            self.players.sort();
+        }
+        self.per_player.entry(player_id).or_default()
+    }
+
+    // Actions per minute of game time
+    pub fn apm(&self, player_id: PlayerId) -> f32 {
+        if self.elapsed <= 0. {
+            return 0.;
+        }
+        self.player(player_id).actions as f32 / (self.elapsed / 60.)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+
+    // Timeline as one row per sample and player, for spreadsheets
+    pub fn timeline_csv(&self) -> String {
+        let mut csv = String::from("time,player,resources,army_value,supply,kills\n");
+        for sample in self.timeline.iter() {
+            for player_id in self.players.iter() {
+                let player_sample = sample.players.get(player_id).copied().unwrap_or_default();
+                let _ = writeln!(
+                    csv,
+                    "{:.2},{},{},{},{},{}",
+                    sample.time,
+                    player_id,
+                    player_sample.resources,
+                    player_sample.army_value,
+                    player_sample.supply,
+                    player_sample.kills,
+                );
+            }
+        }
+        csv
+    }
+
+    // Writes <name>.json with everything and <name>.csv with the timeline
+    pub fn export(&self, directory: impl AsRef<Path>, name: &str) -> Result<(), String> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+        fs::write(directory.join(format!("{}.json", name)), self.to_json()?).map_err(|err| err.to_string())?;
+        fs::write(directory.join(format!("{}.csv", name)), self.timeline_csv()).map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Clone, Resource)]
+pub struct StatsConfig {
+    // Seconds of game time between timeline samples
+    pub timeline_interval: f32,
+    // Where statistics are written when a match ends, None to keep them in memory only
+    pub export_directory: Option<String>,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        StatsConfig {
+            timeline_interval: TIMELINE_INTERVAL,
+            export_directory: Some(STATS_DIRECTORY.to_string()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StatsPlugin {
+    pub config: StatsConfig,
+}
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(self.config.clone())
+            .add_event::<ResourcesGatheredEvent>()
+            .add_event::<KillEvent>()
+            .add_event::<OrderIssuedEvent>()
+            // Also registered by the selection systems, which headless mode leaves out
+            .add_event::<SelectionEvent>()
+            .init_resource::<MatchStats>()
+            .add_systems(OnEnter(InMatch), reset_match_stats)
+            .add_systems(OnExit(InMatch), export_match_stats)
+            .add_systems(FixedUpdate, (
+                track_spawns,
+                track_losses,
+                track_match_events,
+                sample_timeline,
+            ).chain().in_set(SimulationSet::Logic))
+            // Selections and input are local to this client, so they are logged per frame
+            .add_systems(Update, (
+                track_selections,
+                track_actions
+                    .run_if(in_state(AppState::InGame)),
+            ).run_if(in_state(InMatch)));
+    }
+}
+
+pub fn reset_match_stats(mut stats: ResMut<MatchStats>) {
+    *stats = MatchStats::default();
+}
+
+pub fn export_match_stats(config: Res<StatsConfig>, stats: Res<MatchStats>) {
+    let Some(directory) = config.export_directory.as_ref() else { return; };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let name = format!("match-{}", timestamp);
+    match stats.export(directory, &name) {
+        Ok(()) => println!("Exported match statistics to {}/{}", directory, name),
+        Err(err) => println!("Failed to export match statistics: {}", err),
+    }
+}
+
+pub fn track_spawns(
+    mut stats: ResMut<MatchStats>,
+    q_added: Query<(Entity, &Owner, Has<Unit>, Has<Structure>), Added<Owner>>,
+) {
+    for (entity, owner, is_unit, is_structure) in q_added.iter() {
+        if !is_unit && !is_structure {
+            continue;
+        }
+        stats.owners.insert(entity, (owner.0, is_structure));
+        let player_stats = stats.player_mut(owner.0);
+        if is_unit {
+            player_stats.units_built += 1;
+        }
+    }
+}
+
+// Checked against the tracked owners rather than RemovedComponents, which can be dropped
+// between simulation ticks when several frames pass without one
+pub fn track_losses(
+    mut stats: ResMut<MatchStats>,
+    q_owned: Query<(), With<Owner>>,
+) {
+    let lost: Vec<Entity> = stats.owners.keys()
+        .filter(|entity| !q_owned.contains(**entity))
+        .copied()
+        .collect();
+    for entity in lost {
+        let Some((player_id, is_structure)) = stats.owners.remove(&entity) else { continue; };
+        let player_stats = stats.player_mut(player_id);
+        if is_structure {
+            player_stats.structures_lost += 1;
+        } else {
+            player_stats.units_lost += 1;
+        }
+    }
+}
+
+pub fn track_match_events(
+    mut stats: ResMut<MatchStats>,
+    mut ev_gathered: EventReader<ResourcesGatheredEvent>,
+    mut ev_kill: EventReader<KillEvent>,
+    mut ev_order: EventReader<OrderIssuedEvent>,
+) {
+    for event in ev_gathered.read() {
+        stats.player_mut(event.player).resources_gathered += event.amount;
+    }
+    for event in ev_kill.read() {
+        stats.player_mut(event.killer).kills += 1;
+    }
+    for event in ev_order.read() {
+        let entry = OrderLogEntry {
+            time: stats.elapsed,
+            player: event.player,
+            order: event.order.clone(),
+            units: event.units,
+        };
+        stats.orders.push(entry);
+    }
+}
+
+pub fn track_selections(
+    mut stats: ResMut<MatchStats>,
+    mut ev_selection: EventReader<SelectionEvent>,
+    q_selected: Query<(), With<Selected>>,
+) {
+    let changes = ev_selection.read().count() as u32;
+    if changes == 0 {
+        return;
+    }
+    let entry = SelectionLogEntry {
+        time: stats.elapsed,
+        changes,
+        selected: q_selected.iter().count() as u32,
+    };
+    stats.selections.push(entry);
+}
+
+pub fn track_actions(
+    mut stats: ResMut<MatchStats>,
+    player: Res<Player>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    key: Res<ButtonInput<KeyCode>>,
+) {
+    let actions = mouse.get_just_pressed().count() + key.get_just_pressed().count();
+    if actions > 0 {
+        stats.player_mut(player.id).actions += actions as u32;
+    }
+}
+
+pub fn sample_timeline(
+    time: Res<Time>,
+    config: Res<StatsConfig>,
+    mut stats: ResMut<MatchStats>,
+    stockpiles: Res<Stockpiles>,
+    q_units: Query<(&Owner, Option<&Cost>), With<Unit>>,
+) {
+    stats.elapsed += time.delta_seconds();
+    if stats.elapsed < stats.next_sample {
+        return;
+    }
+    stats.next_sample += config.timeline_interval;
+    let mut players: HashMap<PlayerId, PlayerSample> = stats.players.iter()
+        .map(|player_id| (*player_id, PlayerSample {
+            resources: stockpiles.get(*player_id),
+            kills: stats.player(*player_id).kills,
+            ..default()
+        }))
+        .collect();
+    for (owner, cost) in q_units.iter() {
+        let player_sample = players.entry(owner.0).or_default();
+        player_sample.supply += 1;
+        player_sample.army_value += cost.map_or(0, |cost| cost.0);
+    }
+    let sample = TimelineSample {
+        time: stats.elapsed,
+        players,
+    };
+    stats.timeline.push(sample);
+}
@@ -0,0 +1,332 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    network::{client::ServerConnection, session::NetworkSession},
+    entities::{combat::Health, structures::{is_valid_site, Structure}, units::{behaviour::{OrderQueue, Stance, UnitOrder}, spawn_unit, Unit, UNIT_COST}, world_objects::ResourceNode, Owner},
+    resources::{player::{PlayerId, Stockpiles}, settings::Settings, stats::OrderIssuedEvent},
+    states::{match_rules::{population, ActiveMatchRules}, AppState, InMatch},
+};
+
+use super::{fixed::FixedVec2, SimId, SimIdAllocator, SimulationSet, SimulationTick};
+
+// Ticks between a command being issued and applied, so it can reach every peer first
+pub const COMMAND_DELAY_TICKS: u64 = 1;
+// Distance from a structure at which trained units appear
+const TRAIN_OFFSET: Vec3 = Vec3::new(0., 0., 4.);
+
+// Everything a player can do that affects the match. Gameplay only changes through these, so
+// replaying the same commands on the same ticks reproduces the match.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum PlayerCommand {
+    // Selection is local to each client, recorded so replays show what players were looking at
+    Select { units: Vec<SimId> },
+    // Queued orders are carried out after the ones the units already have instead of replacing them
+    Move { units: Vec<SimId>, target: FixedVec2, #[serde(default)] queued: bool },
+    Attack { units: Vec<SimId>, target: SimId, #[serde(default)] queued: bool },
+    Patrol { units: Vec<SimId>, target: FixedVec2, queued: bool },
+    Guard { units: Vec<SimId>, target: SimId, queued: bool },
+    Gather { units: Vec<SimId>, node: SimId, queued: bool },
+    Stance { units: Vec<SimId>, stance: Stance },
+    // Built right away, or by the given units once they walk there
+    Build { position: FixedVec2, #[serde(default)] units: Vec<SimId>, #[serde(default)] queued: bool },
+    Train { structure: SimId },
+    // Destroys everything the player owns. Players can only give it for themselves, to resign, and
+    // the debug keys give it on behalf of local opponents to try out the end of a match.
+    Eliminate { player: PlayerId },
+}
+
+impl PlayerCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerCommand::Select { .. } => "select",
+            PlayerCommand::Move { .. } => "move",
+            PlayerCommand::Attack { .. } => "attack",
+            PlayerCommand::Patrol { .. } => "patrol",
+            PlayerCommand::Guard { .. } => "guard",
+            PlayerCommand::Gather { .. } => "gather",
+            PlayerCommand::Stance { .. } => "stance",
+            PlayerCommand::Build { .. } => "build",
+            PlayerCommand::Train { .. } => "train",
+            PlayerCommand::Eliminate { .. } => "eliminate",
+        }
+    }
+
+    // Units the command is given to
+    pub fn units(&self) -> &[SimId] {
+        match self {
+            PlayerCommand::Select { units }
+            | PlayerCommand::Move { units, .. }
+            | PlayerCommand::Attack { units, .. }
+            | PlayerCommand::Patrol { units, .. }
+            | PlayerCommand::Guard { units, .. }
+            | PlayerCommand::Gather { units, .. }
+            | PlayerCommand::Stance { units, .. }
+            | PlayerCommand::Build { units, .. } => units,
+            PlayerCommand::Train { .. } | PlayerCommand::Eliminate { .. } => &[],
+        }
+    }
+}
+
+// A command and the simulation tick it is applied on
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TimedCommand {
+    pub tick: u64,
+    pub player: PlayerId,
+    pub command: PlayerCommand,
+}
+
+// Sent by input and AI to have a command scheduled for the next tick it can be applied on
+#[derive(Event)]
+pub struct IssueCommandEvent {
+    pub player: PlayerId,
+    pub command: PlayerCommand,
+}
+
+// Commands waiting for their tick
+#[derive(Default, Resource)]
+pub struct CommandQueue {
+    pending: Vec<TimedCommand>,
+}
+
+impl CommandQueue {
+    pub fn push(&mut self, command: TimedCommand) {
+        self.pending.push(command);
+    }
+
+    pub fn extend(&mut self, commands: impl IntoIterator<Item = TimedCommand>) {
+        self.pending.extend(commands);
+    }
+
+    // Commands due by the given tick, by player and then in the order they were issued
+    pub fn take_due(&mut self, tick: u64) -> Vec<TimedCommand> {
+        let (mut due, pending): (Vec<_>, Vec<_>) = self.pending.drain(..)
+            .partition(|command| command.tick <= tick);
+        self.pending = pending;
+        due.sort_by_key(|command| (command.tick, command.player));
+        due
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+// Every command that came due this match, in the order it was applied
+#[derive(Default, Resource)]
+pub struct CommandLog {
+    pub commands: Vec<TimedCommand>,
+}
+
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<IssueCommandEvent>()
+            .init_resource::<CommandQueue>()
+            .init_resource::<CommandLog>()
+            .add_systems(OnExit(InMatch), reset_commands)
+            // Networked matches hold local commands back for the next lockstep turn or send them to the server instead
+            .add_systems(Update, queue_issued_commands
+                .run_if(in_state(AppState::InGame)
+                    .and_then(not(resource_exists::<NetworkSession>))
+                    .and_then(not(resource_exists::<ServerConnection>))))
+            .add_systems(FixedUpdate, apply_commands.in_set(SimulationSet::Commands));
+    }
+}
+
+pub fn reset_commands(
+    mut queue: ResMut<CommandQueue>,
+    mut log: ResMut<CommandLog>,
+) {
+    queue.clear();
+    log.commands.clear();
+}
+
+// Events only live for two frames, which can pass without a tick, so they are queued every frame
+pub fn queue_issued_commands(
+    tick: Res<SimulationTick>,
+    mut queue: ResMut<CommandQueue>,
+    mut ev_issue: EventReader<IssueCommandEvent>,
+) {
+    for event in ev_issue.read() {
+        queue.push(TimedCommand {
+            tick: tick.0 + COMMAND_DELAY_TICKS,
+            player: event.player,
+            command: event.command.clone(),
+        });
+    }
+}
+
+// Entity, owner, position and kind of every simulated entity, looked up by id
+struct SimEntity {
+    entity: Entity,
+    owner: Option<PlayerId>,
+    translation: Vec3,
+    is_unit: bool,
+    is_structure: bool,
+    is_resource_node: bool,
+}
+
+pub fn apply_commands(
+    mut commands: Commands,
+    tick: Res<SimulationTick>,
+    settings: Res<Settings>,
+    rules: Res<ActiveMatchRules>,
+    mut queue: ResMut<CommandQueue>,
+    mut log: ResMut<CommandLog>,
+    mut stockpiles: ResMut<Stockpiles>,
+    mut sim_ids: ResMut<SimIdAllocator>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ev_order: EventWriter<OrderIssuedEvent>,
+    q_simulated: Query<(Entity, &SimId, Option<&Owner>, &Transform, Has<Unit>, Has<Structure>, Has<ResourceNode>)>,
+    q_units: Query<&Owner, With<Unit>>,
+    mut q_queues: Query<&mut OrderQueue>,
+    mut q_health: Query<(&Owner, &mut Health)>,
+) {
+    let due = queue.take_due(tick.0);
+    if due.is_empty() {
+        return;
+    }
+    let sim_entities: HashMap<SimId, SimEntity> = q_simulated.iter()
+        .map(|(entity, sim_id, owner, transform, is_unit, is_structure, is_resource_node)| (*sim_id, SimEntity {
+            entity,
+            owner: owner.map(|owner| owner.0),
+            translation: transform.translation,
+            is_unit,
+            is_structure,
+            is_resource_node,
+        }))
+        .collect();
+    // Players can only order their own units around
+    let owned_units = |player: PlayerId, units: &[SimId]| -> Vec<Entity> {
+        units.iter()
+            .filter_map(|sim_id| sim_entities.get(sim_id))
+            .filter(|sim_entity| sim_entity.is_unit && sim_entity.owner == Some(player))
+            .map(|sim_entity| sim_entity.entity)
+            .collect()
+    };
+    // Units that didn't have an order queue yet get one once every command is applied
+    let mut new_queues: HashMap<Entity, OrderQueue> = HashMap::new();
+    let mut give_order = |entity: Entity, order: UnitOrder, queued: bool| {
+        match q_queues.get_mut(entity) {
+            Ok(mut queue) => queue.give(order, queued),
+            Err(_) => new_queues.entry(entity).or_default().give(order, queued),
+        }
+    };
+    // Units trained this tick count towards the cap before they are spawned
+    let mut trained: HashMap<PlayerId, u32> = HashMap::new();
+
+    for timed in due {
+        // Logged even when rejected, so replays and peers see exactly what was issued
+        log.commands.push(timed.clone());
+        let player = timed.player;
+        match &timed.command {
+            PlayerCommand::Select { .. } => {},
+            PlayerCommand::Move { units, target, queued } => {
+                for entity in owned_units(player, units) {
+                    give_order(entity, UnitOrder::Move(*target), *queued);
+                }
+            },
+            PlayerCommand::Attack { units, target, queued } => {
+                if !sim_entities.get(target).is_some_and(|sim_entity| sim_entity.owner.is_some_and(|owner| owner != player)) {
+                    continue;
+                }
+                for entity in owned_units(player, units) {
+                    give_order(entity, UnitOrder::Attack(*target), *queued);
+                }
+            },
+            PlayerCommand::Patrol { units, target, queued } => {
+                for entity in owned_units(player, units) {
+                    give_order(entity, UnitOrder::Patrol(vec![*target]), *queued);
+                }
+            },
+            PlayerCommand::Guard { units, target, queued } => {
+                // Only the player's own units and structures can be guarded
+                if !sim_entities.get(target).is_some_and(|sim_entity| sim_entity.owner == Some(player)) {
+                    continue;
+                }
+                for entity in owned_units(player, units) {
+                    // Guarding itself would just stand still forever
+                    if sim_entities.get(target).is_some_and(|sim_entity| sim_entity.entity == entity) {
+                        continue;
+                    }
+                    give_order(entity, UnitOrder::Guard(*target), *queued);
+                }
+            },
+            PlayerCommand::Gather { units, node, queued } => {
+                if !sim_entities.get(node).is_some_and(|sim_entity| sim_entity.is_resource_node) {
+                    continue;
+                }
+                for entity in owned_units(player, units) {
+                    give_order(entity, UnitOrder::Gather(*node), *queued);
+                }
+            },
+            PlayerCommand::Stance { units, stance } => {
+                for entity in owned_units(player, units) {
+                    commands.entity(entity).insert(*stance);
+                }
+            },
+            PlayerCommand::Build { position, units, queued } => {
+                // Builders pay once they reach the site
+                let builders = owned_units(player, units);
+                if builders.is_empty() {
+                    continue;
+                }
+                let obstacles = sim_entities.values()
+                    .filter(|sim_entity| sim_entity.is_structure || sim_entity.is_resource_node)
+                    .map(|sim_entity| FixedVec2::from_vec2(sim_entity.translation.xz()));
+                if !is_valid_site(*position, obstacles) {
+                    println!("Player {} can't build at {:?}", player, position);
+                    continue;
+                }
+                for entity in builders {
+                    give_order(entity, UnitOrder::Build(*position), *queued);
+                }
+            },
+            PlayerCommand::Train { structure } => {
+                let Some(sim_entity) = sim_entities.get(structure) else { continue; };
+                if !sim_entity.is_structure || sim_entity.owner != Some(player) {
+                    continue;
+                }
+                let trained_count = trained.entry(player).or_default();
+                if population(&q_units, player) + *trained_count >= rules.game.population_cap {
+                    println!("Player {} is at the population cap", player);
+                    continue;
+                }
+                if !stockpiles.spend(player, UNIT_COST) {
+                    println!("Player {} can't afford a unit", player);
+                    continue;
+                }
+                *trained_count += 1;
+                spawn_unit(&mut commands, &mut meshes, &mut materials, &settings, sim_ids.next(), player, sim_entity.translation + TRAIN_OFFSET);
+            },
+            PlayerCommand::Eliminate { player: eliminated } => {
+                if *eliminated != player {
+                    println!("Player {} can't eliminate player {}", player, eliminated);
+                    continue;
+                }
+                // Destroyed through combat like anything else, so losses are counted
+                for (_, mut health) in q_health.iter_mut().filter(|(owner, _)| owner.0 == *eliminated) {
+                    health.current = 0;
+                }
+            },
+        }
+        if !matches!(timed.command, PlayerCommand::Select { .. }) {
+            ev_order.send(OrderIssuedEvent {
+                player,
+                order: timed.command.name().to_string(),
+                units: timed.command.units().len() as u32,
+            });
+        }
+    }
+    for (entity, queue) in new_queues {
+        commands.entity(entity).insert(queue);
+    }
+}
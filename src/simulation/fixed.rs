@@ -0,0 +1,207 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+const FRACTIONAL_BITS: u32 = 16;
+const ONE_BITS: i32 = 1 << FRACTIONAL_BITS;
+
+// Signed 16.16 fixed-point number. Simulation math done in these gives the same bits on every
+// machine, unlike floats whose rounding can differ between compilers and instruction sets.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct FixedPoint(i32);
+
+impl FixedPoint {
+    pub const ZERO: FixedPoint = FixedPoint(0);
+    pub const ONE: FixedPoint = FixedPoint(ONE_BITS);
+    pub const MAX: FixedPoint = FixedPoint(i32::MAX);
+    pub const MIN: FixedPoint = FixedPoint(i32::MIN);
+
+    pub const fn from_bits(bits: i32) -> Self {
+        FixedPoint(bits)
+    }
+
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    pub const fn from_int(value: i32) -> Self {
+        FixedPoint(value << FRACTIONAL_BITS)
+    }
+
+    // Rounds to the nearest representable value. Only use on values that are already the same
+    // on every machine, such as constants or data from map files.
+    pub fn from_num(value: f32) -> Self {
+        FixedPoint((value * ONE_BITS as f32).round() as i32)
+    }
+
+    pub fn to_num(self) -> f32 {
+        self.0 as f32 / ONE_BITS as f32
+    }
+
+    pub fn abs(self) -> Self {
+        FixedPoint(self.0.saturating_abs())
+    }
+
+    pub fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return FixedPoint::ZERO;
+        }
+        FixedPoint(integer_sqrt((self.0 as u64) << FRACTIONAL_BITS) as i32)
+    }
+}
+
+// Bit by bit integer square root, rounded down
+fn integer_sqrt(value: u64) -> u64 {
+    let mut root = 0u64;
+    let mut bit = 1u64 << 62;
+    while bit > value {
+        bit >>= 2;
+    }
+    let mut remainder = value;
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+impl Add for FixedPoint {
+    type Output = FixedPoint;
+
+    fn add(self, rhs: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl AddAssign for FixedPoint {
+    fn add_assign(&mut self, rhs: FixedPoint) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for FixedPoint {
+    type Output = FixedPoint;
+
+    fn sub(self, rhs: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SubAssign for FixedPoint {
+    fn sub_assign(&mut self, rhs: FixedPoint) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul for FixedPoint {
+    type Output = FixedPoint;
+
+    fn mul(self, rhs: FixedPoint) -> FixedPoint {
+        let product = (self.0 as i64 * rhs.0 as i64) >> FRACTIONAL_BITS;
+        FixedPoint(product.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
+
+impl Div for FixedPoint {
+    type Output = FixedPoint;
+
+    // Dividing by zero saturates instead of panicking, so bad data can't take the simulation down
+    fn div(self, rhs: FixedPoint) -> FixedPoint {
+        if rhs.0 == 0 {
+            return if self.0 < 0 { FixedPoint::MIN } else { FixedPoint::MAX };
+        }
+        let quotient = ((self.0 as i64) << FRACTIONAL_BITS) / rhs.0 as i64;
+        FixedPoint(quotient.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
+
+impl Neg for FixedPoint {
+    type Output = FixedPoint;
+
+    fn neg(self) -> FixedPoint {
+        FixedPoint(self.0.saturating_neg())
+    }
+}
+
+// Position or direction on the ground plane, x and z in world space
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct FixedVec2 {
+    pub x: FixedPoint,
+    pub y: FixedPoint,
+}
+
+impl FixedVec2 {
+    pub const ZERO: FixedVec2 = FixedVec2 { x: FixedPoint::ZERO, y: FixedPoint::ZERO };
+
+    pub const fn new(x: FixedPoint, y: FixedPoint) -> Self {
+        FixedVec2 { x, y }
+    }
+
+    pub fn from_vec2(value: Vec2) -> Self {
+        FixedVec2::new(FixedPoint::from_num(value.x), FixedPoint::from_num(value.y))
+    }
+
+    pub fn to_vec2(self) -> Vec2 {
+        Vec2::new(self.x.to_num(), self.y.to_num())
+    }
+
+    // Squared in 64 bits, so lengths across the whole map don't overflow on the way
+    pub fn length(self) -> FixedPoint {
+        let x = self.x.0.unsigned_abs() as u64;
+        let y = self.y.0.unsigned_abs() as u64;
+        let root = integer_sqrt(x * x + y * y);
+        FixedPoint(root.min(i32::MAX as u64) as i32)
+    }
+
+    pub fn distance(self, other: FixedVec2) -> FixedPoint {
+        (other - self).length()
+    }
+
+    pub fn normalize_or_zero(self) -> Self {
+        let length = self.length();
+        if length == FixedPoint::ZERO {
+            return FixedVec2::ZERO;
+        }
+        FixedVec2::new(self.x / length, self.y / length)
+    }
+
+    // Step towards the target by at most max_distance, landing exactly on it when close enough
+    pub fn move_towards(self, target: FixedVec2, max_distance: FixedPoint) -> Self {
+        let offset = target - self;
+        let distance = offset.length();
+        if distance <= max_distance {
+            return target;
+        }
+        self + offset.normalize_or_zero() * max_distance
+    }
+}
+
+impl Add for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn add(self, rhs: FixedVec2) -> FixedVec2 {
+        FixedVec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn sub(self, rhs: FixedVec2) -> FixedVec2 {
+        FixedVec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<FixedPoint> for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn mul(self, rhs: FixedPoint) -> FixedVec2 {
+        FixedVec2::new(self.x * rhs, self.y * rhs)
+    }
+}
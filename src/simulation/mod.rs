@@ -0,0 +1,198 @@
+use std::{collections::VecDeque, hash::Hasher, time::Duration};
+
+use avian3d::PhysicsPlugins;
+use bevy::prelude::*;
+use fixed::FixedPoint;
+use serde::{Deserialize, Serialize};
+
+use crate::{entities::{combat::Health, Owner}, network::client::ServerConnection, resources::player::Stockpiles, states::{AppState, InMatch}};
+
+pub mod commands;
+pub mod fixed;
+pub mod replay;
+
+// Simulation ticks per second of game time, the same on every peer in a match
+pub const SIMULATION_HZ: u64 = 30;
+pub const SIMULATION_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / SIMULATION_HZ);
+// Ticks of checksums kept for comparing against other peers
+const CHECKSUM_HISTORY: usize = 256;
+
+// Order of work within a simulation tick, all in FixedUpdate. Physics steps afterwards in FixedPostUpdate.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimulationSet {
+    Tick,
+    Commands,
+    Logic,
+    Checksum,
+}
+
+// Set while the simulation has to wait, e.g. on commands from other peers. Ticks are skipped
+// rather than delayed, so a stalled peer doesn't rush through a backlog once it can go on.
+#[derive(Default, Resource)]
+pub struct SimulationStall {
+    pub stalled: bool,
+}
+
+// Clients of a dedicated server don't simulate at all, they mirror the server's snapshots
+pub fn simulation_running(
+    stall: Res<SimulationStall>,
+    connection: Option<Res<ServerConnection>>,
+) -> bool {
+    !stall.stalled && connection.is_none()
+}
+
+// Number of simulation ticks run this match
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Resource)]
+pub struct SimulationTick(pub u64);
+
+// Identifier handed out in spawn order, the same on every peer. Sort by it whenever iteration
+// order affects the outcome, since query order depends on archetype layout.
+#[derive(Clone, Copy, Component, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct SimId(pub u32);
+
+#[derive(Default, Resource)]
+pub struct SimIdAllocator {
+    next: u32,
+}
+
+impl SimIdAllocator {
+    pub fn next(&mut self) -> SimId {
+        let id = SimId(self.next);
+        self.next += 1;
+        id
+    }
+}
+
+// FNV-1a, stable across platforms and Rust versions unlike the std hashers
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
+// Checksums of the simulation state at the end of recent ticks
+#[derive(Default, Resource)]
+pub struct ChecksumHistory {
+    entries: VecDeque<(u64, u64)>,
+}
+
+impl ChecksumHistory {
+    pub fn push(&mut self, tick: u64, checksum: u64) {
+        if self.entries.len() == CHECKSUM_HISTORY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((tick, checksum));
+    }
+
+    pub fn get(&self, tick: u64) -> Option<u64> {
+        self.entries.iter()
+            .find(|(entry_tick, _)| *entry_tick == tick)
+            .map(|(_, checksum)| *checksum)
+    }
+
+    pub fn latest(&self) -> Option<(u64, u64)> {
+        self.entries.back().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// Physics steps inside the fixed timestep so every peer integrates with the same delta
+pub fn physics_plugins() -> PhysicsPlugins {
+    PhysicsPlugins::new(FixedPostUpdate)
+}
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Time::<Fixed>::from_duration(SIMULATION_TIMESTEP))
+            .init_resource::<SimulationTick>()
+            .init_resource::<SimIdAllocator>()
+            .init_resource::<ChecksumHistory>()
+            .init_resource::<SimulationStall>()
+            .configure_sets(FixedUpdate, (
+                SimulationSet::Tick,
+                SimulationSet::Commands,
+                SimulationSet::Logic,
+                SimulationSet::Checksum,
+            ).chain().run_if(in_state(AppState::InGame).and_then(simulation_running)))
+            .add_systems(OnExit(InMatch), reset_simulation)
+            .add_systems(FixedUpdate, advance_tick.in_set(SimulationSet::Tick))
+            .add_systems(FixedUpdate, record_checksum.in_set(SimulationSet::Checksum));
+    }
+}
+
+// Ids and ticks restart with every match, so a replayed or networked match numbers them the same
+pub fn reset_simulation(
+    mut tick: ResMut<SimulationTick>,
+    mut sim_ids: ResMut<SimIdAllocator>,
+    mut history: ResMut<ChecksumHistory>,
+    mut stall: ResMut<SimulationStall>,
+) {
+    *tick = SimulationTick::default();
+    *sim_ids = SimIdAllocator::default();
+    history.clear();
+    stall.stalled = false;
+}
+
+pub fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
+}
+
+// Transforms are quantized to fixed point first, so only real divergence changes the checksum
+pub fn record_checksum(
+    tick: Res<SimulationTick>,
+    stockpiles: Res<Stockpiles>,
+    mut history: ResMut<ChecksumHistory>,
+    q_simulated: Query<(&SimId, Option<&Owner>, &Transform, Option<&Health>)>,
+) {
+    let mut simulated: Vec<_> = q_simulated.iter().collect();
+    simulated.sort_unstable_by_key(|(sim_id, ..)| **sim_id);
+
+    let mut hasher = StateHasher::default();
+    hasher.write_u64(tick.0);
+    for (sim_id, owner, transform, health) in simulated {
+        hasher.write_u32(sim_id.0);
+        hasher.write_u8(owner.map_or(u8::MAX, |owner| owner.0));
+        hasher.write_u32(health.map_or(u32::MAX, |health| health.current));
+        for value in transform.translation.to_array().into_iter().chain(transform.rotation.to_array()) {
+            hasher.write_i32(FixedPoint::from_num(value).to_bits());
+        }
+    }
+    for (player_id, amount) in stockpiles.amounts() {
+        hasher.write_u8(player_id);
+        hasher.write_u32(amount);
+    }
+    history.push(tick.0, hasher.finish());
+}
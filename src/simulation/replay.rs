@@ -0,0 +1,223 @@
+use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    headless::Headless,
+    map::MAP_NAME,
+    resources::{player::{Player, PlayerId}, settings::GameSettings},
+    states::{match_rules::{setup_match, ActiveMatchRules}, AppState, InMatch},
+};
+
+use super::{commands::{reset_commands, CommandLog, CommandQueue, TimedCommand}, record_checksum, reset_simulation, ChecksumHistory, SimulationSet, SimulationTick};
+
+pub const REPLAY_DIRECTORY: &str = "replays";
+pub const REPLAY_ARG: &str = "--replay";
+// Bumped whenever commands or simulation rules change in a way that breaks old replays
+pub const REPLAY_VERSION: u32 = 1;
+
+// A recorded match: everything needed to simulate it again from the start
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Replay {
+    pub version: u32,
+    pub map: String,
+    pub settings: GameSettings,
+    // Player who recorded the match
+    pub player: PlayerId,
+    // Ticks simulated before the match ended
+    pub ticks: u64,
+    // Checksum of the last tick, compared against when playing back
+    pub final_checksum: Option<u64>,
+    pub commands: Vec<TimedCommand>,
+}
+
+impl Replay {
+    // The match so far, as recorded by the given player
+    pub fn record(
+        settings: &GameSettings,
+        player: PlayerId,
+        tick: &SimulationTick,
+        history: &ChecksumHistory,
+        log: &CommandLog,
+    ) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            map: MAP_NAME.to_string(),
+            settings: settings.clone(),
+            player,
+            ticks: tick.0,
+            final_checksum: history.get(tick.0),
+            commands: log.commands.clone(),
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Self, String> {
+        let replay: Replay = ron::from_str(contents).map_err(|err| err.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("replay version {} is not supported, expected {}", replay.version, REPLAY_VERSION));
+        }
+        Ok(replay)
+    }
+
+    pub fn write(&self, directory: &str, name: &str) -> Result<PathBuf, String> {
+        let contents = self.to_ron()?;
+        fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+        let path = Path::new(directory).join(format!("{}.ron", name));
+        fs::write(&path, contents).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Replay::from_ron(&contents)
+    }
+
+    // Most recently written replay in the directory
+    pub fn latest(directory: &str) -> Option<PathBuf> {
+        fs::read_dir(directory).ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+            .max_by_key(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+    }
+}
+
+// Present while a recorded match is played back instead of taking input
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub finished: bool,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayback {
+            replay,
+            finished: false,
+        }
+    }
+
+    // None unless --replay <path> was passed and the replay could be read
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let position = args.iter().position(|arg| arg == REPLAY_ARG)?;
+        let Some(path) = args.get(position + 1) else {
+            println!("Ignoring {}, expected the path of a replay file", REPLAY_ARG);
+            return None;
+        };
+        match Replay::read(path) {
+            Ok(replay) => Some(ReplayPlayback::new(replay)),
+            Err(err) => {
+                println!("Failed to read replay {}: {}", path, err);
+                None
+            },
+        }
+    }
+}
+
+#[derive(Clone, Resource)]
+pub struct ReplayConfig {
+    // Where matches are recorded to, None to not record them
+    pub directory: Option<String>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            directory: Some(REPLAY_DIRECTORY.to_string()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ReplayPlugin {
+    pub config: ReplayConfig,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(self.config.clone())
+            // Rules have to be in place before the match sets up stockpiles
+            .add_systems(OnEnter(InMatch), start_replay_playback.before(setup_match))
+            // Recorded before the simulation forgets its ticks and commands
+            .add_systems(OnExit(InMatch), finish_replay
+                .before(reset_simulation)
+                .before(reset_commands))
+            .add_systems(FixedUpdate, check_replay_end
+                .after(record_checksum)
+                .in_set(SimulationSet::Checksum)
+                .run_if(resource_exists::<ReplayPlayback>));
+    }
+}
+
+// Playback feeds the recorded commands in place of input, under the rules they were recorded with
+pub fn start_replay_playback(
+    playback: Option<Res<ReplayPlayback>>,
+    mut rules: ResMut<ActiveMatchRules>,
+    mut queue: ResMut<CommandQueue>,
+) {
+    let Some(playback) = playback else { return; };
+    let replay = &playback.replay;
+    if replay.map != MAP_NAME {
+        println!("Replay was recorded on map {}, playing it back on {}", replay.map, MAP_NAME);
+    }
+    rules.incoming = Some(replay.settings.clone());
+    queue.extend(replay.commands.iter().cloned());
+    println!("Playing back replay of {} ticks and {} commands", replay.ticks, replay.commands.len());
+}
+
+// Save the match that just ended, or stop playing back the replay that did
+pub fn finish_replay(
+    mut commands: Commands,
+    config: Res<ReplayConfig>,
+    playback: Option<Res<ReplayPlayback>>,
+    player: Res<Player>,
+    rules: Res<ActiveMatchRules>,
+    tick: Res<SimulationTick>,
+    history: Res<ChecksumHistory>,
+    log: Res<CommandLog>,
+) {
+    if playback.is_some() {
+        commands.remove_resource::<ReplayPlayback>();
+        return;
+    }
+    let Some(directory) = config.directory.as_ref() else { return; };
+    let replay = Replay::record(&rules.game, player.id, &tick, &history, &log);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    match replay.write(directory, &format!("replay-{}", timestamp)) {
+        Ok(path) => println!("Saved replay to {}", path.display()),
+        Err(err) => println!("Failed to save replay: {}", err),
+    }
+}
+
+// Once the recorded ticks have run, check the re-simulation ended up where the recording did
+pub fn check_replay_end(
+    tick: Res<SimulationTick>,
+    history: Res<ChecksumHistory>,
+    headless: Option<Res<Headless>>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if playback.finished || tick.0 < playback.replay.ticks {
+        return;
+    }
+    playback.finished = true;
+    match (playback.replay.final_checksum, history.get(playback.replay.ticks)) {
+        (Some(recorded), Some(simulated)) if recorded != simulated => {
+            println!("Replay desynced: checksum {:016x} at tick {}, recorded {:016x}", simulated, tick.0, recorded);
+        },
+        _ => println!("Replay finished after {} ticks", tick.0),
+    }
+    // Nobody is watching a headless playback, so it ends with the recording
+    if headless.is_some() {
+        next_app_state.set(AppState::GameOver);
+    }
+}
@@ -0,0 +1,102 @@
+use bevy::{asset::{RecursiveDependencyLoadState, UntypedAssetId}, prelude::*};
+use bevy_ambient_cg::ambient_cg::AmbientCGPath;
+
+use crate::{resources::materials::tile::TILES_074, ui::menu::{spawn_menu_camera, spawn_menu_root, MENU_FONT}};
+
+use super::AppState;
+
+// Assets needed by the match, loaded up front while the loading screen is shown
+#[derive(Default, Resource)]
+pub struct GameAssets {
+    pub ground_material: Handle<StandardMaterial>,
+    pub generator: Handle<Scene>,
+}
+
+#[derive(Component)]
+pub struct LoadingProgressText;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GameAssets>()
+            .add_systems(OnEnter(AppState::Loading), (
+                load_game_assets,
+                setup_loading_screen,
+            ))
+            .add_systems(Update, handle_loading_progress.run_if(in_state(AppState::Loading)));
+    }
+}
+
+pub fn load_game_assets(
+    mut commands: Commands,
+    acg_path: Res<AmbientCGPath>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let generator = asset_server.load(GltfAssetLabel::Scene(0).from_asset("models/Generator.glb"));
+    let ground_material = TILES_074.load(acg_path.clone(), asset_server, &mut materials);
+    commands.insert_resource(GameAssets {
+        ground_material,
+        generator,
+    });
+}
+
+pub fn setup_loading_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    spawn_menu_camera(&mut commands, AppState::Loading);
+    let root = spawn_menu_root(&mut commands, AppState::Loading);
+    commands.entity(root).with_children(|parent| {
+        parent.spawn((
+            LoadingProgressText,
+            TextBundle::from_section(
+                "Loading... 0%",
+                TextStyle {
+                    font: asset_server.load(MENU_FONT),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+            ),
+        ));
+    });
+}
+
+pub fn handle_loading_progress(
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    materials: Res<Assets<StandardMaterial>>,
+    mut q_progress_text: Query<&mut Text, With<LoadingProgressText>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    // AmbientCG materials are added directly rather than loaded, so track their textures instead
+    let mut pending: Vec<UntypedAssetId> = vec![game_assets.generator.id().untyped()];
+    if let Some(material) = materials.get(&game_assets.ground_material) {
+        pending.extend([
+            &material.base_color_texture,
+            &material.normal_map_texture,
+            &material.metallic_roughness_texture,
+            &material.occlusion_texture,
+            &material.depth_map,
+            &material.emissive_texture,
+        ].into_iter().flatten().map(|handle| handle.id().untyped()));
+    }
+
+    // Failed assets count as finished so a missing file can't hang the loading screen
+    let finished = pending.iter()
+        .filter(|id| matches!(
+            asset_server.recursive_dependency_load_state(**id),
+            RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed
+        ))
+        .count();
+
+    if let Ok(mut text) = q_progress_text.get_single_mut() {
+        text.sections[0].value = format!("Loading... {}%", finished * 100 / pending.len());
+    }
+
+    if finished == pending.len() {
+        next_app_state.set(AppState::InGame);
+    }
+}
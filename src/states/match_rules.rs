@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+
+use crate::{controls::{selection::{Selectable, SelectionMask}, InputMap}, entities::{structures::Structure, units::Unit, Owner}, resources::{player::{PlayerId, Stockpiles}, settings::{GameSettings, Settings, VictoryCondition, GAME_SPEED_OPTIONS}, stats::{track_spawns, MatchStats}}, simulation::SimulationSet};
+
+use super::{AppState, InMatch};
+
+// Result of the current or last match, read by the game over screen
+#[derive(Default, Resource)]
+pub struct MatchOutcome {
+    pub eliminated: Vec<PlayerId>,
+    pub winner: Option<PlayerId>,
+    // Tick a networked match desynced on, ending it without a winner
+    pub desync: Option<u64>,
+}
+
+// Rules of the match being played. The player's settings apply unless a replay or the host hands
+// over their own, which are only kept for the match and never written to the settings file.
+#[derive(Default, Resource)]
+pub struct ActiveMatchRules {
+    pub game: GameSettings,
+    // Rules the next match is played under instead of the player's settings
+    pub incoming: Option<GameSettings>,
+}
+
+pub struct MatchRulesPlugin;
+
+impl Plugin for MatchRulesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MatchOutcome>()
+            .init_resource::<ActiveMatchRules>()
+            .add_systems(OnEnter(InMatch), (setup_match, apply_game_speed).chain())
+            .add_systems(OnExit(InMatch), reset_game_speed)
+            .add_systems(Update, (
+                handle_game_speed_keys
+                    .run_if(in_state(AppState::InGame)),
+                apply_game_speed
+                    .run_if(in_state(InMatch).and_then(resource_changed::<ActiveMatchRules>)),
+            ).chain())
+            // Players are only known once their entities have been seen
+            .add_systems(FixedUpdate, check_victory
+                .after(track_spawns)
+                .in_set(SimulationSet::Logic));
+    }
+}
+
+// Number of units a player currently fields, checked against the population cap
+pub fn population(q_units: &Query<&Owner, With<Unit>>, player_id: PlayerId) -> u32 {
+    q_units.iter().filter(|owner| owner.0 == player_id).count() as u32
+}
+
+pub fn setup_match(
+    mut stockpiles: ResMut<Stockpiles>,
+    mut outcome: ResMut<MatchOutcome>,
+    mut rules: ResMut<ActiveMatchRules>,
+    settings: Res<Settings>,
+) {
+    rules.game = rules.incoming.take().unwrap_or_else(|| settings.game.clone());
+    stockpiles.reset(rules.game.starting_resources);
+    *outcome = MatchOutcome::default();
+    println!(
+        "Match started: {}x speed, {} starting resources, population cap {}, victory by {:?}",
+        rules.game.game_speed,
+        rules.game.starting_resources,
+        rules.game.population_cap,
+        rules.game.victory_condition,
+    );
+}
+
+pub fn is_defeated(
+    victory_condition: VictoryCondition,
+    player_id: PlayerId,
+    q_units: &Query<&Owner, With<Unit>>,
+    q_structures: &Query<(&Owner, &Selectable), With<Structure>>,
+) -> bool {
+    match victory_condition {
+        VictoryCondition::DestroyHq => !q_structures.iter().any(|(owner, selectable)| {
+            owner.0 == player_id && matches!(selectable.selection_mask, SelectionMask::Hq)
+        }),
+        VictoryCondition::EliminateAllUnits => population(q_units, player_id) == 0,
+    }
+}
+
+// Eliminate players that meet the defeat condition and end the match once one player is left
+pub fn check_victory(
+    rules: Res<ActiveMatchRules>,
+    stats: Res<MatchStats>,
+    mut outcome: ResMut<MatchOutcome>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    q_units: Query<&Owner, With<Unit>>,
+    q_structures: Query<(&Owner, &Selectable), With<Structure>>,
+) {
+    if outcome.winner.is_some() || stats.players.len() < 2 {
+        return;
+    }
+    for player_id in stats.players.iter() {
+        if outcome.eliminated.contains(player_id) {
+            continue;
+        }
+        if is_defeated(rules.game.victory_condition, *player_id, &q_units, &q_structures) {
+            println!("Player {} eliminated", player_id);
+            outcome.eliminated.push(*player_id);
+        }
+    }
+    let remaining: Vec<PlayerId> = stats.players.iter()
+        .filter(|player_id| !outcome.eliminated.contains(player_id))
+        .copied()
+        .collect();
+    if remaining.len() <= 1 {
+        outcome.winner = remaining.first().copied();
+        match outcome.winner {
+            Some(winner) => println!("Player {} wins", winner),
+            None => println!("Match ended in a draw"),
+        }
+        next_app_state.set(AppState::GameOver);
+    }
+}
+
+// Speeding up virtual time runs more simulation ticks per second, each still the same length
+pub fn apply_game_speed(
+    rules: Res<ActiveMatchRules>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let speed = rules.game.game_speed;
+    if virtual_time.relative_speed() != speed {
+        virtual_time.set_relative_speed(speed);
+    }
+}
+
+pub fn reset_game_speed(mut virtual_time: ResMut<Time<Virtual>>) {
+    virtual_time.set_relative_speed(1.);
+}
+
+// Step through the speed options, staying at either end. Only this match speeds up, the setting
+// is left alone.
+pub fn handle_game_speed_keys(
+    key: Res<ButtonInput<KeyCode>>,
+    mut rules: ResMut<ActiveMatchRules>,
+) {
+    let input_map = InputMap::default();
+    let step: isize = if key.just_pressed(input_map.speed_up) {
+        1
+    } else if key.just_pressed(input_map.speed_down) {
+        -1
+    } else {
+        return;
+    };
+    let current = GAME_SPEED_OPTIONS.iter()
+        .position(|speed| *speed >= rules.game.game_speed)
+        .unwrap_or(GAME_SPEED_OPTIONS.len() - 1);
+    let next = (current as isize + step).clamp(0, GAME_SPEED_OPTIONS.len() as isize - 1) as usize;
+    if GAME_SPEED_OPTIONS[next] != rules.game.game_speed {
+        rules.game.game_speed = GAME_SPEED_OPTIONS[next];
+        println!("Game speed: {}x", rules.game.game_speed);
+    }
+}
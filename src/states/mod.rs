@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+
+use crate::{headless::Headless, network::{client::ServerConnection, server::GameServer, session::NetworkSession}, simulation::replay::ReplayPlayback};
+use loading::LoadingPlugin;
+use match_rules::MatchRulesPlugin;
+
+pub mod loading;
+pub mod match_rules;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, States)]
+pub enum AppState {
+    #[default]
+    Boot,
+    MainMenu,
+    Lobby,
+    Loading,
+    InGame,
+    Paused,
+    GameOver,
+}
+
+// Active for the whole lifetime of a match, paused or not. The game world is scoped to this state
+// so it survives pausing but is despawned when returning to the menu.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct InMatch;
+
+impl ComputedStates for InMatch {
+    type SourceStates = AppState;
+
+    fn compute(sources: AppState) -> Option<Self> {
+        match sources {
+            AppState::InGame | AppState::Paused => Some(InMatch),
+            _ => None,
+        }
+    }
+}
+
+pub struct AppStatePlugin;
+
+impl Plugin for AppStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_state::<AppState>()
+            .add_computed_state::<InMatch>()
+            .enable_state_scoped_entities::<AppState>()
+            .enable_state_scoped_entities::<InMatch>()
+            .add_plugins((LoadingPlugin, MatchRulesPlugin))
+            .add_systems(Update, handle_boot.run_if(in_state(AppState::Boot)));
+    }
+}
+
+pub fn handle_boot(
+    headless: Option<Res<Headless>>,
+    playback: Option<Res<ReplayPlayback>>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    server: Option<Res<GameServer>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    // Networked games wait in the lobby for the other players, headless ones start once they joined
+    if session.is_some() || connection.is_some() || server.is_some() {
+        next_app_state.set(AppState::Lobby);
+    } else if headless.is_some() {
+        // Headless runs have no menus or assets to load, so they go straight into the match
+        next_app_state.set(AppState::InGame);
+    } else if playback.is_some() {
+        next_app_state.set(AppState::Loading);
+    } else {
+        next_app_state.set(AppState::MainMenu);
+    }
+}
@@ -1,12 +1,11 @@
 use avian3d::prelude::*;
 use core::f32;
 use bevy::{input::mouse::MouseMotion, prelude::*, render::camera::RenderTarget, window::*};
-use bevy_mod_picking::{pointer::*, prelude::*, PointerBundle};
+use bevy_mod_picking::{focus::HoverMap, pointer::*, prelude::*, PointerBundle};
 
-use crate::controls::camera::PlayerCamera;
+use crate::{controls::camera::{CameraConfig, PlayerCamera}, entities::{structures::PlacementPreview, world_objects::ResourceNode, Owner}, resources::{player::Player, settings::{CursorKind, DragMode, Settings}}, states::AppState};
 
 pub const CURSOR_POSITION_DEFAULT: Vec2 = Vec2::new(0.5, 0.5);
-pub const MOUSE_SENSITIVITY: f32 = 10.;
 
 #[derive(Clone, Copy)]
 pub struct CursorTextureIndex;
@@ -75,16 +74,62 @@ impl CursorModeChangeEvent {
 pub struct Cursor {
     pub visibility: Visibility,
     pub location: Vec2,
-    pub mode: CursorMode,
+    pub context: CursorContext,
 }
 
+// What the cursor is currently pointing at, used to pick the cursor glyph while idle
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CursorContext {
+    #[default]
+    Default,
+    Attack,
+    Gather,
+    InvalidPlacement,
+    // Direction of the window edge being scrolled towards, in screen space
+    EdgeScroll(IVec2),
+}
+
+impl CursorContext {
+    pub fn texture_index(&self) -> usize {
+        match self {
+            CursorContext::Default => CursorTextureIndex::POINTER,
+            CursorContext::Attack => CursorTextureIndex::CROSSHAIR_1,
+            CursorContext::Gather => CursorTextureIndex::POINTER_CIRCLE,
+            CursorContext::InvalidPlacement => CursorTextureIndex::POINTER_X,
+            CursorContext::EdgeScroll(direction) => match (direction.x, direction.y) {
+                (0, _) => CursorTextureIndex::RESIZE_TB,
+                (_, 0) => CursorTextureIndex::RESIZE_RL,
+                (x, y) if x == y => CursorTextureIndex::RESIZE_TL_BR,
+                _ => CursorTextureIndex::RESIZE_TR_BL,
+            },
+        }
+    }
+}
+
+// Only exists while playing, so menus and the pause screen get the OS cursor back
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, SubStates)]
+#[source(AppState = AppState::InGame)]
 pub enum CursorMode {
     CameraControl,
     #[default]
     Idle,
     Selecting,
-    _Locked,
+    Locked,
+}
+
+impl CursorMode {
+    // Transition table for cursor modes, anything not listed here is rejected
+    pub fn can_transition_to(&self, next: CursorMode) -> bool {
+        matches!(
+            (self, next),
+            (CursorMode::Idle, CursorMode::Selecting)
+                | (CursorMode::Idle, CursorMode::CameraControl)
+                | (CursorMode::Selecting, CursorMode::Idle)
+                | (CursorMode::CameraControl, CursorMode::Idle)
+                | (_, CursorMode::Locked)
+                | (CursorMode::Locked, CursorMode::Idle)
+        )
+    }
 }
 
 impl std::fmt::Display for CursorMode {
@@ -93,23 +138,37 @@ impl std::fmt::Display for CursorMode {
     }
 }
 
-pub fn add_cursor_systems(app: &mut App) {
-    app
-        .add_systems(PostStartup, setup_cursor)
-        .add_systems(Update, handle_cursor)
-        .add_systems(Update, handle_cursor_mode_event)
-        .add_systems(Update, handle_input_press);
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<CursorModeChangeEvent>()
+            .add_sub_state::<CursorMode>()
+            .add_systems(PostStartup, setup_cursor)
+            .add_systems(OnExit(AppState::InGame), release_cursor)
+            .add_systems(Update, (
+                log_cursor_mode_transitions,
+                handle_cursor,
+                handle_cursor_mode_event,
+                (
+                    update_cursor_context,
+                    update_cursor_texture,
+                ).chain().after(handle_cursor_mode_event).after(handle_cursor),
+                handle_input_press,
+            ).run_if(in_state(AppState::InGame)));
+    }
 }
 
 pub fn setup_cursor(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
     mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
     q_mouse_pointer: Query<(&mut PointerId, Entity)>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
 ) {
     let mut window = q_windows.single_mut();
-    window.cursor.visible = false;
     let cursor_position = window.size() * CURSOR_POSITION_DEFAULT;
     
     let (mouse_pointer, mouse_pointer_entity) = q_mouse_pointer.single();
@@ -128,7 +187,7 @@ pub fn setup_cursor(
         CursorBundle {
             cursor: Cursor {
                 visibility: Visibility::Visible,
-                location: CURSOR_POSITION_DEFAULT,
+                location: cursor_position,
                 ..default()
             },
             ..default()
@@ -157,12 +216,14 @@ pub fn setup_cursor(
             CursorTexture,
             ImageBundle {
                 image: texture.into(),
+                // Shown by handle_cursor once gameplay starts
+                visibility: Visibility::Hidden,
                 style: Style {
                     position_type: PositionType::Absolute,
-                    left: Val::Px(cursor_position.x),
-                    top:  Val::Px(cursor_position.y),
-                    height: Val::Px(16.),
-                    width: Val::Px(16.),
+                    left: Val::Px(cursor_position.x / ui_scale.0),
+                    top:  Val::Px(cursor_position.y / ui_scale.0),
+                    height: Val::Px(16. / ui_scale.0),
+                    width: Val::Px(16. / ui_scale.0),
                     ..default()
                 },
                 ..default()
@@ -175,6 +236,20 @@ pub fn setup_cursor(
     });
 }
 
+// Hand the cursor back to the OS when leaving gameplay for a menu
+pub fn release_cursor(
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut q_cursor_texture: Query<&mut Visibility, With<CursorTexture>>,
+) {
+    let mut window = q_windows.single_mut();
+    window.cursor.visible = true;
+    window.cursor.grab_mode = CursorGrabMode::None;
+    window.cursor.icon = CursorIcon::Default;
+    if let Ok(mut visibility) = q_cursor_texture.get_single_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
+
 // Trigger buffered input press events for mapping mouse pointer events to custom pointer
 pub fn handle_input_press(
     mouse: Res<ButtonInput<MouseButton>>,
@@ -224,6 +299,9 @@ pub fn handle_cursor(
     mut ev_cursor_change: EventWriter<CursorModeChangeEvent>,
     mouse: Res<ButtonInput<MouseButton>>,
     key: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    ui_scale: Res<UiScale>,
+    cursor_mode: Res<State<CursorMode>>,
 ) {
     let (mut window, window_entity) = q_windows.single_mut();
     let (_camera, _camera_3d) = q_camera.single_mut();
@@ -231,14 +309,20 @@ pub fn handle_cursor(
     let cursor_texture_entity = q_cursor_texture_entity.single_mut();
     let delta = time.delta_seconds();
 
+    let cursor_texture_visibility = match settings.input.cursor_kind {
+        CursorKind::Hardware => Visibility::Hidden,
+        CursorKind::Software => cursor.visibility,
+    };
+    // UI pixels are scaled by UiScale, undo it so the texture sits on the pointer at its own size
+    let cursor_size = settings.input.cursor_size / ui_scale.0;
     commands.entity(cursor_texture_entity).insert(
-        (cursor.visibility,
+        (cursor_texture_visibility,
         Style {
             position_type: PositionType::Absolute,
-            left: Val::Px(cursor.location.x),
-            top:  Val::Px(cursor.location.y),
-            height: Val::Vw(2.5),
-            width: Val::Vw(2.5),
+            left: Val::Px(cursor.location.x / ui_scale.0),
+            top:  Val::Px(cursor.location.y / ui_scale.0),
+            height: Val::Px(cursor_size),
+            width: Val::Px(cursor_size),
             ..default()
         }
     ));
@@ -251,13 +335,29 @@ pub fn handle_cursor(
         window.cursor.grab_mode = CursorGrabMode::None;
     }
 
-    if window.focused && cursor.mode == CursorMode::Idle || cursor.mode == CursorMode::Selecting { 
-        for mouse_event in ev_mouse.read() {
-            let motion = mouse_event.delta * delta;
-            cursor.location += motion * MOUSE_SENSITIVITY;
-            cursor.location = cursor.location.clamp(Vec2::ZERO, window.size());
-        }
+    let tracking = window.focused && matches!(*cursor_mode.get(), CursorMode::Idle | CursorMode::Selecting);
+    match settings.input.cursor_kind {
+        CursorKind::Hardware => {
+            if tracking {
+                if let Some(position) = window.cursor_position() {
+                    cursor.location = position;
+                }
+            } else if window.focused && *cursor_mode.get() == CursorMode::CameraControl {
+                // Pin the OS cursor while it drags the camera, other windows keep it when focus is lost
+                window.set_cursor_position(Some(cursor.location));
+            }
+        },
+        CursorKind::Software => {
+            if tracking {
+                for mouse_event in ev_mouse.read() {
+                    let motion = mouse_event.delta * delta;
+                    cursor.location += motion * settings.input.mouse_sensitivity;
+                }
+            }
+        },
     }
+    // Window size is in logical pixels, so this also keeps the cursor on screen across scale factor changes
+    cursor.location = cursor.location.clamp(Vec2::ZERO, window.size());
 
     if let Ok((_pointer_id, mut pointer_location)) = q_pointer.get_single_mut() {
         pointer_location.location = Some(Location {
@@ -272,8 +372,8 @@ pub fn handle_cursor(
         });
     }
 
-    // Handle state change
-    match cursor.mode {
+    // Request state changes, validated in handle_cursor_mode_event
+    match cursor_mode.get() {
         CursorMode::Idle => {
             if mouse.just_pressed(MouseButton::Left) {
                 ev_cursor_change.send(CursorModeChangeEvent(CursorMode::Selecting));
@@ -283,7 +383,11 @@ pub fn handle_cursor(
             }
         },
         CursorMode::CameraControl => {
-            if mouse.just_released(MouseButton::Right) {
+            let stop_dragging = match settings.accessibility.camera_drag {
+                DragMode::Hold => mouse.just_released(MouseButton::Right),
+                DragMode::Toggle => mouse.just_pressed(MouseButton::Right),
+            };
+            if stop_dragging {
                 ev_cursor_change.send(CursorModeChangeEvent(CursorMode::Idle));
             }
         },
@@ -299,27 +403,150 @@ pub fn handle_cursor(
 }
 
 pub fn handle_cursor_mode_event(
-    mut q_cursor: Query<&mut Cursor>,
-    mut q_cursor_texture: Query<&mut TextureAtlas, With<CursorTexture>>,
+    cursor_mode: Res<State<CursorMode>>,
+    mut next_cursor_mode: ResMut<NextState<CursorMode>>,
     mut ev_cursor_change: EventReader<CursorModeChangeEvent>
 ) {
-    let mut cursor = q_cursor.single_mut();
-    let mut texture_atlas = q_cursor_texture.single_mut();
+    let mut mode = *cursor_mode.get();
     for cursor_change_event in ev_cursor_change.read() {
-        match cursor_change_event.cursor_mode() {
-            CursorMode::CameraControl => {
-                texture_atlas.index = CursorTextureIndex::CROSSHAIR_5;
-            },
-            CursorMode::Idle => {
-                texture_atlas.index = CursorTextureIndex::POINTER;
-            },
-            CursorMode::_Locked => {
-                texture_atlas.index = CursorTextureIndex::POINTER_X;
-            },
-            CursorMode::Selecting => {
-                texture_atlas.index = CursorTextureIndex::CROSSHAIR_10;
-            }
+        let next = cursor_change_event.cursor_mode();
+        if next == mode {
+            continue;
+        }
+        if !mode.can_transition_to(next) {
+            println!("Rejected cursor mode transition: {} -> {}", mode, next);
+            continue;
         }
-        cursor.mode = cursor_change_event.cursor_mode();
+        mode = next;
     }
-}
\ No newline at end of file
+    if mode != *cursor_mode.get() {
+        next_cursor_mode.set(mode);
+    }
+}
+
+pub fn log_cursor_mode_transitions(
+    mut ev_transition: EventReader<StateTransitionEvent<CursorMode>>,
+) {
+    for transition in ev_transition.read() {
+        if let (Some(exited), Some(entered)) = (transition.exited, transition.entered) {
+            println!("Cursor mode: {} -> {}", exited, entered);
+        }
+    }
+}
+
+pub fn update_cursor_context(
+    hover_map: Res<HoverMap>,
+    player: Res<Player>,
+    mut q_cursor: Query<(&PointerId, &mut Cursor)>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_owner: Query<&Owner>,
+    q_resource_node: Query<(), With<ResourceNode>>,
+    q_placement_preview: Query<&PlacementPreview>,
+    settings: Res<Settings>,
+    camera_config: Res<CameraConfig>,
+) {
+    let (pointer_id, mut cursor) = q_cursor.single_mut();
+    let window = q_windows.single();
+
+    let edge_direction = if settings.accessibility.edge_scroll {
+        IVec2::new(
+            edge_sign(cursor.location.x, window.width(), camera_config.edge_scroll_margin),
+            edge_sign(cursor.location.y, window.height(), camera_config.edge_scroll_margin),
+        )
+    } else {
+        IVec2::ZERO
+    };
+
+    // Nearest hovered entity under this cursor's pointer
+    let hovered = hover_map.get(pointer_id)
+        .and_then(|hovered| {
+            hovered.iter()
+                .min_by(|(_, a), (_, b)| a.depth.total_cmp(&b.depth))
+                .map(|(entity, _)| *entity)
+        });
+
+    let context = if q_placement_preview.iter().any(|preview| !preview.valid) {
+        CursorContext::InvalidPlacement
+    } else if edge_direction != IVec2::ZERO {
+        CursorContext::EdgeScroll(edge_direction)
+    } else if let Some(entity) = hovered {
+        if q_owner.get(entity).is_ok_and(|owner| owner.0 != player.id) {
+            CursorContext::Attack
+        } else if q_resource_node.contains(entity) {
+            CursorContext::Gather
+        } else {
+            CursorContext::Default
+        }
+    } else {
+        CursorContext::Default
+    };
+
+    if cursor.context != context {
+        cursor.context = context;
+    }
+}
+
+// -1 or 1 when within the edge scroll margin of either side of the window, otherwise 0
+fn edge_sign(position: f32, size: f32, margin: f32) -> i32 {
+    if position < margin {
+        -1
+    } else if position > size - margin {
+        1
+    } else {
+        0
+    }
+}
+
+pub fn update_cursor_texture(
+    cursor_mode: Res<State<CursorMode>>,
+    q_cursor: Query<&Cursor>,
+    mut q_cursor_texture: Query<&mut TextureAtlas, With<CursorTexture>>,
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    settings: Res<Settings>,
+) {
+    let cursor = q_cursor.single();
+    let mut window = q_windows.single_mut();
+    let hardware = settings.input.cursor_kind == CursorKind::Hardware;
+    if window.cursor.visible != hardware {
+        window.cursor.visible = hardware;
+    }
+    if hardware {
+        let icon = hardware_cursor_icon(*cursor_mode.get(), cursor.context);
+        if window.cursor.icon != icon {
+            window.cursor.icon = icon;
+        }
+        return;
+    }
+
+    let mut texture_atlas = q_cursor_texture.single_mut();
+    let index = match cursor_mode.get() {
+        CursorMode::CameraControl => CursorTextureIndex::CROSSHAIR_5,
+        CursorMode::Idle => cursor.context.texture_index(),
+        CursorMode::Locked => CursorTextureIndex::POINTER_X,
+        CursorMode::Selecting => CursorTextureIndex::CROSSHAIR_10,
+    };
+    if texture_atlas.index != index {
+        texture_atlas.index = index;
+    }
+}
+
+// Closest system cursor to each software cursor glyph, custom images aren't supported for OS cursors
+fn hardware_cursor_icon(mode: CursorMode, context: CursorContext) -> CursorIcon {
+    match mode {
+        CursorMode::CameraControl => CursorIcon::Move,
+        CursorMode::Locked => CursorIcon::NotAllowed,
+        CursorMode::Selecting => CursorIcon::Crosshair,
+        CursorMode::Idle => match context {
+            CursorContext::Default => CursorIcon::Default,
+            CursorContext::Attack => CursorIcon::Crosshair,
+            CursorContext::Gather => CursorIcon::Grab,
+            CursorContext::InvalidPlacement => CursorIcon::NotAllowed,
+            CursorContext::EdgeScroll(direction) => match (direction.x, direction.y) {
+                (0, _) => CursorIcon::NsResize,
+                (_, 0) => CursorIcon::EwResize,
+                (x, y) if x == y => CursorIcon::NwseResize,
+                _ => CursorIcon::NeswResize,
+            },
+        },
+    }
+}
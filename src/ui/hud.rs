@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+
+use crate::{controls::bookmarks::LastAlert, entities::{units::Unit, Owner}, network::{client::ServerConnection, session::NetworkSession}, resources::player::{Player, Stockpiles}, states::{match_rules::{population, ActiveMatchRules}, InMatch}};
+
+use super::menu::MENU_FONT;
+
+// Seconds the simulation can wait on the other player before it is mentioned, short waits are routine
+const STALL_NOTICE_DELAY: f64 = 0.5;
+// Seconds an alert stays on the HUD
+const ALERT_NOTICE_DURATION: f64 = 4.;
+
+#[derive(Component)]
+pub struct HudText;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(InMatch), setup_hud)
+            .add_systems(Update, update_hud.run_if(in_state(InMatch)));
+    }
+}
+
+pub fn setup_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.spawn((
+        StateScoped(InMatch),
+        HudText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(MENU_FONT),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+    ));
+}
+
+pub fn update_hud(
+    time: Res<Time<Real>>,
+    player: Res<Player>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    stockpiles: Res<Stockpiles>,
+    rules: Res<ActiveMatchRules>,
+    last_alert: Res<LastAlert>,
+    q_units: Query<&Owner, With<Unit>>,
+    mut q_hud: Query<&mut Text, With<HudText>>,
+) {
+    let Ok(mut text) = q_hud.get_single_mut() else { return; };
+    let mut hud = format!(
+        "Resources: {}   Population: {}/{}   Speed: {}x",
+        stockpiles.get(player.id),
+        population(&q_units, player.id),
+        rules.game.population_cap,
+        rules.game.game_speed,
+    );
+    if let Some((kind, _)) = last_alert.alert {
+        if time.elapsed_seconds_f64() - last_alert.raised_at < ALERT_NOTICE_DURATION {
+            hud.push_str(&format!("\n{}", kind.notice()));
+        }
+    }
+    if let Some(session) = session {
+        if session.peer_dropped() {
+            hud.push_str("\nOpponent disconnected");
+        } else if session.stalled_since.is_some_and(|since| time.elapsed_seconds_f64() - since > STALL_NOTICE_DELAY) {
+            hud.push_str("\nWaiting for opponent...");
+        } else if let Some(round_trip) = session.round_trip_ms() {
+            hud.push_str(&format!("\nPing: {} ms", round_trip));
+        }
+    }
+    if connection.is_some_and(|connection| connection.lost) {
+        hud.push_str("\nConnection to server lost");
+    }
+    text.sections[0].value = hud;
+}
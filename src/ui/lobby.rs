@@ -0,0 +1,189 @@
+use bevy::{input::{keyboard::{Key, KeyboardInput}, ButtonState}, prelude::*};
+
+use crate::{network::{client::ServerConnection, session::{NetworkRole, NetworkSession}}, resources::settings::Settings, states::AppState};
+
+use super::{menu::{spawn_menu_button, spawn_menu_camera, spawn_menu_root, spawn_menu_title, MenuAction, MENU_FONT}, settings_menu::{spawn_dropdown, ChoiceSetting}};
+
+const STATUS_FONT_SIZE: f32 = 22.;
+// Longest host address that can be typed in, enough for IPv6 with a port
+const ADDRESS_MAX_LENGTH: usize = 47;
+
+// Kind of match the lobby sets up, picked from the main menu
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Resource)]
+pub enum LobbyMode {
+    #[default]
+    Local,
+    Host,
+    Join,
+    // Joining a dedicated server
+    Server,
+}
+
+#[derive(Component)]
+pub struct LobbyStatusText;
+
+#[derive(Component)]
+pub struct JoinAddressText;
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LobbyMode>()
+            .add_systems(OnEnter(AppState::Lobby), setup_lobby)
+            .add_systems(Update, (
+                handle_address_input,
+                update_lobby_status,
+            ).run_if(in_state(AppState::Lobby)));
+    }
+}
+
+fn status_style(font: Handle<Font>) -> TextStyle {
+    TextStyle {
+        font,
+        font_size: STATUS_FONT_SIZE,
+        color: Color::WHITE,
+    }
+}
+
+// Pre-match screen for picking the rules of the next match, or for joining someone else's
+pub fn setup_lobby(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    mut lobby_mode: ResMut<LobbyMode>,
+) {
+    // Connections opened from the command line skip the menu that would have picked the mode
+    if let Some(session) = session {
+        *lobby_mode = match session.role {
+            NetworkRole::Host => LobbyMode::Host,
+            NetworkRole::Client => LobbyMode::Join,
+        };
+    } else if connection.is_some() {
+        *lobby_mode = LobbyMode::Server;
+    }
+    let font = asset_server.load(MENU_FONT);
+    spawn_menu_camera(&mut commands, AppState::Lobby);
+    let root = spawn_menu_root(&mut commands, AppState::Lobby);
+    commands.entity(root).with_children(|parent| {
+        match *lobby_mode {
+            LobbyMode::Local | LobbyMode::Host => {
+                let title = if *lobby_mode == LobbyMode::Host { "Host LAN Game" } else { "New Game" };
+                spawn_menu_title(parent, font.clone(), title);
+                for setting in ChoiceSetting::LOBBY {
+                    spawn_dropdown(parent, font.clone(), setting, &settings);
+                }
+                if *lobby_mode == LobbyMode::Local {
+                    for setting in ChoiceSetting::LOCAL_LOBBY {
+                        spawn_dropdown(parent, font.clone(), setting, &settings);
+                    }
+                }
+                if *lobby_mode == LobbyMode::Host {
+                    for setting in ChoiceSetting::NETWORK_LOBBY {
+                        spawn_dropdown(parent, font.clone(), setting, &settings);
+                    }
+                    parent.spawn((LobbyStatusText, TextBundle::from_section("", status_style(font.clone()))));
+                }
+                spawn_menu_button(parent, font.clone(), "Start", MenuAction::StartMatch);
+            },
+            LobbyMode::Join | LobbyMode::Server => {
+                let title = if *lobby_mode == LobbyMode::Server { "Join Dedicated Server" } else { "Join LAN Game" };
+                spawn_menu_title(parent, font.clone(), title);
+                parent.spawn((
+                    JoinAddressText,
+                    TextBundle::from_section(format!("Host: {}", settings.network.join_address), status_style(font.clone())),
+                ));
+                parent.spawn((LobbyStatusText, TextBundle::from_section("", status_style(font.clone()))));
+                spawn_menu_button(parent, font.clone(), "Connect", MenuAction::Connect);
+            },
+        }
+        spawn_menu_button(parent, font.clone(), "Back", MenuAction::ReturnToMenu);
+    });
+}
+
+// The host address is typed straight into the join screen until connecting
+pub fn handle_address_input(
+    lobby_mode: Res<LobbyMode>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    mut settings: ResMut<Settings>,
+    mut ev_keyboard: EventReader<KeyboardInput>,
+    mut q_address: Query<&mut Text, With<JoinAddressText>>,
+) {
+    let joining = matches!(*lobby_mode, LobbyMode::Join | LobbyMode::Server);
+    let connected = session.is_some_and(|session| session.is_connected())
+        || connection.is_some_and(|connection| connection.is_connected());
+    if !joining || connected {
+        ev_keyboard.clear();
+        return;
+    }
+    let mut address = settings.network.join_address.clone();
+    for event in ev_keyboard.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Backspace => {
+                address.pop();
+            },
+            Key::Character(characters) => {
+                for character in characters.chars() {
+                    if address.len() < ADDRESS_MAX_LENGTH && (character.is_ascii_alphanumeric() || ".:[]".contains(character)) {
+                        address.push(character);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    if address == settings.network.join_address {
+        return;
+    }
+    settings.network.join_address = address;
+    for mut text in q_address.iter_mut() {
+        text.sections[0].value = format!("Host: {}", settings.network.join_address);
+    }
+}
+
+pub fn update_lobby_status(
+    settings: Res<Settings>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    mut q_status: Query<&mut Text, With<LobbyStatusText>>,
+) {
+    let Ok(mut text) = q_status.get_single_mut() else { return; };
+    if let Some(connection) = connection {
+        let status = match (&connection.rejected, connection.lobby) {
+            (Some(reason), _) => format!("Rejected: {}", reason),
+            _ if connection.lost => "Lost connection to the server".to_string(),
+            (None, Some((players, required))) => format!("Waiting for players ({}/{})", players, required),
+            _ if connection.accepted => "Connected, waiting for the match to start".to_string(),
+            _ => format!("Connecting to {}...", connection.server),
+        };
+        if text.sections[0].value != status {
+            text.sections[0].value = status;
+        }
+        return;
+    }
+    let ping = session.as_ref()
+        .and_then(|session| session.round_trip_ms())
+        .map_or(String::new(), |round_trip| format!("   Ping: {} ms", round_trip));
+    let status = match session.as_ref() {
+        None => format!("Type the host's address and connect, port {} by default", settings.network.port),
+        Some(session) => match (session.role, session.peer.as_ref()) {
+            (NetworkRole::Host, None) => format!("Hosting on port {}, waiting for an opponent", session.local_port().unwrap_or(settings.network.port)),
+            (NetworkRole::Host, Some(peer)) => format!("Opponent joined from {}{}", peer.address, ping),
+            (NetworkRole::Client, _) if session.rejected.is_some() => format!("Rejected: {}", session.rejected.as_deref().unwrap_or_default()),
+            (NetworkRole::Client, Some(peer)) if peer.dropped => "Host left".to_string(),
+            (NetworkRole::Client, Some(peer)) if peer.connected => format!("Connected, waiting for the host to start{}", ping),
+            (NetworkRole::Client, Some(peer)) => format!("Connecting to {}...", peer.address),
+            (NetworkRole::Client, None) => String::new(),
+        },
+    };
+    if text.sections[0].value != status {
+        text.sections[0].value = status;
+    }
+}
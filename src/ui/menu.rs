@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+
+use crate::{network::{client::ServerConnection, session::NetworkSession}, resources::{player::Player, save::SaveGameEvent, settings::Settings, stats::MatchStats}, simulation::replay::{Replay, ReplayPlayback, REPLAY_DIRECTORY}, states::{match_rules::MatchOutcome, AppState}};
+
+use super::{lobby::LobbyMode, pause::PauseMenu, summary::{outcome_title, spawn_match_summary}};
+
+pub const MENU_FONT: &str = "fonts/Roboto/Roboto-Bold.ttf";
+
+pub const BUTTON_COLOR: Color = Color::srgb(0.15, 0.15, 0.15);
+pub const BUTTON_HOVER_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+pub const BUTTON_PRESSED_COLOR: Color = Color::srgb(0.35, 0.55, 0.35);
+const MENU_BACKGROUND_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.75);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    Back,
+    Connect,
+    HostGame,
+    JoinGame,
+    JoinServer,
+    NewGame,
+    OpenSettings,
+    Quit,
+    QuitToDesktop,
+    Resume,
+    ReturnToMenu,
+    SaveGame,
+    StartMatch,
+    WatchReplay,
+}
+
+#[derive(Component)]
+pub struct MenuButton(pub MenuAction);
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::MainMenu), setup_main_menu)
+            .add_systems(OnEnter(AppState::GameOver), setup_game_over_menu)
+            .add_systems(Update, (
+                handle_menu_button_color,
+                handle_menu_button_action,
+            ));
+    }
+}
+
+// UI needs a camera to render to whenever the game camera doesn't exist
+pub fn spawn_menu_camera<S: States>(commands: &mut Commands, state: S) {
+    commands.spawn((
+        StateScoped(state),
+        Camera2dBundle::default(),
+    ));
+}
+
+// Full screen column that centers its children, despawned when leaving the given state
+pub fn spawn_menu_root<S: States>(commands: &mut Commands, state: S) -> Entity {
+    commands.spawn((
+        StateScoped(state),
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            background_color: MENU_BACKGROUND_COLOR.into(),
+            ..default()
+        },
+    )).id()
+}
+
+pub fn spawn_menu_title(parent: &mut ChildBuilder, font: Handle<Font>, title: &str) {
+    parent.spawn(TextBundle::from_section(
+        title,
+        TextStyle {
+            font,
+            font_size: 48.0,
+            color: Color::WHITE,
+        },
+    ));
+}
+
+pub fn spawn_menu_button(parent: &mut ChildBuilder, font: Handle<Font>, label: &str, action: MenuAction) {
+    parent.spawn((
+        MenuButton(action),
+        ButtonBundle {
+            style: Style {
+                width: Val::Px(260.0),
+                height: Val::Px(48.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: BUTTON_COLOR.into(),
+            ..default()
+        },
+    )).with_children(|button| {
+        button.spawn(TextBundle::from_section(
+            label,
+            TextStyle {
+                font,
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        ));
+    });
+}
+
+pub fn setup_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(MENU_FONT);
+    spawn_menu_camera(&mut commands, AppState::MainMenu);
+    let root = spawn_menu_root(&mut commands, AppState::MainMenu);
+    commands.entity(root).with_children(|parent| {
+        spawn_menu_title(parent, font.clone(), "RTS");
+        spawn_menu_button(parent, font.clone(), "New Game", MenuAction::NewGame);
+        spawn_menu_button(parent, font.clone(), "Host LAN Game", MenuAction::HostGame);
+        spawn_menu_button(parent, font.clone(), "Join LAN Game", MenuAction::JoinGame);
+        spawn_menu_button(parent, font.clone(), "Join Dedicated Server", MenuAction::JoinServer);
+        spawn_menu_button(parent, font.clone(), "Watch Last Replay", MenuAction::WatchReplay);
+        spawn_menu_button(parent, font.clone(), "Quit", MenuAction::Quit);
+    });
+}
+
+pub fn setup_game_over_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    player: Res<Player>,
+    settings: Res<Settings>,
+    stats: Res<MatchStats>,
+    outcome: Res<MatchOutcome>,
+) {
+    let font = asset_server.load(MENU_FONT);
+    spawn_menu_camera(&mut commands, AppState::GameOver);
+    let root = spawn_menu_root(&mut commands, AppState::GameOver);
+    commands.entity(root).with_children(|parent| {
+        spawn_menu_title(parent, font.clone(), outcome_title(&outcome, player.id));
+        spawn_match_summary(parent, font.clone(), &stats, &settings, player.id);
+        spawn_menu_button(parent, font.clone(), "Main Menu", MenuAction::ReturnToMenu);
+    });
+}
+
+pub fn handle_menu_button_color(
+    mut q_buttons: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, mut background_color) in q_buttons.iter_mut() {
+        *background_color = match interaction {
+            Interaction::Pressed => BUTTON_PRESSED_COLOR,
+            Interaction::Hovered => BUTTON_HOVER_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        }.into();
+    }
+}
+
+pub fn handle_menu_button_action(
+    mut commands: Commands,
+    q_buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    settings: Res<Settings>,
+    session: Option<Res<NetworkSession>>,
+    connection: Option<Res<ServerConnection>>,
+    mut lobby_mode: ResMut<LobbyMode>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_pause_menu: ResMut<NextState<PauseMenu>>,
+    mut app_exit_events: EventWriter<AppExit>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+) {
+    for (interaction, menu_button) in q_buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match menu_button.0 {
+            MenuAction::Back => next_pause_menu.set(PauseMenu::Main),
+            MenuAction::Connect => {
+                // Connecting again replaces an attempt that hasn't been answered, e.g. at a mistyped address
+                if session.as_ref().is_some_and(|session| session.is_connected())
+                    || connection.as_ref().is_some_and(|connection| connection.is_connected()) {
+                    continue;
+                }
+                let address = match settings.network.join_address.parse() {
+                    Ok(address) => address,
+                    Err(err) => {
+                        println!("Invalid host address {}: {}", settings.network.join_address, err);
+                        continue;
+                    },
+                };
+                let result = if *lobby_mode == LobbyMode::Server {
+                    ServerConnection::connect(address).map(|connection| commands.insert_resource(connection))
+                } else {
+                    NetworkSession::join(address).map(|session| commands.insert_resource(session))
+                };
+                if let Err(err) = result {
+                    println!("Failed to join {}: {}", address, err);
+                }
+            },
+            MenuAction::HostGame => match NetworkSession::host(settings.network.port) {
+                Ok(session) => {
+                    commands.insert_resource(session);
+                    *lobby_mode = LobbyMode::Host;
+                    next_app_state.set(AppState::Lobby);
+                },
+                Err(err) => println!("Failed to host on port {}: {}", settings.network.port, err),
+            },
+            MenuAction::JoinGame => {
+                *lobby_mode = LobbyMode::Join;
+                next_app_state.set(AppState::Lobby);
+            },
+            MenuAction::JoinServer => {
+                *lobby_mode = LobbyMode::Server;
+                next_app_state.set(AppState::Lobby);
+            },
+            MenuAction::NewGame => {
+                *lobby_mode = LobbyMode::Local;
+                next_app_state.set(AppState::Lobby);
+            },
+            MenuAction::OpenSettings => next_pause_menu.set(PauseMenu::Settings),
+            MenuAction::Quit => {
+                app_exit_events.send(AppExit::Success);
+            },
+            MenuAction::QuitToDesktop => next_pause_menu.set(PauseMenu::ConfirmQuit),
+            MenuAction::Resume => next_app_state.set(AppState::InGame),
+            MenuAction::ReturnToMenu => next_app_state.set(AppState::MainMenu),
+            MenuAction::SaveGame => {
+                ev_save.send(SaveGameEvent);
+            },
+            MenuAction::StartMatch => {
+                // The client is sent Start once the host is in the match
+                if session.as_ref().is_some_and(|session| !session.is_connected()) {
+                    println!("Waiting for an opponent to join");
+                    continue;
+                }
+                next_app_state.set(AppState::Loading);
+            },
+            MenuAction::WatchReplay => {
+                let Some(path) = Replay::latest(REPLAY_DIRECTORY) else {
+                    println!("No replays found in {}", REPLAY_DIRECTORY);
+                    continue;
+                };
+                match Replay::read(&path) {
+                    Ok(replay) => {
+                        commands.insert_resource(ReplayPlayback::new(replay));
+                        next_app_state.set(AppState::Loading);
+                    },
+                    Err(err) => println!("Failed to read replay {}: {}", path.display(), err),
+                }
+            },
+        }
+    }
+}
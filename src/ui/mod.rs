@@ -1 +1,8 @@
-pub mod cursor;
\ No newline at end of file
+pub mod cursor;
+pub mod hud;
+pub mod lobby;
+pub mod menu;
+pub mod pause;
+pub mod replay_viewer;
+pub mod settings_menu;
+pub mod summary;
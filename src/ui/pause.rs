@@ -0,0 +1,90 @@
+use avian3d::prelude::{Physics, PhysicsTime};
+use bevy::prelude::*;
+
+use crate::{resources::settings::Settings, states::AppState};
+
+use super::{menu::{spawn_menu_button, spawn_menu_root, spawn_menu_title, MenuAction, MENU_FONT}, settings_menu::spawn_settings_columns};
+
+// Page of the pause overlay currently shown
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, SubStates)]
+#[source(AppState = AppState::Paused)]
+pub enum PauseMenu {
+    #[default]
+    Main,
+    Settings,
+    ConfirmQuit,
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_sub_state::<PauseMenu>()
+            .enable_state_scoped_entities::<PauseMenu>()
+            .add_systems(OnEnter(AppState::Paused), pause_time)
+            .add_systems(OnExit(AppState::Paused), resume_time)
+            .add_systems(OnEnter(PauseMenu::Main), setup_pause_menu)
+            .add_systems(OnEnter(PauseMenu::Settings), setup_pause_settings_menu)
+            .add_systems(OnEnter(PauseMenu::ConfirmQuit), setup_confirm_quit_menu);
+    }
+}
+
+pub fn pause_time(
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    virtual_time.pause();
+    physics_time.pause();
+}
+
+pub fn resume_time(
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    virtual_time.unpause();
+    physics_time.unpause();
+}
+
+pub fn setup_pause_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(MENU_FONT);
+    let root = spawn_menu_root(&mut commands, PauseMenu::Main);
+    commands.entity(root).with_children(|parent| {
+        spawn_menu_title(parent, font.clone(), "Paused");
+        spawn_menu_button(parent, font.clone(), "Resume", MenuAction::Resume);
+        spawn_menu_button(parent, font.clone(), "Settings", MenuAction::OpenSettings);
+        spawn_menu_button(parent, font.clone(), "Save", MenuAction::SaveGame);
+        spawn_menu_button(parent, font.clone(), "Quit to Menu", MenuAction::ReturnToMenu);
+        spawn_menu_button(parent, font.clone(), "Quit to Desktop", MenuAction::QuitToDesktop);
+    });
+}
+
+pub fn setup_pause_settings_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) {
+    let font = asset_server.load(MENU_FONT);
+    let root = spawn_menu_root(&mut commands, PauseMenu::Settings);
+    commands.entity(root).with_children(|parent| {
+        spawn_menu_title(parent, font.clone(), "Settings");
+        spawn_settings_columns(parent, font.clone(), &settings);
+        spawn_menu_button(parent, font.clone(), "Back", MenuAction::Back);
+    });
+}
+
+pub fn setup_confirm_quit_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(MENU_FONT);
+    let root = spawn_menu_root(&mut commands, PauseMenu::ConfirmQuit);
+    commands.entity(root).with_children(|parent| {
+        spawn_menu_title(parent, font.clone(), "Quit to desktop?");
+        spawn_menu_button(parent, font.clone(), "Quit", MenuAction::Quit);
+        spawn_menu_button(parent, font.clone(), "Cancel", MenuAction::Back);
+    });
+}
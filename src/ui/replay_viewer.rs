@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+
+use crate::{
+    controls::{selection::Selected, InputMap},
+    entities::Owner,
+    resources::player::PlayerId,
+    simulation::{commands::{CommandLog, PlayerCommand}, replay::ReplayPlayback, SimId, SimulationTick},
+    states::{AppState, InMatch},
+};
+
+use super::menu::MENU_FONT;
+
+// Players whose vision can be toggled from the keyboard
+pub const REPLAY_VISION_SLOTS: usize = 4;
+
+// Players whose units and structures are hidden while watching a replay
+#[derive(Default, Resource)]
+pub struct ReplayVision {
+    pub hidden: Vec<PlayerId>,
+}
+
+#[derive(Component)]
+pub struct ReplayText;
+
+pub struct ReplayViewerPlugin;
+
+impl Plugin for ReplayViewerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ReplayVision>()
+            .add_systems(OnEnter(InMatch), setup_replay_viewer.run_if(resource_exists::<ReplayPlayback>))
+            .add_systems(Update, (
+                handle_replay_vision_keys
+                    .run_if(in_state(AppState::InGame)),
+                apply_replay_vision,
+                show_replay_selection,
+                update_replay_text,
+            ).chain().run_if(in_state(InMatch).and_then(resource_exists::<ReplayPlayback>)));
+    }
+}
+
+pub fn setup_replay_viewer(
+    mut commands: Commands,
+    mut vision: ResMut<ReplayVision>,
+    asset_server: Res<AssetServer>,
+) {
+    *vision = ReplayVision::default();
+    commands.spawn((
+        StateScoped(InMatch),
+        ReplayText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(MENU_FONT),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+    ));
+}
+
+// Number keys toggle each player's vision in slot order
+pub fn handle_replay_vision_keys(
+    key: Res<ButtonInput<KeyCode>>,
+    mut vision: ResMut<ReplayVision>,
+) {
+    let input_map = InputMap::default();
+    for (slot, vision_key) in input_map.replay_vision.iter().enumerate() {
+        if !key.just_pressed(*vision_key) {
+            continue;
+        }
+        let player_id = slot as PlayerId;
+        if let Some(position) = vision.hidden.iter().position(|hidden| *hidden == player_id) {
+            vision.hidden.remove(position);
+            println!("Showing player {}", player_id);
+        } else {
+            vision.hidden.push(player_id);
+            println!("Hiding player {}", player_id);
+        }
+    }
+}
+
+pub fn apply_replay_vision(
+    vision: Res<ReplayVision>,
+    mut q_owned: Query<(&Owner, &mut Visibility)>,
+) {
+    for (owner, mut visibility) in q_owned.iter_mut() {
+        let target = if vision.hidden.contains(&owner.0) {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+}
+
+// Mirror the recording player's selection as it was recorded
+pub fn show_replay_selection(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    log: Res<CommandLog>,
+    mut seen: Local<usize>,
+    q_simulated: Query<(Entity, &SimId)>,
+    q_selected: Query<Entity, With<Selected>>,
+) {
+    // The log starts over with every match
+    if *seen > log.commands.len() {
+        *seen = 0;
+    }
+    let latest_selection = log.commands[*seen..].iter()
+        .filter(|timed| timed.player == playback.replay.player)
+        .filter_map(|timed| match &timed.command {
+            PlayerCommand::Select { units } => Some(units),
+            _ => None,
+        })
+        .last();
+    *seen = log.commands.len();
+    let Some(units) = latest_selection else { return; };
+
+    for entity in q_selected.iter() {
+        commands.entity(entity).remove::<Selected>();
+    }
+    for (entity, sim_id) in q_simulated.iter() {
+        if units.contains(sim_id) {
+            commands.entity(entity).insert(Selected);
+        }
+    }
+}
+
+pub fn update_replay_text(
+    playback: Res<ReplayPlayback>,
+    tick: Res<SimulationTick>,
+    vision: Res<ReplayVision>,
+    mut q_text: Query<&mut Text, With<ReplayText>>,
+) {
+    let Ok(mut text) = q_text.get_single_mut() else { return; };
+    let mut hidden = vision.hidden.clone();
+    hidden.sort_unstable();
+    let hidden = if hidden.is_empty() {
+        "none".to_string()
+    } else {
+        hidden.iter().map(|player_id| player_id.to_string()).collect::<Vec<_>>().join(", ")
+    };
+    text.sections[0].value = format!(
+        "Replay   Tick: {}/{}   Hidden players: {}",
+        tick.0.min(playback.replay.ticks),
+        playback.replay.ticks,
+        hidden,
+    );
+}
@@ -0,0 +1,616 @@
+use bevy::{pbr::ShadowFilteringMethod, prelude::*, ui::RelativeCursorPosition, window::WindowMode};
+
+use crate::{resources::settings::{AiDifficulty, DragMode, Settings, TeamPalette, VictoryCondition, GAME_SPEED_OPTIONS, INPUT_DELAY_OPTIONS, POPULATION_CAP_OPTIONS, STARTING_RESOURCES_OPTIONS}, states::AppState};
+
+use super::{menu::{BUTTON_COLOR, BUTTON_PRESSED_COLOR}, pause::PauseMenu};
+
+const ROW_WIDTH: f32 = 480.;
+const WIDGET_WIDTH: f32 = 220.;
+const WIDGET_HEIGHT: f32 = 32.;
+const SLIDER_TRACK_HEIGHT: f32 = 12.;
+const LABEL_FONT_SIZE: f32 = 20.;
+// Discrete steps so the page doesn't rescale under the cursor mid-drag
+const UI_SCALE_OPTIONS: [f32; 5] = [0.75, 1., 1.25, 1.5, 2.];
+
+// Settings edited by dragging along a track
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SliderSetting {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    VoiceVolume,
+    MouseSensitivity,
+    ScrollSpeed,
+    ZoomSensitivity,
+}
+
+impl SliderSetting {
+    pub const ALL: [SliderSetting; 7] = [
+        SliderSetting::MasterVolume,
+        SliderSetting::MusicVolume,
+        SliderSetting::SfxVolume,
+        SliderSetting::VoiceVolume,
+        SliderSetting::MouseSensitivity,
+        SliderSetting::ScrollSpeed,
+        SliderSetting::ZoomSensitivity,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SliderSetting::MasterVolume => "Master Volume",
+            SliderSetting::MusicVolume => "Music Volume",
+            SliderSetting::SfxVolume => "Effects Volume",
+            SliderSetting::VoiceVolume => "Voice Volume",
+            SliderSetting::MouseSensitivity => "Mouse Sensitivity",
+            SliderSetting::ScrollSpeed => "Camera Scroll Speed",
+            SliderSetting::ZoomSensitivity => "Zoom Sensitivity",
+        }
+    }
+
+    pub fn range(&self) -> (f32, f32) {
+        match self {
+            SliderSetting::MasterVolume
+            | SliderSetting::MusicVolume
+            | SliderSetting::SfxVolume
+            | SliderSetting::VoiceVolume => (0., 1.),
+            SliderSetting::MouseSensitivity => (1., 30.),
+            SliderSetting::ScrollSpeed => (10., 150.),
+            SliderSetting::ZoomSensitivity => (0.1, 2.),
+        }
+    }
+
+    pub fn get(&self, settings: &Settings) -> f32 {
+        match self {
+            SliderSetting::MasterVolume => settings.audio.master_volume,
+            SliderSetting::MusicVolume => settings.audio.music_volume,
+            SliderSetting::SfxVolume => settings.audio.sfx_volume,
+            SliderSetting::VoiceVolume => settings.audio.voice_volume,
+            SliderSetting::MouseSensitivity => settings.input.mouse_sensitivity,
+            SliderSetting::ScrollSpeed => settings.input.scroll_speed,
+            SliderSetting::ZoomSensitivity => settings.input.zoom_sensitivity,
+        }
+    }
+
+    pub fn set(&self, settings: &mut Settings, value: f32) {
+        let field = match self {
+            SliderSetting::MasterVolume => &mut settings.audio.master_volume,
+            SliderSetting::MusicVolume => &mut settings.audio.music_volume,
+            SliderSetting::SfxVolume => &mut settings.audio.sfx_volume,
+            SliderSetting::VoiceVolume => &mut settings.audio.voice_volume,
+            SliderSetting::MouseSensitivity => &mut settings.input.mouse_sensitivity,
+            SliderSetting::ScrollSpeed => &mut settings.input.scroll_speed,
+            SliderSetting::ZoomSensitivity => &mut settings.input.zoom_sensitivity,
+        };
+        *field = value;
+    }
+
+    // Position of the current value along the track, from 0 to 1
+    pub fn fraction(&self, settings: &Settings) -> f32 {
+        let (min, max) = self.range();
+        ((self.get(settings) - min) / (max - min)).clamp(0., 1.)
+    }
+
+    pub fn format(&self, settings: &Settings) -> String {
+        let value = self.get(settings);
+        match self {
+            SliderSetting::MasterVolume
+            | SliderSetting::MusicVolume
+            | SliderSetting::SfxVolume
+            | SliderSetting::VoiceVolume => format!("{:.0}%", value * 100.),
+            _ => format!("{:.1}", value),
+        }
+    }
+}
+
+// Settings picked from a fixed list of options
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChoiceSetting {
+    AntiAliasing,
+    ShadowFiltering,
+    WindowMode,
+    TeamPalette,
+    UiScale,
+    HighContrastSelection,
+    EdgeScroll,
+    CameraDrag,
+    ReducedMotion,
+    GameSpeed,
+    StartingResources,
+    PopulationCap,
+    VictoryCondition,
+    InputDelay,
+    Opponent,
+}
+
+impl ChoiceSetting {
+    // Shown on the settings page
+    pub const OPTIONS_PAGE: [ChoiceSetting; 9] = [
+        ChoiceSetting::AntiAliasing,
+        ChoiceSetting::ShadowFiltering,
+        ChoiceSetting::WindowMode,
+        ChoiceSetting::TeamPalette,
+        ChoiceSetting::UiScale,
+        ChoiceSetting::HighContrastSelection,
+        ChoiceSetting::EdgeScroll,
+        ChoiceSetting::CameraDrag,
+        ChoiceSetting::ReducedMotion,
+    ];
+
+    // Match rules shown in the lobby
+    pub const LOBBY: [ChoiceSetting; 4] = [
+        ChoiceSetting::GameSpeed,
+        ChoiceSetting::StartingResources,
+        ChoiceSetting::PopulationCap,
+        ChoiceSetting::VictoryCondition,
+    ];
+
+    // Extra rules shown for matches on this machine
+    pub const LOCAL_LOBBY: [ChoiceSetting; 1] = [
+        ChoiceSetting::Opponent,
+    ];
+
+    // Extra rules shown when hosting a LAN game
+    pub const NETWORK_LOBBY: [ChoiceSetting; 1] = [
+        ChoiceSetting::InputDelay,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChoiceSetting::AntiAliasing => "Anti-Aliasing",
+            ChoiceSetting::ShadowFiltering => "Shadow Filtering",
+            ChoiceSetting::WindowMode => "Window Mode",
+            ChoiceSetting::TeamPalette => "Team Colors",
+            ChoiceSetting::UiScale => "UI Scale",
+            ChoiceSetting::HighContrastSelection => "High Contrast Selection",
+            ChoiceSetting::EdgeScroll => "Edge Scrolling",
+            ChoiceSetting::CameraDrag => "Camera Drag",
+            ChoiceSetting::ReducedMotion => "Reduced Motion",
+            ChoiceSetting::GameSpeed => "Game Speed",
+            ChoiceSetting::StartingResources => "Starting Resources",
+            ChoiceSetting::PopulationCap => "Population Cap",
+            ChoiceSetting::VictoryCondition => "Victory Condition",
+            ChoiceSetting::InputDelay => "Input Delay",
+            ChoiceSetting::Opponent => "Opponent",
+        }
+    }
+
+    pub fn options(&self) -> &'static [&'static str] {
+        match self {
+            ChoiceSetting::AntiAliasing => &["Off", "MSAA 2x", "MSAA 4x", "MSAA 8x"],
+            ChoiceSetting::ShadowFiltering => &["Hardware 2x2", "Gaussian", "Temporal"],
+            ChoiceSetting::WindowMode => &["Windowed", "Borderless", "Fullscreen"],
+            ChoiceSetting::TeamPalette => &["Standard", "Deuteranopia", "Tritanopia"],
+            ChoiceSetting::UiScale => &["75%", "100%", "125%", "150%", "200%"],
+            ChoiceSetting::CameraDrag => &["Hold", "Toggle"],
+            ChoiceSetting::GameSpeed => &["0.5x", "0.75x", "1x", "1.5x", "2x", "3x"],
+            ChoiceSetting::StartingResources => &["500", "1000", "2500", "5000"],
+            ChoiceSetting::PopulationCap => &["50", "100", "150", "200"],
+            ChoiceSetting::VictoryCondition => &["Destroy HQ", "Eliminate All Units"],
+            ChoiceSetting::InputDelay => &["100 ms", "200 ms", "300 ms", "400 ms", "600 ms"],
+            ChoiceSetting::Opponent => &["None", "Easy AI", "Normal AI", "Hard AI"],
+            ChoiceSetting::HighContrastSelection
+            | ChoiceSetting::EdgeScroll
+            | ChoiceSetting::ReducedMotion => &["Off", "On"],
+        }
+    }
+
+    pub fn get(&self, settings: &Settings) -> usize {
+        match self {
+            ChoiceSetting::AntiAliasing => match settings.video.anti_aliasing {
+                Msaa::Off => 0,
+                Msaa::Sample2 => 1,
+                Msaa::Sample4 => 2,
+                Msaa::Sample8 => 3,
+            },
+            ChoiceSetting::ShadowFiltering => match settings.video.shadow_filtering_method {
+                ShadowFilteringMethod::Hardware2x2 => 0,
+                ShadowFilteringMethod::Gaussian => 1,
+                ShadowFilteringMethod::Temporal => 2,
+            },
+            ChoiceSetting::WindowMode => match settings.video.window_mode {
+                WindowMode::Windowed => 0,
+                WindowMode::BorderlessFullscreen => 1,
+                _ => 2,
+            },
+            ChoiceSetting::TeamPalette => match settings.accessibility.team_palette {
+                TeamPalette::Standard => 0,
+                TeamPalette::Deuteranopia => 1,
+                TeamPalette::Tritanopia => 2,
+            },
+            ChoiceSetting::UiScale => closest_option(&UI_SCALE_OPTIONS, settings.accessibility.ui_scale),
+            ChoiceSetting::HighContrastSelection => settings.accessibility.high_contrast_selection as usize,
+            ChoiceSetting::EdgeScroll => settings.accessibility.edge_scroll as usize,
+            ChoiceSetting::CameraDrag => match settings.accessibility.camera_drag {
+                DragMode::Hold => 0,
+                DragMode::Toggle => 1,
+            },
+            ChoiceSetting::ReducedMotion => settings.accessibility.reduced_motion as usize,
+            ChoiceSetting::GameSpeed => closest_option(&GAME_SPEED_OPTIONS, settings.game.game_speed),
+            ChoiceSetting::StartingResources => STARTING_RESOURCES_OPTIONS.iter()
+                .position(|amount| *amount >= settings.game.starting_resources)
+                .unwrap_or(STARTING_RESOURCES_OPTIONS.len() - 1),
+            ChoiceSetting::PopulationCap => POPULATION_CAP_OPTIONS.iter()
+                .position(|cap| *cap >= settings.game.population_cap)
+                .unwrap_or(POPULATION_CAP_OPTIONS.len() - 1),
+            ChoiceSetting::VictoryCondition => match settings.game.victory_condition {
+                VictoryCondition::DestroyHq => 0,
+                VictoryCondition::EliminateAllUnits => 1,
+            },
+            ChoiceSetting::InputDelay => INPUT_DELAY_OPTIONS.iter()
+                .position(|turns| *turns >= settings.network.input_delay)
+                .unwrap_or(INPUT_DELAY_OPTIONS.len() - 1),
+            ChoiceSetting::Opponent => match settings.game.opponent {
+                None => 0,
+                Some(AiDifficulty::Easy) => 1,
+                Some(AiDifficulty::Normal) => 2,
+                Some(AiDifficulty::Hard) => 3,
+            },
+        }
+    }
+
+    pub fn set(&self, settings: &mut Settings, index: usize) {
+        match self {
+            ChoiceSetting::AntiAliasing => {
+                settings.video.anti_aliasing = match index {
+                    0 => Msaa::Off,
+                    1 => Msaa::Sample2,
+                    2 => Msaa::Sample4,
+                    _ => Msaa::Sample8,
+                };
+            },
+            ChoiceSetting::ShadowFiltering => {
+                settings.video.shadow_filtering_method = match index {
+                    0 => ShadowFilteringMethod::Hardware2x2,
+                    1 => ShadowFilteringMethod::Gaussian,
+                    _ => ShadowFilteringMethod::Temporal,
+                };
+            },
+            ChoiceSetting::WindowMode => {
+                settings.video.window_mode = match index {
+                    0 => WindowMode::Windowed,
+                    1 => WindowMode::BorderlessFullscreen,
+                    _ => WindowMode::Fullscreen,
+                };
+            },
+            ChoiceSetting::TeamPalette => {
+                settings.accessibility.team_palette = match index {
+                    0 => TeamPalette::Standard,
+                    1 => TeamPalette::Deuteranopia,
+                    _ => TeamPalette::Tritanopia,
+                };
+            },
+            ChoiceSetting::UiScale => {
+                settings.accessibility.ui_scale = UI_SCALE_OPTIONS[index.min(UI_SCALE_OPTIONS.len() - 1)];
+            },
+            ChoiceSetting::HighContrastSelection => settings.accessibility.high_contrast_selection = index != 0,
+            ChoiceSetting::EdgeScroll => settings.accessibility.edge_scroll = index != 0,
+            ChoiceSetting::CameraDrag => {
+                settings.accessibility.camera_drag = match index {
+                    0 => DragMode::Hold,
+                    _ => DragMode::Toggle,
+                };
+            },
+            ChoiceSetting::ReducedMotion => settings.accessibility.reduced_motion = index != 0,
+            ChoiceSetting::GameSpeed => {
+                settings.game.game_speed = GAME_SPEED_OPTIONS[index.min(GAME_SPEED_OPTIONS.len() - 1)];
+            },
+            ChoiceSetting::StartingResources => {
+                settings.game.starting_resources = STARTING_RESOURCES_OPTIONS[index.min(STARTING_RESOURCES_OPTIONS.len() - 1)];
+            },
+            ChoiceSetting::PopulationCap => {
+                settings.game.population_cap = POPULATION_CAP_OPTIONS[index.min(POPULATION_CAP_OPTIONS.len() - 1)];
+            },
+            ChoiceSetting::VictoryCondition => {
+                settings.game.victory_condition = match index {
+                    0 => VictoryCondition::DestroyHq,
+                    _ => VictoryCondition::EliminateAllUnits,
+                };
+            },
+            ChoiceSetting::InputDelay => {
+                settings.network.input_delay = INPUT_DELAY_OPTIONS[index.min(INPUT_DELAY_OPTIONS.len() - 1)];
+            },
+            ChoiceSetting::Opponent => {
+                settings.game.opponent = match index {
+                    0 => None,
+                    1 => Some(AiDifficulty::Easy),
+                    2 => Some(AiDifficulty::Normal),
+                    _ => Some(AiDifficulty::Hard),
+                };
+            },
+        }
+    }
+
+    pub fn format(&self, settings: &Settings) -> &'static str {
+        self.options()[self.get(settings)]
+    }
+}
+
+// Closest step, in case the file holds a value that isn't one of the options
+fn closest_option(options: &[f32], value: f32) -> usize {
+    options.iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - value).abs().total_cmp(&(*b - value).abs()))
+        .map_or(0, |(index, _)| index)
+}
+
+#[derive(Component)]
+pub struct SettingSlider(pub SliderSetting);
+
+#[derive(Component)]
+pub struct SliderFill(pub SliderSetting);
+
+#[derive(Component)]
+pub struct SliderValueText(pub SliderSetting);
+
+#[derive(Component)]
+pub struct SettingDropdown(pub ChoiceSetting);
+
+#[derive(Component)]
+pub struct DropdownLabel(pub ChoiceSetting);
+
+#[derive(Component)]
+pub struct DropdownList(pub ChoiceSetting);
+
+#[derive(Component)]
+pub struct DropdownOption {
+    pub setting: ChoiceSetting,
+    pub index: usize,
+}
+
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                handle_slider_drag,
+                handle_dropdown_toggle,
+                handle_dropdown_option,
+                update_settings_widgets
+                    .run_if(resource_changed::<Settings>),
+            ).chain().run_if(in_state(PauseMenu::Settings).or_else(in_state(AppState::Lobby))));
+    }
+}
+
+fn label_style(font: Handle<Font>) -> TextStyle {
+    TextStyle {
+        font,
+        font_size: LABEL_FONT_SIZE,
+        color: Color::WHITE,
+    }
+}
+
+// Sliders in one column and choices in the other, so the page fits on small screens
+pub fn spawn_settings_columns(parent: &mut ChildBuilder, font: Handle<Font>, settings: &Settings) {
+    let column = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(8.0),
+            ..default()
+        },
+        ..default()
+    };
+    parent.spawn(NodeBundle {
+        style: Style {
+            column_gap: Val::Px(32.0),
+            ..default()
+        },
+        ..default()
+    }).with_children(|columns| {
+        columns.spawn(column.clone()).with_children(|sliders| {
+            for setting in SliderSetting::ALL {
+                spawn_slider(sliders, font.clone(), setting, settings);
+            }
+        });
+        columns.spawn(column).with_children(|choices| {
+            for setting in ChoiceSetting::OPTIONS_PAGE {
+                spawn_dropdown(choices, font.clone(), setting, settings);
+            }
+        });
+    });
+}
+
+// Label on the left, widget on the right
+fn spawn_setting_row(parent: &mut ChildBuilder, font: Handle<Font>, label: &str, spawn_widget: impl FnOnce(&mut ChildBuilder)) {
+    parent.spawn(NodeBundle {
+        style: Style {
+            width: Val::Px(ROW_WIDTH),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::SpaceBetween,
+            ..default()
+        },
+        ..default()
+    }).with_children(|row| {
+        row.spawn(TextBundle::from_section(label, label_style(font)));
+        spawn_widget(row);
+    });
+}
+
+pub fn spawn_slider(parent: &mut ChildBuilder, font: Handle<Font>, setting: SliderSetting, settings: &Settings) {
+    spawn_setting_row(parent, font.clone(), setting.label(), |row| {
+        row.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(WIDGET_WIDTH),
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        }).with_children(|widget| {
+            widget.spawn((
+                SettingSlider(setting),
+                Interaction::default(),
+                RelativeCursorPosition::default(),
+                NodeBundle {
+                    style: Style {
+                        flex_grow: 1.0,
+                        height: Val::Px(SLIDER_TRACK_HEIGHT),
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+            )).with_children(|track| {
+                track.spawn((
+                    SliderFill(setting),
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(setting.fraction(settings) * 100.),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        background_color: BUTTON_PRESSED_COLOR.into(),
+                        ..default()
+                    },
+                ));
+            });
+            widget.spawn((
+                SliderValueText(setting),
+                TextBundle::from_section(setting.format(settings), label_style(font)).with_style(Style {
+                    width: Val::Px(56.0),
+                    ..default()
+                }),
+            ));
+        });
+    });
+}
+
+pub fn spawn_dropdown(parent: &mut ChildBuilder, font: Handle<Font>, setting: ChoiceSetting, settings: &Settings) {
+    spawn_setting_row(parent, font.clone(), setting.label(), |row| {
+        row.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(WIDGET_WIDTH),
+                ..default()
+            },
+            ..default()
+        }).with_children(|widget| {
+            widget.spawn((
+                SettingDropdown(setting),
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(WIDGET_HEIGHT),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+            )).with_children(|button| {
+                button.spawn((
+                    DropdownLabel(setting),
+                    TextBundle::from_section(setting.format(settings), label_style(font.clone())),
+                ));
+            });
+            // Options hang below the button on top of the rows that follow, hidden until opened
+            widget.spawn((
+                DropdownList(setting),
+                NodeBundle {
+                    style: Style {
+                        display: Display::None,
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(WIDGET_HEIGHT),
+                        width: Val::Percent(100.0),
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+            )).with_children(|list| {
+                for (index, option) in setting.options().iter().enumerate() {
+                    list.spawn((
+                        DropdownOption { setting, index },
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Px(WIDGET_HEIGHT),
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                    )).with_children(|button| {
+                        button.spawn(TextBundle::from_section(*option, label_style(font.clone())));
+                    });
+                }
+            });
+        });
+    });
+}
+
+// Interaction stays pressed while the button is held, so the slider keeps following the cursor
+pub fn handle_slider_drag(
+    q_sliders: Query<(&Interaction, &RelativeCursorPosition, &SettingSlider)>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, relative_cursor, slider) in q_sliders.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(position) = relative_cursor.normalized else { continue; };
+        let (min, max) = slider.0.range();
+        let value = min + (max - min) * position.x.clamp(0., 1.);
+        // Avoid flagging the settings as changed while the cursor holds still
+        if slider.0.get(&settings) != value {
+            slider.0.set(&mut settings, value);
+        }
+    }
+}
+
+pub fn handle_dropdown_toggle(
+    q_dropdowns: Query<(&Interaction, &SettingDropdown), Changed<Interaction>>,
+    mut q_lists: Query<(&mut Style, &DropdownList)>,
+) {
+    for (interaction, dropdown) in q_dropdowns.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Only one list is open at a time
+        for (mut style, list) in q_lists.iter_mut() {
+            style.display = if list.0 == dropdown.0 && style.display == Display::None {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+    }
+}
+
+pub fn handle_dropdown_option(
+    q_options: Query<(&Interaction, &DropdownOption), Changed<Interaction>>,
+    mut q_lists: Query<(&mut Style, &DropdownList)>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, option) in q_options.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        option.setting.set(&mut settings, option.index);
+        for (mut style, list) in q_lists.iter_mut() {
+            if list.0 == option.setting {
+                style.display = Display::None;
+            }
+        }
+    }
+}
+
+pub fn update_settings_widgets(
+    settings: Res<Settings>,
+    mut q_fills: Query<(&mut Style, &SliderFill)>,
+    mut q_value_texts: Query<(&mut Text, &SliderValueText), Without<DropdownLabel>>,
+    mut q_dropdown_labels: Query<(&mut Text, &DropdownLabel), Without<SliderValueText>>,
+) {
+    for (mut style, fill) in q_fills.iter_mut() {
+        style.width = Val::Percent(fill.0.fraction(&settings) * 100.);
+    }
+    for (mut text, value_text) in q_value_texts.iter_mut() {
+        text.sections[0].value = value_text.0.format(&settings);
+    }
+    for (mut text, label) in q_dropdown_labels.iter_mut() {
+        text.sections[0].value = label.0.format(&settings).to_string();
+    }
+}
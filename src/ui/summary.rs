@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use crate::{resources::{player::PlayerId, settings::Settings, stats::MatchStats}, states::match_rules::MatchOutcome};
+
+const TABLE_FONT_SIZE: f32 = 18.;
+const TABLE_COLUMN_WIDTH: f32 = 140.;
+const GRAPH_WIDTH: f32 = 720.;
+const GRAPH_HEIGHT: f32 = 160.;
+const GRAPH_BACKGROUND_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.05);
+
+const TABLE_HEADERS: [&str; 7] = ["Player", "Units Built", "Units Lost", "Structures Lost", "Kills", "Gathered", "APM"];
+
+pub fn outcome_title(outcome: &MatchOutcome, local_player: PlayerId) -> &'static str {
+    if outcome.desync.is_some() {
+        return "Desynced";
+    }
+    match outcome.winner {
+        Some(winner) if winner == local_player => "Victory",
+        Some(_) => "Defeat",
+        None => "Draw",
+    }
+}
+
+fn table_cell(parent: &mut ChildBuilder, font: Handle<Font>, value: String, color: Color) {
+    parent.spawn(TextBundle::from_section(
+        value,
+        TextStyle {
+            font,
+            font_size: TABLE_FONT_SIZE,
+            color,
+        },
+    ).with_style(Style {
+        width: Val::Px(TABLE_COLUMN_WIDTH),
+        ..default()
+    }));
+}
+
+// Match length, per player statistics and a graph of army sizes over time
+pub fn spawn_match_summary(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    stats: &MatchStats,
+    settings: &Settings,
+    local_player: PlayerId,
+) {
+    let seconds = stats.elapsed as u32;
+    parent.spawn(TextBundle::from_section(
+        format!("Match length {}:{:02}", seconds / 60, seconds % 60),
+        TextStyle {
+            font: font.clone(),
+            font_size: TABLE_FONT_SIZE,
+            color: Color::WHITE,
+        },
+    ));
+
+    let row = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            ..default()
+        },
+        ..default()
+    };
+    parent.spawn(NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    }).with_children(|table| {
+        table.spawn(row.clone()).with_children(|header| {
+            for title in TABLE_HEADERS {
+                table_cell(header, font.clone(), title.to_string(), Color::srgb(0.7, 0.7, 0.7));
+            }
+        });
+        for player_id in stats.players.iter() {
+            let player_stats = stats.player(*player_id);
+            let color = settings.accessibility.team_palette.color(*player_id);
+            let name = if *player_id == local_player {
+                format!("Player {} (You)", player_id + 1)
+            } else {
+                format!("Player {}", player_id + 1)
+            };
+            table.spawn(row.clone()).with_children(|table_row| {
+                table_cell(table_row, font.clone(), name, color);
+                table_cell(table_row, font.clone(), player_stats.units_built.to_string(), Color::WHITE);
+                table_cell(table_row, font.clone(), player_stats.units_lost.to_string(), Color::WHITE);
+                table_cell(table_row, font.clone(), player_stats.structures_lost.to_string(), Color::WHITE);
+                table_cell(table_row, font.clone(), player_stats.kills.to_string(), Color::WHITE);
+                table_cell(table_row, font.clone(), player_stats.resources_gathered.to_string(), Color::WHITE);
+                table_cell(table_row, font.clone(), format!("{:.0}", stats.apm(*player_id)), Color::WHITE);
+            });
+        }
+    });
+
+    spawn_timeline_graph(parent, font, stats, settings);
+}
+
+// One group of bars per timeline sample, one bar per player, scaled to the largest army
+fn spawn_timeline_graph(parent: &mut ChildBuilder, font: Handle<Font>, stats: &MatchStats, settings: &Settings) {
+    parent.spawn(TextBundle::from_section(
+        "Army Size",
+        TextStyle {
+            font,
+            font_size: TABLE_FONT_SIZE,
+            color: Color::WHITE,
+        },
+    ));
+    let max_units = stats.timeline.iter()
+        .flat_map(|sample| sample.players.values())
+        .map(|player_sample| player_sample.supply)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    parent.spawn(NodeBundle {
+        style: Style {
+            width: Val::Px(GRAPH_WIDTH),
+            height: Val::Px(GRAPH_HEIGHT),
+            align_items: AlignItems::FlexEnd,
+            column_gap: Val::Px(2.0),
+            ..default()
+        },
+        background_color: GRAPH_BACKGROUND_COLOR.into(),
+        ..default()
+    }).with_children(|graph| {
+        for sample in stats.timeline.iter() {
+            graph.spawn(NodeBundle {
+                style: Style {
+                    flex_grow: 1.0,
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::FlexEnd,
+                    ..default()
+                },
+                ..default()
+            }).with_children(|group| {
+                for player_id in stats.players.iter() {
+                    let units = sample.players.get(player_id).map_or(0, |player_sample| player_sample.supply);
+                    group.spawn(NodeBundle {
+                        style: Style {
+                            flex_grow: 1.0,
+                            height: Val::Percent(units as f32 / max_units as f32 * 100.),
+                            ..default()
+                        },
+                        background_color: settings.accessibility.team_palette.color(*player_id).into(),
+                        ..default()
+                    });
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{headless_app, sim_ids};
+use rts::{
+    ai::{skirmish::SkirmishAi, AiController, AiPlayers, AiView, AI_PLAYER},
+    entities::{units::Unit, Owner},
+    resources::{player::PlayerId, settings::{AiDifficulty, Settings}},
+    simulation::{commands::{CommandLog, PlayerCommand}, fixed::FixedVec2},
+    states::match_rules::MatchOutcome,
+};
+
+const MATCH_UPDATES: usize = 900;
+// Five minutes of game time, plenty for a one-sided match
+const DECISIVE_UPDATES: usize = 9000;
+
+fn orders(app: &App, player: PlayerId) -> Vec<&'static str> {
+    app.world().resource::<CommandLog>().commands.iter()
+        .filter(|timed| timed.player == player)
+        .map(|timed| timed.command.name())
+        .collect()
+}
+
+// Lobby opponent of the given difficulty, after the given number of updates
+fn skirmish(difficulty: AiDifficulty, updates: usize) -> App {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<Settings>().game.opponent = Some(difficulty);
+    for _ in 0..updates {
+        app.update();
+    }
+    app
+}
+
+#[test]
+fn skirmish_ai_builds_scouts_and_attacks() {
+    let app = skirmish(AiDifficulty::Hard, MATCH_UPDATES);
+    let ai_orders = orders(&app, AI_PLAYER);
+    for order in ["train", "build", "move", "attack"] {
+        assert!(ai_orders.contains(&order), "AI never gave a {} order: {:?}", order, ai_orders);
+    }
+    // The human seat is left alone
+    assert!(orders(&app, 0).is_empty());
+}
+
+#[test]
+fn harder_ai_trains_faster() {
+    let mut easy = skirmish(AiDifficulty::Easy, 300);
+    let mut hard = skirmish(AiDifficulty::Hard, 300);
+    assert!(sim_ids::<With<Unit>>(&mut hard, AI_PLAYER).len() > sim_ids::<With<Unit>>(&mut easy, AI_PLAYER).len());
+}
+
+// Sends every idle unit to one spot
+struct Rally {
+    target: Vec2,
+    thoughts: usize,
+}
+
+impl AiController for Rally {
+    fn name(&self) -> &str {
+        "rally"
+    }
+
+    fn interval(&self) -> u64 {
+        30
+    }
+
+    fn think(&mut self, view: &AiView) -> Vec<PlayerCommand> {
+        self.thoughts += 1;
+        let units: Vec<_> = view.units.iter().filter(|unit| unit.idle).map(|unit| unit.sim_id).collect();
+        if units.is_empty() {
+            return Vec::new();
+        }
+        vec![PlayerCommand::Move { units, target: FixedVec2::from_vec2(self.target), queued: false }]
+    }
+}
+
+#[test]
+fn custom_controllers_play_through_commands() {
+    let target = Vec2::new(30., -10.);
+    let mut app = headless_app();
+    let mut ai_players = AiPlayers::default();
+    ai_players.add(AI_PLAYER, Rally {
+        target,
+        thoughts: 0,
+    });
+    // Both seats can be computer players
+    ai_players.add(0, SkirmishAi::new(AiDifficulty::Normal));
+    app.insert_resource(ai_players);
+    for _ in 0..MATCH_UPDATES {
+        app.update();
+    }
+
+    assert!(orders(&app, AI_PLAYER).iter().all(|order| *order == "move"));
+    assert!(orders(&app, 0).contains(&"train"));
+    let world = app.world_mut();
+    let rallied = world.query::<(&Owner, &Transform)>().iter(world)
+        .filter(|(owner, _)| owner.0 == AI_PLAYER)
+        .filter(|(_, transform)| transform.translation.xz().distance(target) < 3.)
+        .count();
+    assert!(rallied >= 3, "only {} units reached the rally point", rallied);
+}
+
+#[test]
+fn stronger_ai_destroys_the_weaker_one() {
+    let mut app = headless_app();
+    let mut ai_players = AiPlayers::default();
+    // The first seat also starts with far more units
+    ai_players.add(0, SkirmishAi::new(AiDifficulty::Hard));
+    ai_players.add(AI_PLAYER, SkirmishAi::new(AiDifficulty::Easy));
+    app.insert_resource(ai_players);
+    for _ in 0..DECISIVE_UPDATES {
+        app.update();
+        if app.world().resource::<MatchOutcome>().winner.is_some() {
+            break;
+        }
+    }
+    let outcome = app.world().resource::<MatchOutcome>();
+    assert_eq!(outcome.winner, Some(0));
+    assert_eq!(outcome.eliminated, [AI_PLAYER]);
+    assert!(orders(&app, 0).contains(&"gather"));
+}
+
+#[test]
+fn ai_args_seat_players() {
+    let args: Vec<String> = ["rts", "--headless", "--ai", "hard,easy"].map(String::from).to_vec();
+    let ai_players = AiPlayers::from_args(&args).unwrap();
+    let seats: Vec<(PlayerId, &str)> = ai_players.players.iter().map(|ai| (ai.player, ai.controller.name())).collect();
+    assert_eq!(seats, [(0, "hard skirmish"), (1, "easy skirmish")]);
+    assert!(AiPlayers::from_args(&["rts".to_string()]).is_none());
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{booted_app, issue, sim_ids};
+use rts::{
+    entities::{structures::Structure, units::{behaviour::{BuildOrder, OrderQueue, Stance, CARRY_CAPACITY}, orders::AttackOrder, Unit}, world_objects::ResourceNode},
+    resources::{player::Stockpiles, stats::MatchStats},
+    simulation::{commands::PlayerCommand, fixed::FixedVec2, SimId},
+};
+
+// Close enough to a point to count as having been there
+const REACHED: f32 = 2.5;
+
+fn resource_nodes(app: &mut App) -> Vec<(SimId, u32)> {
+    let world = app.world_mut();
+    let mut nodes: Vec<(SimId, u32)> = world.query::<(&SimId, &ResourceNode)>().iter(world)
+        .map(|(sim_id, node)| (*sim_id, node.amount))
+        .collect();
+    nodes.sort_unstable();
+    nodes
+}
+
+fn position(app: &mut App, unit: SimId) -> Vec2 {
+    let world = app.world_mut();
+    world.query::<(&SimId, &Transform)>().iter(world)
+        .find(|(sim_id, _)| **sim_id == unit)
+        .map(|(_, transform)| transform.translation.xz())
+        .unwrap()
+}
+
+fn is_attacking(app: &mut App, unit: SimId) -> bool {
+    let world = app.world_mut();
+    world.query_filtered::<&SimId, With<AttackOrder>>().iter(world).any(|sim_id| *sim_id == unit)
+}
+
+fn queued_orders(app: &mut App, unit: SimId) -> usize {
+    let world = app.world_mut();
+    world.query::<(&SimId, &OrderQueue)>().iter(world)
+        .find(|(sim_id, _)| **sim_id == unit)
+        .map_or(0, |(_, queue)| queue.orders.len())
+}
+
+fn point(x: f32, y: f32) -> FixedVec2 {
+    FixedVec2::from_vec2(Vec2::new(x, y))
+}
+
+#[test]
+fn queued_moves_run_in_turn() {
+    let mut app = booted_app();
+    let unit = sim_ids::<With<Unit>>(&mut app, 0)[0];
+    let (first, second) = (Vec2::new(-8., 0.), Vec2::new(-8., -8.));
+    // Both given in the same frame, so they are applied on the same tick
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![unit], target: FixedVec2::from_vec2(first), queued: false });
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![unit], target: FixedVec2::from_vec2(second), queued: true });
+    let mut reached_first = false;
+    for _ in 0..300 {
+        app.update();
+        let position = position(&mut app, unit);
+        if !reached_first && position.distance(first) <= REACHED {
+            reached_first = true;
+            // Still on the first move, the second one waits in the queue
+            assert_eq!(queued_orders(&mut app, unit), 1);
+        }
+        // The second move waits for the first
+        if position.distance(second) <= REACHED {
+            assert!(reached_first);
+        }
+    }
+    assert!(reached_first);
+    assert!(position(&mut app, unit).distance(second) <= REACHED);
+    assert_eq!(queued_orders(&mut app, unit), 0);
+}
+
+#[test]
+fn order_without_shift_replaces_the_queue() {
+    let mut app = booted_app();
+    let unit = sim_ids::<With<Unit>>(&mut app, 0)[0];
+    let target = Vec2::new(-6., -6.);
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![unit], target: point(-8., 0.), queued: false });
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![unit], target: point(-8., -8.), queued: true });
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![unit], target: FixedVec2::from_vec2(target), queued: false });
+    for _ in 0..300 {
+        app.update();
+    }
+    assert!(position(&mut app, unit).distance(target) <= REACHED);
+    assert_eq!(queued_orders(&mut app, unit), 0);
+}
+
+#[test]
+fn patrols_walk_back_and_forth() {
+    let mut app = booted_app();
+    let unit = sim_ids::<With<Unit>>(&mut app, 0)[0];
+    let target = Vec2::new(-8., -6.);
+    issue(&mut app, 0, PlayerCommand::Patrol { units: vec![unit], target: FixedVec2::from_vec2(target), queued: false });
+    app.update();
+    let start = position(&mut app, unit);
+    let mut visits = 0;
+    let mut heading_out = true;
+    for _ in 0..600 {
+        app.update();
+        let position = position(&mut app, unit);
+        let waypoint = if heading_out { target } else { start };
+        if position.distance(waypoint) <= REACHED {
+            visits += 1;
+            heading_out = !heading_out;
+        }
+    }
+    // Out, back and out again at least
+    assert!(visits >= 3, "only {} waypoints visited", visits);
+}
+
+#[test]
+fn passive_units_ignore_enemies() {
+    let mut app = booted_app();
+    let units = sim_ids::<With<Unit>>(&mut app, 0);
+    let (passive, aggressive) = (units[0], units[1]);
+    // Right next to the enemy's units
+    issue(&mut app, 0, PlayerCommand::Stance { units: vec![passive], stance: Stance::Passive });
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![passive], target: point(12., 5.), queued: false });
+    issue(&mut app, 0, PlayerCommand::Move { units: vec![aggressive], target: point(16., 5.), queued: false });
+    let mut engaged = false;
+    for _ in 0..300 {
+        app.update();
+        assert!(!is_attacking(&mut app, passive));
+        engaged |= is_attacking(&mut app, aggressive);
+    }
+    assert!(engaged);
+}
+
+#[test]
+fn queued_build_waits_for_the_builders() {
+    let mut app = booted_app();
+    let builders = sim_ids::<With<Unit>>(&mut app, 0)[..2].to_vec();
+    let structures = sim_ids::<With<Structure>>(&mut app, 0).len();
+    let stockpile = app.world().resource::<Stockpiles>().get(0);
+    issue(&mut app, 0, PlayerCommand::Move { units: builders.clone(), target: point(-8., 0.), queued: false });
+    issue(&mut app, 0, PlayerCommand::Build { position: point(-12., -8.), units: builders, queued: true });
+    for _ in 0..5 {
+        app.update();
+    }
+    // Nothing is paid for before the builders get there
+    assert_eq!(sim_ids::<With<Structure>>(&mut app, 0).len(), structures);
+    assert_eq!(app.world().resource::<Stockpiles>().get(0), stockpile);
+    for _ in 0..400 {
+        app.update();
+    }
+    // Both builders went, only one structure went up
+    assert_eq!(sim_ids::<With<Structure>>(&mut app, 0).len(), structures + 1);
+}
+
+#[test]
+fn builds_need_builders_and_room() {
+    let mut app = booted_app();
+    let builders = sim_ids::<With<Unit>>(&mut app, 0)[..1].to_vec();
+    let structures = sim_ids::<With<Structure>>(&mut app, 0).len();
+    let stockpile = app.world().resource::<Stockpiles>().get(0);
+    // Nobody to build it, then right on top of the HQ
+    issue(&mut app, 0, PlayerCommand::Build { position: point(-12., -8.), units: Vec::new(), queued: false });
+    issue(&mut app, 0, PlayerCommand::Build { position: point(-5., 5.), units: builders, queued: false });
+    for _ in 0..300 {
+        app.update();
+    }
+    let world = app.world_mut();
+    assert_eq!(world.query::<&BuildOrder>().iter(world).count(), 0);
+    assert_eq!(sim_ids::<With<Structure>>(&mut app, 0).len(), structures);
+    assert_eq!(app.world().resource::<Stockpiles>().get(0), stockpile);
+}
+
+#[test]
+fn gatherers_bring_resources_home() {
+    let mut app = booted_app();
+    let gatherers = sim_ids::<With<Unit>>(&mut app, 0)[..2].to_vec();
+    let (node, amount) = resource_nodes(&mut app)[0];
+    let stockpile = app.world().resource::<Stockpiles>().get(0);
+    issue(&mut app, 0, PlayerCommand::Gather { units: gatherers, node, queued: false });
+    for _ in 0..900 {
+        app.update();
+    }
+    let gathered = app.world().resource::<Stockpiles>().get(0) - stockpile;
+    assert!(gathered >= 2 * CARRY_CAPACITY, "only {} gathered", gathered);
+    assert!(app.world().resource::<MatchStats>().player(0).resources_gathered >= 2 * CARRY_CAPACITY);
+    // Everything brought home came out of the node
+    let (_, left) = resource_nodes(&mut app)[0];
+    assert!(amount - left >= gathered);
+}
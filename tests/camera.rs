@@ -0,0 +1,106 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{Harness, WINDOW_SIZE};
+use rts::ui::cursor::CursorMode;
+
+const FRAMES: usize = 30;
+
+#[test]
+fn cursor_in_middle_does_not_scroll() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.run_frames(FRAMES);
+
+    assert_eq!(harness.player_camera().location, Vec3::ZERO);
+}
+
+#[test]
+fn edge_scroll_moves_towards_edge() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.set_cursor_location(Vec2::new(1., WINDOW_SIZE.y / 2.));
+    harness.run_frames(FRAMES);
+
+    let camera = harness.player_camera();
+    assert!(camera.location.x < 0., "camera should scroll left, got {}", camera.location);
+    assert_eq!(camera.location.z, 0.);
+    assert!(camera.velocity.x < 0.);
+}
+
+#[test]
+fn edge_scroll_in_corner_moves_diagonally() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.set_cursor_location(WINDOW_SIZE - Vec2::ONE);
+    harness.run_frames(FRAMES);
+
+    let camera = harness.player_camera();
+    assert!(camera.location.x > 0.);
+    assert!(camera.location.z > 0.);
+}
+
+#[test]
+fn edge_scroll_follows_camera_rotation() {
+    let mut harness = Harness::new().with_camera_systems();
+    let camera = harness.camera;
+    harness.app.world_mut().get_mut::<rts::controls::camera::PlayerCamera>(camera).unwrap().rotation.y = std::f32::consts::FRAC_PI_2;
+    harness.set_cursor_location(Vec2::new(1., WINDOW_SIZE.y / 2.));
+    harness.run_frames(FRAMES);
+
+    // Screen left is world +z once the camera has turned a quarter
+    let camera = harness.player_camera();
+    assert!(camera.location.z > 0., "camera should scroll along z, got {}", camera.location);
+    assert!(camera.location.x.abs() < 1e-3);
+}
+
+#[test]
+fn edge_scroll_disabled_in_settings() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.settings_mut().accessibility.edge_scroll = false;
+    harness.set_cursor_location(Vec2::new(1., WINDOW_SIZE.y / 2.));
+    harness.run_frames(FRAMES);
+
+    assert_eq!(harness.player_camera().location, Vec3::ZERO);
+}
+
+#[test]
+fn edge_scroll_paused_during_camera_control() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.set_cursor_mode(CursorMode::CameraControl);
+    assert_eq!(harness.cursor_mode(), CursorMode::CameraControl);
+    harness.set_cursor_location(Vec2::new(1., WINDOW_SIZE.y / 2.));
+    harness.run_frames(FRAMES);
+
+    assert_eq!(harness.player_camera().location, Vec3::ZERO);
+}
+
+#[test]
+fn mouse_motion_drags_camera() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.set_cursor_mode(CursorMode::CameraControl);
+    harness.send_mouse_motion(Vec2::new(50., 0.));
+    harness.update();
+
+    let camera = harness.player_camera();
+    assert!(camera.location.x > 0., "camera should follow the drag, got {}", camera.location);
+    assert_eq!(camera.location.z, 0.);
+}
+
+#[test]
+fn mouse_motion_ignored_while_idle() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.send_mouse_motion(Vec2::new(50., 0.));
+    harness.update();
+
+    assert_eq!(harness.player_camera().location, Vec3::ZERO);
+}
+
+#[test]
+fn reduced_motion_stops_without_gliding() {
+    let mut harness = Harness::new().with_camera_systems();
+    harness.settings_mut().accessibility.reduced_motion = true;
+    harness.set_cursor_location(Vec2::new(1., WINDOW_SIZE.y / 2.));
+    harness.run_frames(FRAMES);
+    harness.set_cursor_location(WINDOW_SIZE / 2.);
+    harness.update();
+
+    assert_eq!(harness.player_camera().velocity, Vec3::ZERO);
+}
@@ -0,0 +1,337 @@
+// Shared harness for integration tests. Builds a windowless App with only the gameplay
+// systems under test, and lets tests feed it input and pointer hits frame by frame.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use avian3d::prelude::{CollidingEntities, CollisionLayers, LayerMask, PhysicsGizmos, SpatialQueryPipeline};
+use bevy::{ecs::query::QueryFilter, gizmos::GizmoPlugin, input::mouse::{MouseMotion, MouseWheel}, prelude::*, render::render_resource::Shader, state::app::StatesPlugin, time::TimeUpdateStrategy, window::PrimaryWindow};
+use bevy_mod_picking::{backend::{HitData, PointerHits}, pointer::PointerId, selection::PointerMultiselect};
+use rts::{
+    controls::{
+        camera::{PlayerCamera, PlayerCameraPlugin},
+        orders::handle_order_click,
+        selection::{Selectable, Selected, SelectionPlugin},
+    },
+    entities::{units::Unit, EntityCollisionLayers, Owner},
+    headless::{Headless, HeadlessPlugin},
+    resources::{player::{Player, PlayerId}, settings::{Settings, SettingsPlugin}, stats::{StatsConfig, StatsPlugin}},
+    simulation::{commands::{IssueCommandEvent, PlayerCommand}, replay::{ReplayConfig, ReplayPlugin}, SimId},
+    states::AppState,
+    ui::cursor::{handle_cursor_mode_event, Cursor, CursorModeChangeEvent, CursorMode, CursorSelection, Selection},
+    SimulationPlugins,
+};
+
+pub const TIMESTEP: Duration = Duration::from_millis(16);
+pub const WINDOW_SIZE: Vec2 = Vec2::new(800., 600.);
+// Enough updates for a headless app to boot into the match
+pub const BOOT_UPDATES: usize = 4;
+// Updates allowed for networked apps to find each other and start the match
+pub const CONNECT_UPDATES: usize = 30;
+
+// Commands the systems under test issued, oldest first
+#[derive(Default, Resource)]
+pub struct IssuedCommands(pub Vec<PlayerCommand>);
+
+pub struct Harness {
+    pub app: App,
+    pub cursor: Entity,
+    pub camera: Entity,
+    pub ground: Entity,
+}
+
+impl Harness {
+    // In game with the cursor idle in the middle of an 800x600 window
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app
+            .add_plugins((
+                MinimalPlugins,
+                StatesPlugin,
+                AssetPlugin::default(),
+                SettingsPlugin {
+                    persist: false,
+                },
+            ))
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            // Input is fed by hand, without InputPlugin clearing it every frame
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<MouseMotion>()
+            .add_event::<MouseWheel>()
+            .add_event::<PointerHits>()
+            .add_event::<CursorModeChangeEvent>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(TIMESTEP))
+            .insert_state(AppState::InGame)
+            .add_sub_state::<CursorMode>()
+            .add_systems(Update, handle_cursor_mode_event.run_if(in_state(AppState::InGame)));
+
+        let world = app.world_mut();
+        world.spawn((
+            Window {
+                resolution: WINDOW_SIZE.into(),
+                ..default()
+            },
+            PrimaryWindow,
+        ));
+        let cursor = world.spawn((
+            PointerId::Mouse,
+            PointerMultiselect::default(),
+            CursorSelection::default(),
+            Cursor {
+                location: WINDOW_SIZE / 2.,
+                ..default()
+            },
+        )).id();
+        let camera = world.spawn((PlayerCamera::default(), Camera::default(), Transform::default(), GlobalTransform::default())).id();
+        let ground = world.spawn(CollisionLayers::new(EntityCollisionLayers::Ground, LayerMask::ALL)).id();
+
+        let mut harness = Harness {
+            app,
+            cursor,
+            camera,
+            ground,
+        };
+        // Let the state machinery enter InGame and the default cursor mode
+        harness.update();
+        harness
+    }
+
+    // Box and click selection. The outlines are drawn into gizmo groups nothing renders, but the
+    // groups still have to exist.
+    pub fn with_selection_systems(mut self) -> Self {
+        self.app
+            .init_asset::<Shader>()
+            .add_plugins(GizmoPlugin)
+            .insert_gizmo_config(PhysicsGizmos::none(), GizmoConfig::default())
+            .add_plugins(SelectionPlugin);
+        self
+    }
+
+    // Camera input and movement. Nothing is in the spatial query pipeline, so there is no terrain
+    // to follow or to pull the camera in.
+    pub fn with_camera_systems(mut self) -> Self {
+        self.app
+            .init_resource::<SpatialQueryPipeline>()
+            .add_plugins(PlayerCameraPlugin::default());
+        self
+    }
+
+    // Right click orders for the local player, collecting what they issue instead of simulating it
+    pub fn with_order_systems(mut self) -> Self {
+        self.app
+            .init_resource::<Player>()
+            .init_resource::<IssuedCommands>()
+            .add_event::<IssueCommandEvent>()
+            .add_systems(Update, (
+                handle_order_click,
+                collect_issued_commands,
+            ).chain().run_if(in_state(AppState::InGame)));
+        self
+    }
+
+    pub fn update(&mut self) {
+        self.app.update();
+    }
+
+    pub fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    pub fn spawn_selectable(&mut self, position: Vec3) -> Entity {
+        self.app.world_mut().spawn((
+            Selectable::default(),
+            CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+            Transform::from_translation(position),
+        )).id()
+    }
+
+    pub fn spawn_unit(&mut self, sim_id: SimId, owner: PlayerId, position: Vec3) -> Entity {
+        self.app.world_mut().spawn((
+            sim_id,
+            Owner(owner),
+            Unit,
+            Selectable::default(),
+            CollisionLayers::new(EntityCollisionLayers::Selectable, LayerMask::ALL),
+            Transform::from_translation(position),
+        )).id()
+    }
+
+    pub fn select(&mut self, entity: Entity) {
+        self.app.world_mut().entity_mut(entity).insert(Selected);
+    }
+
+    pub fn issued_commands(&self) -> &[PlayerCommand] {
+        &self.app.world().resource::<IssuedCommands>().0
+    }
+
+    pub fn settings_mut(&mut self) -> Mut<Settings> {
+        self.app.world_mut().resource_mut::<Settings>()
+    }
+
+    pub fn set_cursor_mode(&mut self, mode: CursorMode) {
+        self.app.world_mut().send_event(CursorModeChangeEvent(mode));
+        // One frame to request the transition and one for StateTransition to apply it
+        self.run_frames(2);
+    }
+
+    pub fn cursor_mode(&self) -> CursorMode {
+        *self.app.world().resource::<State<CursorMode>>().get()
+    }
+
+    pub fn set_cursor_location(&mut self, location: Vec2) {
+        self.app.world_mut().get_mut::<Cursor>(self.cursor).unwrap().location = location;
+    }
+
+    pub fn set_multiselect(&mut self, is_pressed: bool) {
+        self.app.world_mut().get_mut::<PointerMultiselect>(self.cursor).unwrap().is_pressed = is_pressed;
+    }
+
+    // A hit reported by the picking backend for the cursor's pointer this frame
+    pub fn send_pointer_hit(&mut self, entity: Entity, position: Vec3) {
+        let hit = HitData::new(self.camera, 1., Some(position), Some(Vec3::Y));
+        self.app.world_mut().send_event(PointerHits::new(PointerId::Mouse, vec![(entity, hit)], 0.));
+    }
+
+    // Holds the button for one frame, then clears just_pressed like InputPlugin would
+    pub fn press_mouse(&mut self, button: MouseButton) {
+        self.app.world_mut().resource_mut::<ButtonInput<MouseButton>>().press(button);
+        self.update();
+        self.app.world_mut().resource_mut::<ButtonInput<MouseButton>>().clear();
+    }
+
+    pub fn release_mouse(&mut self, button: MouseButton) {
+        self.app.world_mut().resource_mut::<ButtonInput<MouseButton>>().release(button);
+        self.update();
+        self.app.world_mut().resource_mut::<ButtonInput<MouseButton>>().clear();
+    }
+
+    pub fn send_mouse_motion(&mut self, delta: Vec2) {
+        self.app.world_mut().send_event(MouseMotion { delta });
+    }
+
+    // Click on an entity, as the picking backend would report it under the cursor
+    pub fn click(&mut self, entity: Entity, position: Vec3) {
+        self.send_pointer_hit(entity, position);
+        self.press_mouse(MouseButton::Left);
+        self.release_mouse(MouseButton::Left);
+    }
+
+    pub fn selection_box(&mut self) -> Option<Entity> {
+        let world = self.app.world_mut();
+        world.query_filtered::<Entity, With<Selection>>().iter(world).next()
+    }
+
+    // Stand-in for avian's narrow phase, which isn't running in the harness
+    pub fn set_selection_box_contents(&mut self, entities: &[Entity]) {
+        let selection_box = self.selection_box().expect("no selection box");
+        let mut colliding_entities = CollidingEntities::default();
+        for entity in entities {
+            colliding_entities.insert(*entity);
+        }
+        self.app.world_mut().entity_mut(selection_box).insert(colliding_entities);
+    }
+
+    pub fn is_selected(&self, entity: Entity) -> bool {
+        self.app.world().get::<Selected>(entity).is_some()
+    }
+
+    pub fn selected_count(&mut self) -> usize {
+        let world = self.app.world_mut();
+        world.query_filtered::<(), With<Selected>>().iter(world).count()
+    }
+
+    pub fn player_camera(&self) -> &PlayerCamera {
+        self.app.world().get::<PlayerCamera>(self.camera).unwrap()
+    }
+}
+
+fn collect_issued_commands(
+    mut issued: ResMut<IssuedCommands>,
+    mut ev_issue: EventReader<IssueCommandEvent>,
+) {
+    issued.0.extend(ev_issue.read().map(|event| event.command.clone()));
+}
+
+// A headless match that keeps its settings, statistics and replays in memory
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        HeadlessPlugin {
+            config: Headless::default(),
+        },
+        SimulationPlugins.build()
+            .set(SettingsPlugin {
+                persist: false,
+            })
+            .set(StatsPlugin {
+                config: StatsConfig {
+                    export_directory: None,
+                    ..default()
+                },
+            })
+            .set(ReplayPlugin {
+                config: ReplayConfig {
+                    directory: None,
+                },
+            }),
+    ));
+    app.finish();
+    app.cleanup();
+    app
+}
+
+// A headless match that is already under way
+pub fn booted_app() -> App {
+    let mut app = headless_app();
+    for _ in 0..BOOT_UPDATES {
+        app.update();
+    }
+    app
+}
+
+pub fn in_game(app: &App) -> bool {
+    *app.world().resource::<State<AppState>>().get() == AppState::InGame
+}
+
+// Two networked apps updated in turn until both are in the match
+pub fn start_together(mut first: App, mut second: App) -> Option<(App, App)> {
+    for _ in 0..CONNECT_UPDATES {
+        first.update();
+        second.update();
+        if in_game(&first) && in_game(&second) {
+            return Some((first, second));
+        }
+    }
+    None
+}
+
+pub fn run_both(first: &mut App, second: &mut App, updates: usize) {
+    for _ in 0..updates {
+        first.update();
+        second.update();
+    }
+}
+
+// Entities of the player matching the filter, in SimId order
+pub fn sim_ids<F: QueryFilter>(app: &mut App, player: PlayerId) -> Vec<SimId> {
+    let world = app.world_mut();
+    let mut sim_ids: Vec<SimId> = world.query_filtered::<(&SimId, &Owner), F>()
+        .iter(world)
+        .filter(|(_, owner)| owner.0 == player)
+        .map(|(sim_id, _)| *sim_id)
+        .collect();
+    sim_ids.sort_unstable();
+    sim_ids
+}
+
+// Give a command as the player would, it is applied on the next tick after this update
+pub fn issue(app: &mut App, player: PlayerId, command: PlayerCommand) {
+    app.world_mut().send_event(IssueCommandEvent {
+        player,
+        command,
+    });
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{booted_app, issue, sim_ids};
+use rts::{
+    entities::{structures::Structure, units::Unit},
+    resources::{player::PlayerId, stats::MatchStats},
+    simulation::commands::PlayerCommand,
+    states::{match_rules::MatchOutcome, AppState},
+};
+
+// Long enough to cross the map and bring a HQ down
+const ASSAULT_UPDATES: usize = 1800;
+
+fn winner(app: &App) -> Option<PlayerId> {
+    app.world().resource::<MatchOutcome>().winner
+}
+
+// Until someone wins or the updates run out, then one more for the game over screen to come up
+fn run_until_won(app: &mut App, updates: usize) {
+    for _ in 0..updates {
+        app.update();
+        if winner(app).is_some() {
+            break;
+        }
+    }
+    app.update();
+}
+
+#[test]
+fn destroying_the_hq_wins_the_match() {
+    let mut app = booted_app();
+    let units = sim_ids::<With<Unit>>(&mut app, 0);
+    let headquarters = sim_ids::<With<Structure>>(&mut app, 1)[0];
+    issue(&mut app, 0, PlayerCommand::Attack { units, target: headquarters, queued: false });
+    run_until_won(&mut app, ASSAULT_UPDATES);
+
+    assert_eq!(winner(&app), Some(0));
+    assert_eq!(*app.world().resource::<State<AppState>>().get(), AppState::GameOver);
+}
+
+#[test]
+fn resigning_ends_the_match() {
+    let mut app = booted_app();
+    issue(&mut app, 1, PlayerCommand::Eliminate { player: 1 });
+    run_until_won(&mut app, 10);
+
+    assert_eq!(winner(&app), Some(0));
+    assert_eq!(app.world().resource::<MatchOutcome>().eliminated, [1]);
+    // Three units and the HQ
+    let stats = app.world().resource::<MatchStats>();
+    assert_eq!(stats.player(1).units_lost, 3);
+    assert_eq!(stats.player(1).structures_lost, 1);
+}
+
+#[test]
+fn players_cannot_eliminate_each_other() {
+    let mut app = booted_app();
+    let units = sim_ids::<With<Unit>>(&mut app, 1);
+    issue(&mut app, 0, PlayerCommand::Eliminate { player: 1 });
+    run_until_won(&mut app, 10);
+
+    assert_eq!(winner(&app), None);
+    assert_eq!(sim_ids::<With<Unit>>(&mut app, 1), units);
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use bevy::prelude::*;
+use common::{headless_app, in_game, issue, run_both, sim_ids, start_together};
+use rts::{
+    entities::{structures::Structure, units::Unit},
+    network::{session::NetworkSession, TURN_TICKS},
+    resources::{player::{PlayerId, Stockpiles}, settings::Settings},
+    simulation::{
+        commands::{CommandLog, PlayerCommand},
+        fixed::FixedVec2,
+        ChecksumHistory, SimulationTick,
+    },
+    states::{match_rules::{ActiveMatchRules, MatchOutcome}, AppState},
+};
+
+const MATCH_UPDATES: usize = 120;
+
+// A host and a client talking over loopback, both in the match
+fn connect() -> (App, App) {
+    connect_to(headless_app())
+}
+
+fn connect_to(mut host: App) -> (App, App) {
+    let session = NetworkSession::host(0).unwrap();
+    let port = session.local_port().unwrap();
+    host.insert_resource(session);
+    let mut client = headless_app();
+    client.insert_resource(NetworkSession::join(SocketAddr::from(([127, 0, 0, 1], port))).unwrap());
+    start_together(host, client).expect("peers did not start the match")
+}
+
+fn tick(app: &App) -> u64 {
+    app.world().resource::<SimulationTick>().0
+}
+
+#[test]
+fn peers_stay_in_sync() {
+    let (mut host, mut client) = connect();
+    let host_headquarters = sim_ids::<With<Structure>>(&mut host, 0)[0];
+    let client_headquarters = sim_ids::<With<Structure>>(&mut client, 1)[0];
+    let client_units = sim_ids::<With<Unit>>(&mut client, 1);
+    issue(&mut host, 0, PlayerCommand::Train { structure: host_headquarters });
+    issue(&mut client, 1, PlayerCommand::Train { structure: client_headquarters });
+    issue(&mut client, 1, PlayerCommand::Move { units: client_units, target: FixedVec2::from_vec2(Vec2::new(0., -10.)), queued: false });
+    run_both(&mut host, &mut client, MATCH_UPDATES);
+
+    let common_tick = tick(&host).min(tick(&client));
+    assert!(common_tick > MATCH_UPDATES as u64 / 2, "simulation stalled at tick {}", common_tick);
+    let host_checksum = host.world().resource::<ChecksumHistory>().get(common_tick);
+    assert!(host_checksum.is_some());
+    assert_eq!(host_checksum, client.world().resource::<ChecksumHistory>().get(common_tick));
+
+    // Both peers applied the same commands on the same ticks
+    let host_log = &host.world().resource::<CommandLog>().commands;
+    let mut orders: Vec<(PlayerId, &str)> = host_log.iter().map(|timed| (timed.player, timed.command.name())).collect();
+    orders.sort_unstable();
+    assert_eq!(orders, [(0, "train"), (1, "move"), (1, "train")]);
+    assert_eq!(host_log, &client.world().resource::<CommandLog>().commands);
+    assert_eq!(sim_ids::<With<Unit>>(&mut host, 0).len(), 11);
+    assert_eq!(sim_ids::<With<Unit>>(&mut client, 1).len(), 4);
+}
+
+#[test]
+fn host_waits_for_client() {
+    let (mut host, mut client) = connect();
+    run_both(&mut host, &mut client, 30);
+    for _ in 0..60 {
+        host.update();
+    }
+
+    // Only turns the client already sent commands for can be simulated
+    let input_delay = host.world().resource::<NetworkSession>().input_delay;
+    assert!(tick(&host) <= tick(&client) + (input_delay + 1) * TURN_TICKS);
+    assert!(host.world().resource::<NetworkSession>().stalled_since.is_some());
+
+    // Picks up again once the client catches up
+    let stalled_at = tick(&host);
+    run_both(&mut host, &mut client, 30);
+    assert!(tick(&host) >= stalled_at + 20);
+}
+
+#[test]
+fn diverged_state_is_detected() {
+    let (mut host, mut client) = connect();
+    run_both(&mut host, &mut client, 30);
+    host.world_mut().resource_mut::<Stockpiles>().add(0, 1);
+    run_both(&mut host, &mut client, 30);
+
+    for app in [&host, &client] {
+        assert!(app.world().resource::<NetworkSession>().desync.is_some());
+        assert!(app.world().resource::<MatchOutcome>().desync.is_some());
+        assert_eq!(*app.world().resource::<State<AppState>>().get(), AppState::GameOver);
+    }
+}
+
+#[test]
+fn host_continues_after_client_leaves() {
+    let (mut host, mut client) = connect();
+    run_both(&mut host, &mut client, 30);
+    client.world().resource::<NetworkSession>().leave();
+    drop(client);
+
+    let before = tick(&host);
+    for _ in 0..60 {
+        host.update();
+    }
+    assert!(host.world().resource::<NetworkSession>().peer_dropped());
+    assert!(tick(&host) >= before + 50);
+    assert!(in_game(&host));
+}
+
+#[test]
+fn client_plays_under_the_host_rules() {
+    let mut host = headless_app();
+    let population_cap = host.world().resource::<Settings>().game.population_cap;
+    host.world_mut().resource_mut::<Settings>().game.population_cap = population_cap + 50;
+    let (_host, client) = connect_to(host);
+
+    assert_eq!(client.world().resource::<ActiveMatchRules>().game.population_cap, population_cap + 50);
+    assert_eq!(client.world().resource::<Settings>().game.population_cap, population_cap);
+}
+
+#[test]
+fn zero_input_delay_still_plays() {
+    let mut host = headless_app();
+    host.world_mut().resource_mut::<Settings>().network.input_delay = 0;
+    let (mut host, mut client) = connect_to(host);
+    run_both(&mut host, &mut client, MATCH_UPDATES);
+
+    assert_eq!(host.world().resource::<NetworkSession>().input_delay, 1);
+    assert!(tick(&host).min(tick(&client)) > MATCH_UPDATES as u64 / 2, "simulation stalled");
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use rts::simulation::{commands::PlayerCommand, fixed::FixedVec2, SimId};
+
+// Press and release the right button over an entity, moving the mouse by drag in between
+fn right_click(harness: &mut Harness, entity: Entity, position: Vec3, drag: Vec2) {
+    harness.press_mouse(MouseButton::Right);
+    harness.send_mouse_motion(drag);
+    harness.send_pointer_hit(entity, position);
+    harness.release_mouse(MouseButton::Right);
+}
+
+#[test]
+fn right_click_on_ground_moves_selected_units() {
+    let mut harness = Harness::new().with_order_systems();
+    let unit = harness.spawn_unit(SimId(3), 0, Vec3::ZERO);
+    harness.spawn_unit(SimId(4), 0, Vec3::X);
+    harness.select(unit);
+    let ground = harness.ground;
+    right_click(&mut harness, ground, Vec3::new(5., 0., -2.), Vec2::ZERO);
+
+    assert_eq!(harness.issued_commands(), [PlayerCommand::Move {
+        units: vec![SimId(3)],
+        target: FixedVec2::from_vec2(Vec2::new(5., -2.)),
+        queued: false,
+    }]);
+}
+
+#[test]
+fn right_click_on_enemy_attacks_it() {
+    let mut harness = Harness::new().with_order_systems();
+    let unit = harness.spawn_unit(SimId(1), 0, Vec3::ZERO);
+    let enemy = harness.spawn_unit(SimId(2), 1, Vec3::new(8., 0., 0.));
+    harness.select(unit);
+    right_click(&mut harness, enemy, Vec3::new(8., 0.5, 0.), Vec2::ZERO);
+
+    assert_eq!(harness.issued_commands(), [PlayerCommand::Attack {
+        units: vec![SimId(1)],
+        target: SimId(2),
+        queued: false,
+    }]);
+}
+
+#[test]
+fn right_drag_does_not_order() {
+    let mut harness = Harness::new().with_order_systems();
+    let unit = harness.spawn_unit(SimId(1), 0, Vec3::ZERO);
+    harness.select(unit);
+    let ground = harness.ground;
+    right_click(&mut harness, ground, Vec3::new(5., 0., 5.), Vec2::new(40., 0.));
+
+    assert!(harness.issued_commands().is_empty());
+}
+
+#[test]
+fn enemy_units_in_selection_are_not_ordered() {
+    let mut harness = Harness::new().with_order_systems();
+    let enemy = harness.spawn_unit(SimId(2), 1, Vec3::ZERO);
+    harness.select(enemy);
+    let ground = harness.ground;
+    right_click(&mut harness, ground, Vec3::new(5., 0., 5.), Vec2::ZERO);
+
+    assert!(harness.issued_commands().is_empty());
+}
+
+#[test]
+fn shift_right_click_queues_the_order() {
+    let mut harness = Harness::new().with_order_systems();
+    let unit = harness.spawn_unit(SimId(1), 0, Vec3::ZERO);
+    harness.select(unit);
+    harness.set_multiselect(true);
+    let ground = harness.ground;
+    right_click(&mut harness, ground, Vec3::new(4., 0., 4.), Vec2::ZERO);
+
+    assert_eq!(harness.issued_commands(), [PlayerCommand::Move {
+        units: vec![SimId(1)],
+        target: FixedVec2::from_vec2(Vec2::new(4., 4.)),
+        queued: true,
+    }]);
+}
+
+#[test]
+fn right_click_on_own_unit_guards_it() {
+    let mut harness = Harness::new().with_order_systems();
+    let unit = harness.spawn_unit(SimId(1), 0, Vec3::ZERO);
+    let friend = harness.spawn_unit(SimId(2), 0, Vec3::new(6., 0., 0.));
+    harness.select(unit);
+    right_click(&mut harness, friend, Vec3::new(6., 0.5, 0.), Vec2::ZERO);
+
+    assert_eq!(harness.issued_commands(), [PlayerCommand::Guard {
+        units: vec![SimId(1)],
+        target: SimId(2),
+        queued: false,
+    }]);
+}
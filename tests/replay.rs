@@ -0,0 +1,128 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{booted_app, headless_app, issue, sim_ids, BOOT_UPDATES};
+use rts::{
+    entities::{structures::Structure, units::Unit},
+    resources::{settings::Settings, stats::MatchStats},
+    simulation::{
+        commands::{CommandLog, PlayerCommand},
+        fixed::FixedVec2,
+        replay::{Replay, ReplayPlayback},
+        ChecksumHistory, SimulationTick,
+    },
+    states::match_rules::ActiveMatchRules,
+};
+
+const MATCH_UPDATES: usize = 90;
+
+fn unit_count(app: &mut App) -> usize {
+    let world = app.world_mut();
+    world.query_filtered::<(), With<Unit>>().iter(world).count()
+}
+
+// Trains, moves and attacks for player 0, then records the match. Also returns the units left and
+// the units player 0 fielded over the match.
+fn record_match() -> (Replay, usize, u32) {
+    let mut app = booted_app();
+    let headquarters = sim_ids::<With<Structure>>(&mut app, 0)[0];
+    let units = sim_ids::<With<Unit>>(&mut app, 0);
+    let enemy = sim_ids::<With<Unit>>(&mut app, 1)[0];
+    let commands = [
+        PlayerCommand::Train { structure: headquarters },
+        PlayerCommand::Train { structure: headquarters },
+        PlayerCommand::Move { units: units[..5].to_vec(), target: FixedVec2::from_vec2(Vec2::new(-10., 12.)), queued: false },
+        PlayerCommand::Attack { units: units[5..].to_vec(), target: enemy, queued: false },
+    ];
+    for command in commands {
+        issue(&mut app, 0, command);
+        app.update();
+    }
+    for _ in 0..MATCH_UPDATES {
+        app.update();
+    }
+
+    let world = app.world();
+    let replay = Replay::record(
+        &world.resource::<ActiveMatchRules>().game,
+        0,
+        world.resource::<SimulationTick>(),
+        world.resource::<ChecksumHistory>(),
+        world.resource::<CommandLog>(),
+    );
+    let units_built = world.resource::<MatchStats>().player(0).units_built;
+    (replay, unit_count(&mut app), units_built)
+}
+
+// Plays the replay back to its last tick, returning the checksum there and the units left
+fn play_back(replay: Replay) -> (Option<u64>, usize) {
+    let ticks = replay.ticks;
+    let mut app = headless_app();
+    app.insert_resource(ReplayPlayback::new(replay));
+    for _ in 0..ticks as usize + BOOT_UPDATES * 2 {
+        app.update();
+        if app.world().resource::<ReplayPlayback>().finished {
+            break;
+        }
+    }
+    assert!(app.world().resource::<ReplayPlayback>().finished, "replay did not reach its last tick");
+    let checksum = app.world().resource::<ChecksumHistory>().get(ticks);
+    (checksum, unit_count(&mut app))
+}
+
+#[test]
+fn commands_are_recorded_on_their_ticks() {
+    let (replay, _, units_built) = record_match();
+
+    let orders: Vec<&str> = replay.commands.iter().map(|timed| timed.command.name()).collect();
+    assert_eq!(orders, ["train", "train", "move", "attack"]);
+    assert!(replay.commands.windows(2).all(|pair| pair[0].tick < pair[1].tick));
+    // 10 own units and the 2 trained, however many were lost in the attack since
+    assert_eq!(units_built, 12);
+}
+
+#[test]
+fn replay_survives_serialization() {
+    let (replay, ..) = record_match();
+    let restored = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+
+    assert_eq!(restored.commands, replay.commands);
+    assert_eq!(restored.ticks, replay.ticks);
+    assert_eq!(restored.final_checksum, replay.final_checksum);
+}
+
+#[test]
+fn playback_reproduces_the_match() {
+    let (replay, units, _) = record_match();
+    let recorded = replay.final_checksum;
+    assert!(recorded.is_some());
+
+    let (checksum, played_back_units) = play_back(Replay::from_ron(&replay.to_ron().unwrap()).unwrap());
+    assert_eq!(checksum, recorded);
+    assert_eq!(played_back_units, units);
+}
+
+#[test]
+fn playback_without_commands_diverges() {
+    let (mut replay, ..) = record_match();
+    let recorded = replay.final_checksum;
+    replay.commands.clear();
+
+    let (checksum, _) = play_back(replay);
+    assert_ne!(checksum, recorded);
+}
+
+#[test]
+fn playback_leaves_the_settings_alone() {
+    let (mut replay, ..) = record_match();
+    let mut app = headless_app();
+    let population_cap = app.world().resource::<Settings>().game.population_cap;
+    replay.settings.population_cap = population_cap + 50;
+    app.insert_resource(ReplayPlayback::new(replay));
+    for _ in 0..BOOT_UPDATES * 2 {
+        app.update();
+    }
+    // Played under the recorded rules without them ending up in the settings file
+    assert_eq!(app.world().resource::<ActiveMatchRules>().game.population_cap, population_cap + 50);
+    assert_eq!(app.world().resource::<Settings>().game.population_cap, population_cap);
+}
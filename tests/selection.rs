@@ -0,0 +1,122 @@
+mod common;
+
+use avian3d::parry::math::Vector;
+use bevy::prelude::*;
+use common::Harness;
+use rts::ui::cursor::{CursorMode, CursorSelection};
+
+// Press on the ground at start, drag to end and let the selection box settle
+fn drag_selection_box(harness: &mut Harness, start: Vec3, end: Vec3) {
+    let ground = harness.ground;
+    harness.send_pointer_hit(ground, start);
+    harness.press_mouse(MouseButton::Left);
+    harness.set_cursor_mode(CursorMode::Selecting);
+    harness.send_pointer_hit(ground, end);
+    harness.update();
+}
+
+fn release_selection_box(harness: &mut Harness) {
+    harness.release_mouse(MouseButton::Left);
+    harness.set_cursor_mode(CursorMode::Idle);
+}
+
+#[test]
+fn box_spans_drag_area() {
+    let mut harness = Harness::new().with_selection_systems();
+    drag_selection_box(&mut harness, Vec3::new(-2., 0., -1.), Vec3::new(4., 0., 3.));
+
+    let selection_box = harness.selection_box().expect("pressing on the ground should spawn a selection box");
+    let start = harness.app.world().get::<CursorSelection>(harness.cursor).unwrap().start;
+    assert_eq!(start, Some(Vec2::new(-2., -1.)));
+
+    let transform = harness.app.world().get::<Transform>(selection_box).unwrap();
+    assert_eq!(transform.translation, Vec3::new(1., 0., 1.));
+    let collider = harness.app.world().get::<avian3d::prelude::Collider>(selection_box).unwrap();
+    let cuboid = collider.shape().as_cuboid().expect("selection box should be a cuboid");
+    assert_eq!(cuboid.half_extents, Vector::new(3., 500., 2.));
+}
+
+#[test]
+fn box_selects_entities_inside() {
+    let mut harness = Harness::new().with_selection_systems();
+    let inside_a = harness.spawn_selectable(Vec3::new(1., 0., 1.));
+    let inside_b = harness.spawn_selectable(Vec3::new(2., 0., 2.));
+    let outside = harness.spawn_selectable(Vec3::new(10., 0., 10.));
+
+    drag_selection_box(&mut harness, Vec3::ZERO, Vec3::new(3., 0., 3.));
+    harness.set_selection_box_contents(&[inside_a, inside_b]);
+    harness.run_frames(2);
+
+    assert!(harness.is_selected(inside_a));
+    assert!(harness.is_selected(inside_b));
+    assert!(!harness.is_selected(outside));
+
+    release_selection_box(&mut harness);
+    assert!(harness.selection_box().is_none(), "selection box should be removed when selecting ends");
+    assert_eq!(harness.selected_count(), 2);
+}
+
+#[test]
+fn box_replaces_previous_selection() {
+    let mut harness = Harness::new().with_selection_systems();
+    let previous = harness.spawn_selectable(Vec3::new(10., 0., 10.));
+    let inside = harness.spawn_selectable(Vec3::new(1., 0., 1.));
+    harness.click(previous, Vec3::new(10., 0., 10.));
+    assert!(harness.is_selected(previous));
+
+    drag_selection_box(&mut harness, Vec3::ZERO, Vec3::new(3., 0., 3.));
+    harness.set_selection_box_contents(&[inside]);
+    harness.run_frames(2);
+
+    assert!(harness.is_selected(inside));
+    assert!(!harness.is_selected(previous));
+}
+
+#[test]
+fn multiselect_box_keeps_previous_selection() {
+    let mut harness = Harness::new().with_selection_systems();
+    let previous = harness.spawn_selectable(Vec3::new(10., 0., 10.));
+    let inside = harness.spawn_selectable(Vec3::new(1., 0., 1.));
+    harness.click(previous, Vec3::new(10., 0., 10.));
+
+    harness.set_multiselect(true);
+    drag_selection_box(&mut harness, Vec3::ZERO, Vec3::new(3., 0., 3.));
+    harness.set_selection_box_contents(&[inside]);
+    harness.run_frames(2);
+
+    assert!(harness.is_selected(inside));
+    assert!(harness.is_selected(previous));
+}
+
+#[test]
+fn click_replaces_selection() {
+    let mut harness = Harness::new().with_selection_systems();
+    let first = harness.spawn_selectable(Vec3::new(1., 0., 1.));
+    let second = harness.spawn_selectable(Vec3::new(3., 0., 3.));
+
+    harness.click(first, Vec3::new(1., 0., 1.));
+    assert!(harness.is_selected(first));
+
+    harness.click(second, Vec3::new(3., 0., 3.));
+    assert!(harness.is_selected(second));
+    assert!(!harness.is_selected(first));
+    assert_eq!(harness.selected_count(), 1);
+}
+
+#[test]
+fn multiselect_click_adds_and_toggles() {
+    let mut harness = Harness::new().with_selection_systems();
+    let first = harness.spawn_selectable(Vec3::new(1., 0., 1.));
+    let second = harness.spawn_selectable(Vec3::new(3., 0., 3.));
+
+    harness.click(first, Vec3::new(1., 0., 1.));
+    harness.set_multiselect(true);
+    harness.click(second, Vec3::new(3., 0., 3.));
+    assert!(harness.is_selected(first));
+    assert!(harness.is_selected(second));
+
+    // Clicking a selected entity with multiselect held deselects just that one
+    harness.click(first, Vec3::new(1., 0., 1.));
+    assert!(!harness.is_selected(first));
+    assert!(harness.is_selected(second));
+}
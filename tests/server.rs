@@ -0,0 +1,199 @@
+mod common;
+
+use std::{collections::BTreeMap, net::SocketAddr};
+
+use bevy::prelude::*;
+use common::{headless_app, issue, run_both, start_together, CONNECT_UPDATES};
+use rts::{
+    entities::{structures::Structure, units::Unit, Owner},
+    network::{
+        client::ServerConnection,
+        server::{GameServer, ServerConfig, ServerPlugin},
+        protocol::{encoded_size, MAX_DATAGRAM_SIZE},
+        snapshot::{apply, diff, merge, split, EntityKind, EntityState, Snapshot, WorldState},
+    },
+    ai::AiPlayers,
+    resources::{player::PlayerId, settings::{AiDifficulty, Settings}},
+    simulation::{
+        commands::PlayerCommand,
+        fixed::FixedVec2,
+        SimId, SimIdAllocator,
+    },
+    states::match_rules::ActiveMatchRules,
+};
+
+const MATCH_UPDATES: usize = 120;
+// Clients show the world a few ticks behind the server
+const POSITION_TOLERANCE: f32 = 1.5;
+// Far more units than a snapshot of them can fit in one datagram
+const LARGE_WORLD_UNITS: usize = 600;
+
+fn server_app(players: u8) -> (App, SocketAddr) {
+    let mut server = headless_app();
+    let game_server = GameServer::bind(&ServerConfig {
+        port: 0,
+        players,
+    }).unwrap();
+    let address = SocketAddr::from(([127, 0, 0, 1], game_server.local_port().unwrap()));
+    server.add_plugins(ServerPlugin).insert_resource(game_server);
+    (server, address)
+}
+
+fn client_app(address: SocketAddr) -> App {
+    let mut client = headless_app();
+    client.insert_resource(ServerConnection::connect(address).unwrap());
+    client
+}
+
+// A server for one player with its client, both in the match
+fn connect() -> (App, App) {
+    let (server, address) = server_app(1);
+    let client = client_app(address);
+    start_together(server, client).expect("client did not join the match")
+}
+
+fn positions<F: bevy::ecs::query::QueryFilter>(app: &mut App, player: PlayerId) -> BTreeMap<SimId, Vec3> {
+    let world = app.world_mut();
+    world.query_filtered::<(&SimId, &Owner, &Transform), F>()
+        .iter(world)
+        .filter(|(_, owner, _)| owner.0 == player)
+        .map(|(sim_id, _, transform)| (*sim_id, transform.translation))
+        .collect()
+}
+
+#[test]
+fn client_mirrors_server_movement() {
+    let (mut server, mut client) = connect();
+    let units: Vec<SimId> = positions::<With<Unit>>(&mut client, 0).into_keys().collect();
+    let start = positions::<With<Unit>>(&mut server, 0);
+    issue(&mut client, 0, PlayerCommand::Move { units: units.clone(), target: FixedVec2::from_vec2(Vec2::new(0., -10.)), queued: false });
+    run_both(&mut server, &mut client, MATCH_UPDATES);
+    // Let the client's view catch up with where the server left the units
+    for _ in 0..20 {
+        client.update();
+    }
+
+    let on_server = positions::<With<Unit>>(&mut server, 0);
+    let on_client = positions::<With<Unit>>(&mut client, 0);
+    assert_eq!(on_server.keys().collect::<Vec<_>>(), on_client.keys().collect::<Vec<_>>());
+    assert!(units.iter().any(|sim_id| on_server[sim_id].distance(start[sim_id]) > 1.), "units did not move");
+    for (sim_id, position) in on_server.iter() {
+        let distance = position.distance(on_client[sim_id]);
+        assert!(distance < POSITION_TOLERANCE, "{:?} is {} away from the server's position", sim_id, distance);
+    }
+}
+
+#[test]
+fn trained_unit_appears_on_client() {
+    let (mut server, mut client) = connect();
+    let headquarters = *positions::<With<Structure>>(&mut client, 0).keys().next().unwrap();
+    let before = positions::<With<Unit>>(&mut client, 0).len();
+    issue(&mut client, 0, PlayerCommand::Train { structure: headquarters });
+    run_both(&mut server, &mut client, MATCH_UPDATES);
+
+    assert_eq!(positions::<With<Unit>>(&mut server, 0).len(), before + 1);
+    assert_eq!(positions::<With<Unit>>(&mut client, 0).len(), before + 1);
+    let world = client.world_mut();
+    assert_eq!(world.query_filtered::<(), With<Unit>>().iter(world).count(), 14);
+}
+
+#[test]
+fn full_server_rejects_client() {
+    let (mut server, address) = server_app(1);
+    let mut first = client_app(address);
+    let mut second = client_app(address);
+    for _ in 0..CONNECT_UPDATES {
+        server.update();
+        first.update();
+        second.update();
+    }
+
+    assert!(first.world().resource::<ServerConnection>().accepted);
+    let connection = second.world().resource::<ServerConnection>();
+    assert!(!connection.accepted);
+    assert!(connection.rejected.is_some());
+}
+
+#[test]
+fn snapshot_deltas_carry_only_changes() {
+    let state = |x: f32| EntityState::new(EntityKind::Unit, Some(0), &Transform::from_xyz(x, 0., 0.));
+    let baseline: WorldState = [(SimId(1), state(0.)), (SimId(2), state(5.)), (SimId(3), state(9.))].into();
+    let current: WorldState = [(SimId(1), state(1.)), (SimId(2), state(5.)), (SimId(4), state(3.))].into();
+
+    let (entities, removed) = diff(Some(&baseline), &current);
+    let changed: Vec<SimId> = entities.iter().map(|delta| delta.sim_id).collect();
+    assert_eq!(changed, [SimId(1), SimId(4)]);
+    assert!(entities[0].spawn.is_none() && entities[0].rotation.is_none());
+    assert!(entities[1].spawn.is_some());
+    assert_eq!(removed, [SimId(3)]);
+    assert_eq!(apply(Some(&baseline), &entities, &removed), Some(current.clone()));
+
+    // Without a baseline everything is described
+    let (entities, removed) = diff(None, &current);
+    assert_eq!(entities.len(), 3);
+    assert_eq!(apply(None, &entities, &removed), Some(current));
+}
+
+#[test]
+fn split_snapshots_fit_and_merge_back() {
+    let state = |x: f32| EntityState::new(EntityKind::Unit, Some(0), &Transform::from_xyz(x, 0., 0.));
+    let current: WorldState = (0..LARGE_WORLD_UNITS).map(|index| (SimId(index as u32), state(index as f32))).collect();
+    let (entities, removed) = diff(None, &current);
+    let snapshot = Snapshot {
+        tick: 10,
+        baseline: None,
+        command_ack: 0,
+        part: 0,
+        parts: 1,
+        entities,
+        removed,
+        stockpiles: vec![(0, 1000)],
+    };
+    assert!(encoded_size(&snapshot) > MAX_DATAGRAM_SIZE);
+
+    let parts = split(snapshot.clone(), |part| encoded_size(part) <= MAX_DATAGRAM_SIZE);
+    assert!(parts.len() > 1);
+    assert!(parts.iter().all(|part| encoded_size(part) <= MAX_DATAGRAM_SIZE && part.parts == parts.len() as u32));
+    assert_eq!(merge(parts), Some(snapshot));
+}
+
+#[test]
+fn large_worlds_reach_the_client() {
+    let (server, address) = server_app(1);
+    let client = client_app(address);
+    let (mut server, mut client) = start_together(server, client).expect("client did not join the match");
+    // Out of everyone's way, they have no bodies
+    let world = server.world_mut();
+    for index in 0..LARGE_WORLD_UNITS {
+        let sim_id = world.resource_mut::<SimIdAllocator>().next();
+        let translation = Vec3::new(-150. + (index % 30) as f32, 1., -150. + (index / 30) as f32);
+        world.spawn((sim_id, Owner(0), Unit, Transform::from_translation(translation)));
+    }
+    run_both(&mut server, &mut client, 30);
+
+    let on_server = positions::<With<Unit>>(&mut server, 0);
+    assert!(on_server.len() > LARGE_WORLD_UNITS);
+    assert_eq!(positions::<With<Unit>>(&mut client, 0).keys().collect::<Vec<_>>(), on_server.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn client_plays_under_the_server_rules() {
+    let (mut server, address) = server_app(1);
+    let population_cap = server.world().resource::<Settings>().game.population_cap;
+    server.world_mut().resource_mut::<Settings>().game.population_cap = population_cap + 50;
+    let client = client_app(address);
+    let (_server, client) = start_together(server, client).expect("client did not join the match");
+
+    assert_eq!(client.world().resource::<ActiveMatchRules>().game.population_cap, population_cap + 50);
+    assert_eq!(client.world().resource::<Settings>().game.population_cap, population_cap);
+}
+
+#[test]
+fn server_leaves_the_seats_to_clients() {
+    let (mut server, address) = server_app(1);
+    server.world_mut().resource_mut::<Settings>().game.opponent = Some(AiDifficulty::Normal);
+    let client = client_app(address);
+    let (server, _client) = start_together(server, client).expect("client did not join the match");
+
+    assert!(server.world().resource::<AiPlayers>().players.is_empty());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use bevy::prelude::*;
+use common::headless_app;
+use rts::simulation::{fixed::{FixedPoint, FixedVec2}, ChecksumHistory, SimId, SimulationTick};
+
+const UPDATES: usize = 120;
+
+fn run_updates(app: &mut App, updates: usize) {
+    for _ in 0..updates {
+        app.update();
+    }
+}
+
+fn fixed(value: f32) -> FixedPoint {
+    FixedPoint::from_num(value)
+}
+
+#[test]
+fn fixed_point_arithmetic() {
+    assert_eq!(fixed(1.5) + fixed(2.25), fixed(3.75));
+    assert_eq!(fixed(1.5) - fixed(2.25), fixed(-0.75));
+    assert_eq!(fixed(1.5) * fixed(-2.), fixed(-3.));
+    assert_eq!(fixed(3.) / fixed(4.), fixed(0.75));
+    assert_eq!(fixed(16.).sqrt(), fixed(4.));
+    assert_eq!(FixedPoint::from_int(7).to_num(), 7.);
+}
+
+#[test]
+fn fixed_point_saturates() {
+    assert_eq!(FixedPoint::MAX + FixedPoint::ONE, FixedPoint::MAX);
+    assert_eq!(FixedPoint::MIN - FixedPoint::ONE, FixedPoint::MIN);
+    assert_eq!(fixed(30000.) * fixed(30000.), FixedPoint::MAX);
+    assert_eq!(FixedPoint::ONE / FixedPoint::ZERO, FixedPoint::MAX);
+    assert_eq!(-FixedPoint::ONE / FixedPoint::ZERO, FixedPoint::MIN);
+}
+
+#[test]
+fn fixed_vec_length_across_map() {
+    let from = FixedVec2::new(fixed(-200.), fixed(-200.));
+    let to = FixedVec2::new(fixed(100.), fixed(200.));
+    assert_eq!(from.distance(to), fixed(500.));
+}
+
+#[test]
+fn fixed_vec_move_towards_lands_on_target() {
+    let target = FixedVec2::new(FixedPoint::ZERO, fixed(4.));
+    let step = FixedVec2::ZERO.move_towards(target, fixed(2.5));
+    assert_eq!(step, FixedVec2::new(FixedPoint::ZERO, fixed(2.5)));
+    assert_eq!(step.move_towards(target, fixed(2.5)), target);
+}
+
+#[test]
+fn ticks_follow_the_fixed_timestep() {
+    let mut app = headless_app();
+    run_updates(&mut app, UPDATES);
+
+    // One tick per update, less the first few spent booting into the match
+    let tick = app.world().resource::<SimulationTick>().0;
+    assert!(tick > 0 && tick <= UPDATES as u64, "unexpected tick {}", tick);
+    let history = app.world().resource::<ChecksumHistory>();
+    assert_eq!(history.latest().map(|(latest, _)| latest), Some(tick));
+}
+
+#[test]
+fn match_entities_get_unique_ids() {
+    let mut app = headless_app();
+    run_updates(&mut app, 4);
+
+    let world = app.world_mut();
+    let mut ids: Vec<SimId> = world.query::<&SimId>().iter(world).copied().collect();
+    let count = ids.len();
+    ids.sort_unstable();
+    ids.dedup();
+    assert!(count > 0);
+    assert_eq!(ids.len(), count);
+}
+
+#[test]
+fn identical_matches_have_identical_checksums() {
+    let mut first = headless_app();
+    let mut second = headless_app();
+    run_updates(&mut first, UPDATES);
+    run_updates(&mut second, UPDATES);
+
+    let first_history = first.world().resource::<ChecksumHistory>();
+    let second_history = second.world().resource::<ChecksumHistory>();
+    let (tick, checksum) = first_history.latest().expect("no ticks were simulated");
+    assert_eq!(second_history.get(tick), Some(checksum));
+}